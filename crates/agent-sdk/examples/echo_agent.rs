@@ -0,0 +1,48 @@
+use ovsy_agent_sdk::{Agent, Bytes, Event, Result, Sender, Skill, tool_args, variants};
+
+tool_args! {
+    /// The echo tool arguments
+    pub struct EchoArgs {
+        /// Text to repeat back.
+        text: String,
+        /// Letter case of the answer.
+        #[schema(variants(&["lower", "upper"]))]
+        case: Option<String>,
+        /// How many times to repeat the text.
+        times: Option<u32>,
+    }
+}
+
+async fn handle_echo(tx: Sender<Bytes>, args: EchoArgs) -> Result<()> {
+    let text = match args.case.as_deref() {
+        Some("upper") => args.text.to_uppercase(),
+        Some("lower") => args.text.to_lowercase(),
+        _ => args.text,
+    };
+
+    for _ in 0..args.times.unwrap_or(1) {
+        tx.send(Event::answer(format!("{text}\n")))?;
+    }
+
+    Ok(())
+}
+
+async fn handle_ping(tx: Sender<Bytes>) -> Result<()> {
+    tx.send(Event::answer("pong"))?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    Agent::new("echo-agent")
+        .version("0.1.0")
+        .description("Repeats the user text back.")
+        .prompt("You are the Echo agent. Use tools to repeat the requested text.")
+        .skill(
+            Skill::new("echo", "Repeating text and checking the agent health.")
+                .tool("echo", "Repeats the text back.", handle_echo)
+                .tool("ping", "Answers with `pong`.", handle_ping),
+        )
+        .run()
+        .await
+}
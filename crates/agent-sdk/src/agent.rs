@@ -0,0 +1,181 @@
+use crate::{handlers, prelude::*, skill::Skill};
+use anylm::api::Tool;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use ovsy_share::AgentMetadata;
+use pearce::Server;
+use std::collections::HashMap;
+use tokio::{sync::Mutex, task::AbortHandle};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Prints agent metadata in JSON format and exits
+    Metadata,
+    /// Runs the AI agent server
+    Serve,
+}
+
+/// The Ovsy agent builder
+pub struct Agent {
+    name: String,
+    version: String,
+    description: String,
+    prompt: String,
+    skills: Vec<Skill>,
+    /// The in-flight tool calls (by kernel call id)
+    calls: Mutex<HashMap<String, AbortHandle>>,
+}
+
+impl Agent {
+    /// Creates a new agent
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: str!("0.1.0"),
+            description: str!(),
+            prompt: str!(),
+            skills: vec![],
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the agent version
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Sets the agent description (used by the kernel planner)
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the agent system prompt
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Registers a new skill
+    pub fn skill(mut self, skill: Skill) -> Self {
+        self.skills.push(skill);
+        self
+    }
+
+    /// Returns the agent name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the agent metadata
+    pub fn metadata(&self) -> AgentMetadata {
+        AgentMetadata {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            version: self.version.clone(),
+            prompt: self.prompt.clone(),
+            skills: self.skills.iter().map(Skill::info).collect(),
+        }
+    }
+
+    /// Returns the tools list of the selected skills (or all tools)
+    pub fn tools(&self, skills: &[String]) -> Vec<Tool> {
+        self.skills
+            .iter()
+            .filter(|skill| skills.is_empty() || skills.contains(&skill.name))
+            .flat_map(Skill::tools_list)
+            .collect()
+    }
+
+    /// Returns the agent server socket path
+    pub fn sock_path(&self) -> PathBuf {
+        // the app directories are not used here, but required by the macro:
+        const APP_NAME: &str = "ovsy";
+        path!("$temp/ovsy/uds/{}.sock", self.name)
+    }
+
+    /// Calls the agent tool by name
+    pub async fn call_tool(&self, tx: Sender<Bytes>, name: &str, payload: JsonValue) -> Result<()> {
+        let tool = self
+            .skills
+            .iter()
+            .flat_map(|skill| skill.tools.iter())
+            .find(|tool| tool.name == name)
+            .ok_or_else(|| Error::UnknownTool(name.to_owned()))?;
+
+        tool.call(tx, payload).await
+    }
+
+    /// Registers the in-flight tool call
+    pub(crate) async fn add_call(&self, call_id: String, handle: AbortHandle) {
+        self.calls.lock().await.insert(call_id, handle);
+    }
+
+    /// Unregisters the completed tool call
+    pub(crate) async fn remove_call(&self, call_id: &str) {
+        self.calls.lock().await.remove(call_id);
+    }
+
+    /// Aborts the in-flight tool call (returns false if it's not found)
+    pub async fn cancel_call(&self, call_id: &str) -> bool {
+        match self.calls.lock().await.remove(call_id) {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Parses the CLI arguments and handles the subcommand
+    pub async fn run(self) -> Result<()> {
+        let command = Args::command()
+            .name(self.name.clone())
+            .version(self.version.clone())
+            .about(self.description.trim().to_owned());
+        let args = Args::from_arg_matches(&command.get_matches())?;
+
+        match args.command {
+            Commands::Metadata => {
+                let json_output = json::to_string(&self.metadata())?;
+                println!("{json_output}");
+                Ok(())
+            }
+
+            Commands::Serve => self.serve().await,
+        }
+    }
+
+    /// Runs the agent server
+    pub async fn serve(self) -> Result<()> {
+        use handlers as hands;
+
+        ovsy_share::macos_protect();
+
+        let sock = self.sock_path();
+        let agent = Arc::new(self);
+        let (list_agent, call_agent, cancel_agent) = (agent.clone(), agent.clone(), agent.clone());
+
+        Server::new()
+            //    HEALTH
+            .get("/ping", hands::handle_ping)
+            //    TOOLS
+            .post("/tools/list", move |data| {
+                hands::handle_tools_list(list_agent.clone(), data)
+            })
+            .post("/tools/call/{tool}", move |name, headers, payload| {
+                hands::handle_tool_call(call_agent.clone(), name, headers, payload)
+            })
+            .post("/tools/cancel/{call_id}", move |call_id| {
+                hands::handle_tool_cancel(cancel_agent.clone(), call_id)
+            })
+            .run(sock)
+            .await
+    }
+}
@@ -1,4 +1,3 @@
-// use crate::prelude::DynError;
 use macron::{Display, Error, From};
 
 // The error
@@ -6,4 +5,7 @@ use macron::{Display, Error, From};
 pub enum Error {
     #[display(fmt = "A non-existing tool was called: `{0}`")]
     UnknownTool(String),
+
+    #[display(fmt = "The tool call deadline has been exceeded")]
+    DeadlineExceeded,
 }
@@ -0,0 +1,84 @@
+use crate::{agent::Agent, prelude::*, tool::with_deadline};
+use chrono::{DateTime, Utc};
+
+#[derive(Deserialize)]
+pub struct ListAction {
+    #[serde(default)]
+    pub skills: Vec<String>,
+}
+
+/// API: Handles the server ping
+pub async fn handle_ping() -> Response {
+    Response::ok().text("pong")
+}
+
+/// API: Handles the tools list receiving
+#[log(skip_all)]
+pub async fn handle_tools_list(
+    agent: Arc<Agent>,
+    Json(ListAction { skills }): Json<ListAction>,
+) -> Response {
+    let tools = agent.tools(&skills);
+    Response::ok().json(&tools)
+}
+
+/// API: Handles the agent tool call
+#[log(skip_all, fields(tool = %name.0))]
+pub async fn handle_tool_call(
+    agent: Arc<Agent>,
+    name: Paths<String>,
+    headers: Headers,
+    payload: Json<JsonValue>,
+) -> Response {
+    info!("Initialized the `{}` tool handling", name.0);
+    let call_id = headers.get("X-Call-Id").map(str::to_owned);
+    let deadline = headers
+        .get("X-Deadline")
+        .and_then(|deadline| DateTime::parse_from_rfc3339(deadline).ok())
+        .map(|deadline| deadline.with_timezone(&Utc));
+
+    Response::ok().stream(async move |tx| {
+        // run the tool as a separate task, so it can be cancelled by the kernel:
+        let worker = tokio::spawn({
+            let (agent, tx) = (agent.clone(), tx.clone());
+            async move { with_deadline(deadline, agent.call_tool(tx, &name.0, payload.0)).await }
+        });
+
+        if let Some(call_id) = &call_id {
+            agent.add_call(call_id.clone(), worker.abort_handle()).await;
+        }
+
+        let result = worker.await;
+
+        if let Some(call_id) = &call_id {
+            agent.remove_call(call_id).await;
+        }
+
+        match result {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                error!("{e}");
+                tx.send(Event::error(e.to_string())).ok();
+            }
+            Err(e) if e.is_cancelled() => {
+                warn!("The tool call was cancelled");
+                tx.send(Event::cancelled()).ok();
+            }
+            Err(e) => {
+                error!("The tool call panicked: {e}");
+                tx.send(Event::error(e.to_string())).ok();
+            }
+        }
+    })
+}
+
+/// API: Cancels the in-flight tool call
+#[log(skip_all, fields(call_id = %call_id.0))]
+pub async fn handle_tool_cancel(agent: Arc<Agent>, call_id: Paths<String>) -> Response {
+    if agent.cancel_call(&call_id.0).await {
+        info!("Cancelled the tool call");
+        Response::ok().text("The tool call was cancelled")
+    } else {
+        Response::not_found().text("The tool call is not found")
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright (C) 2026 Bulat Sh. (fuderis) <synapdrake@ya.ru>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The SDK for writing Ovsy agents.
+//!
+//! An agent is built from skills, and every skill is a set of typed tool handlers:
+//!
+//! ```ignore
+//! use ovsy_agent_sdk::{Agent, Bytes, Event, Result, Sender, Skill, tool_args};
+//!
+//! tool_args! {
+//!     pub struct GreetArgs {
+//!         /// Name of the person to greet.
+//!         name: String,
+//!     }
+//! }
+//!
+//! async fn handle_greet(tx: Sender<Bytes>, args: GreetArgs) -> Result<()> {
+//!     tx.send(Event::answer(format!("Hello, {}!", args.name)))?;
+//!     Ok(())
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<()> {
+//!     Agent::new("greeter-agent")
+//!         .version("0.1.0")
+//!         .description("Greets people.")
+//!         .prompt("You are the Greeter agent.")
+//!         .skill(
+//!             Skill::new("greeting", "Greeting people by name.")
+//!                 .tool("greet", "Greets a person by name.", handle_greet),
+//!         )
+//!         .run()
+//!         .await
+//! }
+//! ```
+
+pub mod error;
+pub mod prelude;
+
+pub mod tool;
+pub use tool::{Property, ToolArgs, ToolHandler, ToolSchema, deadline, variants};
+
+pub mod skill;
+pub use skill::Skill;
+
+pub mod agent;
+pub use agent::Agent;
+
+mod handlers;
+
+pub use prelude::{DynError, Result};
+
+pub use anylm::api::{Schema, Tool};
+pub use atoman::{Receiver, Sender};
+pub use ovsy_share::{AgentMetadata, Event, EventKind};
+pub use pearce::Bytes;
+pub use serde_json as json;
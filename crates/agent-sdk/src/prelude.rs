@@ -0,0 +1,22 @@
+#![allow(unused_imports)]
+pub use crate::error::Error;
+pub use ovsy_share::Event;
+
+pub use std::result::Result as StdResult;
+pub type DynError = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub type Result<T> = StdResult<T, DynError>;
+
+pub use atoman::*;
+pub use macron::*;
+
+pub use pearce::{Bytes, Headers, Json, Paths, Response};
+
+pub use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+};
+
+pub use serde::{Deserialize, Serialize, de::DeserializeOwned};
+pub use serde_json::{self as json, Value as JsonValue, json};
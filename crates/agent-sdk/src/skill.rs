@@ -0,0 +1,61 @@
+use crate::{
+    prelude::*,
+    tool::{ToolEntry, ToolHandler},
+};
+use anylm::api::Tool;
+
+/// The agent skill (a named group of tools)
+pub struct Skill {
+    pub(crate) name: String,
+    pub(crate) description: String,
+    pub(crate) tools: Vec<ToolEntry>,
+}
+
+impl Skill {
+    /// Creates a new skill
+    pub fn new(name: impl ToString, description: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.into(),
+            tools: vec![],
+        }
+    }
+
+    /// Registers a typed tool handler (the tool schema is derived from its arguments)
+    pub fn tool<H, T>(mut self, name: impl Into<String>, description: &str, handler: H) -> Self
+    where
+        H: ToolHandler<T>,
+    {
+        let name = name.into();
+        if self.tools.iter().any(|tool| tool.name == name) {
+            warn!("The tool `{name}` is already registered, replacing it");
+            self.tools.retain(|tool| tool.name != name);
+        }
+
+        self.tools.push(ToolEntry::new(name, description, handler));
+        self
+    }
+
+    /// Registers a group of tools (e.g. from another module)
+    pub fn tools(self, register: impl FnOnce(Self) -> Self) -> Self {
+        register(self)
+    }
+
+    /// Returns the skill name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the skill tools schemas
+    pub fn tools_list(&self) -> Vec<Tool> {
+        self.tools.iter().map(|tool| tool.schema.clone()).collect()
+    }
+
+    /// Returns the skill info
+    pub fn info(&self) -> ovsy_share::Skill {
+        ovsy_share::Skill {
+            name: self.name.clone(),
+            description: self.description.clone(),
+        }
+    }
+}
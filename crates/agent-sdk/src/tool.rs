@@ -0,0 +1,304 @@
+use crate::prelude::*;
+use anylm::api::{Schema, Tool};
+use chrono::{DateTime, Utc};
+
+/// The boxed tool handler future
+pub type ToolFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+tokio::task_local! {
+    /// The deadline of the current tool call (sent by the kernel)
+    static DEADLINE: Option<DateTime<Utc>>;
+}
+
+/// Returns the deadline of the current tool call, so long-running tools can stop in time
+pub fn deadline() -> Option<DateTime<Utc>> {
+    DEADLINE.try_with(|deadline| *deadline).ok().flatten()
+}
+
+/// Runs the tool call future within the deadline (if any)
+pub(crate) async fn with_deadline<F>(deadline: Option<DateTime<Utc>>, future: F) -> Result<()>
+where
+    F: Future<Output = Result<()>>,
+{
+    DEADLINE
+        .scope(deadline, async move {
+            match deadline {
+                Some(deadline) => {
+                    let remaining = (deadline - Utc::now()).to_std().unwrap_or_default();
+                    tokio::time::timeout(remaining, future)
+                        .await
+                        .unwrap_or_else(|_| Err(Error::DeadlineExceeded.into()))
+                }
+                None => future.await,
+            }
+        })
+        .await
+}
+
+/// The tool argument property
+#[derive(Debug, Clone)]
+pub struct Property {
+    pub name: &'static str,
+    pub schema: Schema,
+    pub required: bool,
+}
+
+impl Property {
+    /// Creates a required property
+    pub fn required(name: &'static str, descr: &str, schema: Schema) -> Self {
+        Self {
+            name,
+            schema: schema.description(descr.trim()),
+            required: true,
+        }
+    }
+
+    /// Creates an optional property
+    pub fn optional(name: &'static str, descr: &str, schema: Schema) -> Self {
+        Self {
+            required: false,
+            ..Self::required(name, descr, schema)
+        }
+    }
+}
+
+/// The type with a known JSON-schema
+pub trait ToolSchema {
+    /// Returns the type schema (without description)
+    fn schema() -> Schema;
+}
+
+macro_rules! impl_tool_schema {
+    ($($ty:ty),* => $kind:ident) => {
+        $(
+            impl ToolSchema for $ty {
+                fn schema() -> Schema {
+                    Schema::$kind("")
+                }
+            }
+        )*
+    };
+}
+
+impl_tool_schema!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize => integer);
+impl_tool_schema!(f32, f64 => number);
+impl_tool_schema!(bool => boolean);
+impl_tool_schema!(String, char, std::path::PathBuf => string);
+
+impl<T: ToolSchema> ToolSchema for Vec<T> {
+    fn schema() -> Schema {
+        Schema::array("").items(T::schema())
+    }
+}
+
+impl<Tz: chrono::TimeZone> ToolSchema for chrono::DateTime<Tz> {
+    fn schema() -> Schema {
+        Schema::string("")
+    }
+}
+
+/// Creates a string schema with fixed value variants
+pub fn variants(vars: &[&str]) -> Schema {
+    Schema::string("").variants(vars.iter().map(|var| var.to_string()).collect())
+}
+
+/// The typed tool arguments
+pub trait ToolArgs: Sized + Send + 'static {
+    /// Returns the arguments properties
+    fn properties() -> Vec<Property>;
+
+    /// Parses the arguments from the tool call payload
+    fn parse(payload: JsonValue) -> Result<Self>;
+
+    /// Builds the tool schema
+    fn tool(name: &str, descr: &str) -> Tool {
+        Self::properties()
+            .into_iter()
+            .fold(Tool::new(name, descr), |tool, prop| {
+                tool.property(prop.name, prop.schema, prop.required)
+            })
+    }
+}
+
+impl ToolArgs for () {
+    fn properties() -> Vec<Property> {
+        vec![]
+    }
+
+    fn parse(_payload: JsonValue) -> Result<Self> {
+        Ok(())
+    }
+}
+
+/// The typed tool handler: `async fn(Sender<Bytes>) -> Result<()>` or `async fn(Sender<Bytes>, Args) -> Result<()>`
+pub trait ToolHandler<T>: Send + Sync + 'static {
+    type Args: ToolArgs;
+
+    /// Calls the handler with parsed arguments
+    fn call(&self, tx: Sender<Bytes>, args: Self::Args) -> ToolFuture;
+}
+
+impl<F, Fut> ToolHandler<()> for F
+where
+    F: Fn(Sender<Bytes>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    type Args = ();
+
+    fn call(&self, tx: Sender<Bytes>, _args: ()) -> ToolFuture {
+        Box::pin(self(tx))
+    }
+}
+
+impl<F, Fut, A> ToolHandler<(A,)> for F
+where
+    F: Fn(Sender<Bytes>, A) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+    A: ToolArgs,
+{
+    type Args = A;
+
+    fn call(&self, tx: Sender<Bytes>, args: A) -> ToolFuture {
+        Box::pin(self(tx, args))
+    }
+}
+
+/// The registered agent tool
+pub(crate) struct ToolEntry {
+    pub name: String,
+    pub schema: Tool,
+    handler: Box<dyn Fn(Sender<Bytes>, JsonValue) -> ToolFuture + Send + Sync>,
+}
+
+impl ToolEntry {
+    /// Creates a new tool entry from the typed handler
+    pub fn new<H, T>(name: String, descr: &str, handler: H) -> Self
+    where
+        H: ToolHandler<T>,
+    {
+        let schema = H::Args::tool(&name, descr);
+        let handler = Arc::new(handler);
+
+        Self {
+            name,
+            schema,
+            handler: Box::new(move |tx, payload| {
+                let handler = handler.clone();
+                Box::pin(async move {
+                    let args = H::Args::parse(payload)?;
+                    handler.call(tx, args).await
+                })
+            }),
+        }
+    }
+
+    /// Calls the tool with a raw JSON payload
+    pub fn call(&self, tx: Sender<Bytes>, payload: JsonValue) -> ToolFuture {
+        (self.handler)(tx, payload)
+    }
+}
+
+/// Declares the tool arguments struct and derives its schema from the field types and doc comments.
+///
+/// Every field must start with a doc comment (used as the property description). `Option<T>` fields
+/// become optional properties. Types without a [`ToolSchema`] implementation need an explicit schema
+/// via `#[schema(...)]` placed right after the doc comment. The agent crate must depend on `serde`:
+///
+/// ```ignore
+/// tool_args! {
+///     pub struct ThemeAction {
+///         /// Target theme style.
+///         #[schema(ovsy_agent_sdk::variants(&["light", "dark"]))]
+///         style: ThemeStyle,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! tool_args {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident { $($body:tt)* }
+    ) => {
+        $crate::tool_args!(@munch [$(#[$meta])* $vis struct $name] [] [] $($body)* ,);
+    };
+
+    // skip the fields separators
+    (@munch $head:tt $fields:tt $props:tt , $($rest:tt)*) => {
+        $crate::tool_args!(@munch $head $fields $props $($rest)*);
+    };
+
+    // optional field with explicit schema
+    (@munch $head:tt [$($fields:tt)*] [$($props:tt)*]
+        #[doc = $descr:literal] #[schema($schema:expr)] $(#[$fmeta:meta])*
+        $fvis:vis $field:ident : Option<$ty:ty> , $($rest:tt)*
+    ) => {
+        $crate::tool_args!(@munch $head
+            [$($fields)* #[doc = $descr] $(#[$fmeta])* $fvis $field: Option<$ty>,]
+            [$($props)* $crate::Property::optional(stringify!($field), $descr, $schema),]
+            $($rest)*
+        );
+    };
+
+    // required field with explicit schema
+    (@munch $head:tt [$($fields:tt)*] [$($props:tt)*]
+        #[doc = $descr:literal] #[schema($schema:expr)] $(#[$fmeta:meta])*
+        $fvis:vis $field:ident : $ty:ty , $($rest:tt)*
+    ) => {
+        $crate::tool_args!(@munch $head
+            [$($fields)* #[doc = $descr] $(#[$fmeta])* $fvis $field: $ty,]
+            [$($props)* $crate::Property::required(stringify!($field), $descr, $schema),]
+            $($rest)*
+        );
+    };
+
+    // optional field
+    (@munch $head:tt [$($fields:tt)*] [$($props:tt)*]
+        #[doc = $descr:literal] $(#[$fmeta:meta])*
+        $fvis:vis $field:ident : Option<$ty:ty> , $($rest:tt)*
+    ) => {
+        $crate::tool_args!(@munch $head
+            [$($fields)* #[doc = $descr] $(#[$fmeta])* $fvis $field: Option<$ty>,]
+            [$($props)* $crate::Property::optional(
+                stringify!($field),
+                $descr,
+                <$ty as $crate::ToolSchema>::schema(),
+            ),]
+            $($rest)*
+        );
+    };
+
+    // required field
+    (@munch $head:tt [$($fields:tt)*] [$($props:tt)*]
+        #[doc = $descr:literal] $(#[$fmeta:meta])*
+        $fvis:vis $field:ident : $ty:ty , $($rest:tt)*
+    ) => {
+        $crate::tool_args!(@munch $head
+            [$($fields)* #[doc = $descr] $(#[$fmeta])* $fvis $field: $ty,]
+            [$($props)* $crate::Property::required(
+                stringify!($field),
+                $descr,
+                <$ty as $crate::ToolSchema>::schema(),
+            ),]
+            $($rest)*
+        );
+    };
+
+    // generate the struct && schema
+    (@munch [$(#[$meta:meta])* $vis:vis struct $name:ident] [$($fields:tt)*] [$($props:tt)*]) => {
+        $(#[$meta])*
+        #[derive(::serde::Deserialize)]
+        $vis struct $name {
+            $($fields)*
+        }
+
+        impl $crate::ToolArgs for $name {
+            fn properties() -> Vec<$crate::Property> {
+                vec![$($props)*]
+            }
+
+            fn parse(payload: $crate::json::Value) -> $crate::Result<Self> {
+                Ok($crate::json::from_value(payload)?)
+            }
+        }
+    };
+}
@@ -0,0 +1,224 @@
+use ovsy_agent_sdk::{
+    Agent, Bytes, Event, EventKind, Result, Sender, Skill, ToolArgs, json, tool_args, variants,
+};
+use pearce::{Client, StreamExt};
+use serde_json::{Value as JsonValue, json};
+use std::time::Duration;
+
+tool_args! {
+    pub struct GreetArgs {
+        /// Name of the person to greet.
+        name: String,
+        /// Number of greetings.
+        times: Option<u32>,
+        /// Greeting style.
+        #[schema(variants(&["formal", "casual"]))]
+        style: Option<String>,
+        /// Extra words.
+        words: Vec<String>,
+    }
+}
+
+tool_args! {
+    pub struct WaitArgs {
+        /// Wait duration in milliseconds.
+        ms: u64,
+    }
+}
+
+async fn handle_greet(tx: Sender<Bytes>, args: GreetArgs) -> Result<()> {
+    let greeting = match args.style.as_deref() {
+        Some("formal") => "Good day",
+        _ => "Hello",
+    };
+    for _ in 0..args.times.unwrap_or(1) {
+        tx.send(Event::answer(format!(
+            "{greeting}, {}{}!",
+            args.name,
+            args.words.join(" ")
+        )))?;
+    }
+    Ok(())
+}
+
+async fn handle_ping(tx: Sender<Bytes>) -> Result<()> {
+    tx.send(Event::answer("pong"))?;
+    Ok(())
+}
+
+async fn handle_wait(tx: Sender<Bytes>, args: WaitArgs) -> Result<()> {
+    tokio::time::sleep(Duration::from_millis(args.ms)).await;
+    tx.send(Event::answer("waited"))?;
+    Ok(())
+}
+
+/// Builds the test agent
+fn test_agent(name: &str) -> Agent {
+    Agent::new(name)
+        .description("The test agent.")
+        .skill(
+            Skill::new("greeting", "Greeting people.")
+                .tool("greet", "Greets a person.", handle_greet)
+                .tool("ping", "Answers the ping.", handle_ping),
+        )
+        .skill(Skill::new("waiting", "Waiting.").tool("wait", "Waits a bit.", handle_wait))
+}
+
+/// Calls the agent tool, returns the sent events
+async fn call(agent: &Agent, tool: &str, payload: JsonValue) -> Result<Vec<Event>> {
+    let (tx, mut rx) = atoman::unbounded_channel::<Bytes>();
+    agent.call_tool(tx, tool, payload).await?;
+
+    let mut events = vec![];
+    while let Some(bytes) = rx.recv().await? {
+        events.push(json::from_slice(&bytes)?);
+    }
+    Ok(events)
+}
+
+/// Returns the answer events texts
+fn answers(events: &[Event]) -> Vec<&str> {
+    events
+        .iter()
+        .filter(|event| event.kind == EventKind::Answer)
+        .map(|event| event.text.as_str())
+        .collect()
+}
+
+#[test]
+fn tool_args_schema() {
+    let mut tool = json::to_value(GreetArgs::tool("greet", "Greets a person.")).unwrap();
+
+    // the variants are unordered:
+    let variants = tool["properties"]["style"]["variants"]
+        .as_array_mut()
+        .unwrap();
+    variants.sort_by_key(|var| var.to_string());
+
+    // the doc comments describe the properties, `Option<T>` fields are optional:
+    assert_eq!(
+        tool,
+        json!({
+            "name": "greet",
+            "description": "Greets a person.",
+            "properties": {
+                "name": {
+                    "type": "string",
+                    "description": "Name of the person to greet.",
+                    "optional": false,
+                },
+                "times": {
+                    "type": "integer",
+                    "description": "Number of greetings.",
+                    "optional": true,
+                },
+                "style": {
+                    "type": "string",
+                    "description": "Greeting style.",
+                    "variants": ["casual", "formal"],
+                    "optional": true,
+                },
+                "words": {
+                    "type": "array",
+                    "description": "Extra words.",
+                    "items": { "type": "string" },
+                    "optional": false,
+                },
+            },
+        })
+    );
+}
+
+#[tokio::test]
+async fn tool_calls_are_routed() {
+    let agent = test_agent("sdk-routing-agent");
+
+    // the tools are listed by skills:
+    let names = |skills: &[String]| {
+        agent
+            .tools(skills)
+            .into_iter()
+            .map(|tool| json::to_value(tool).unwrap()["name"].clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&[]), ["greet", "ping", "wait"]);
+    assert_eq!(names(&["waiting".into()]), ["wait"]);
+
+    // the arguments are parsed to the typed handler:
+    let events = call(
+        &agent,
+        "greet",
+        json!({ "name": "Bob", "times": 2, "words": [] }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(answers(&events), ["Hello, Bob!", "Hello, Bob!"]);
+
+    let events = call(&agent, "ping", json!({})).await.unwrap();
+    assert_eq!(answers(&events), ["pong"]);
+
+    // the unknown tool & the bad arguments are errors:
+    let err = call(&agent, "shout", json!({})).await.unwrap_err();
+    assert_eq!(err.to_string(), "A non-existing tool was called: `shout`");
+
+    let err = call(&agent, "greet", json!({ "times": 1 }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("missing field `name`"), "{err}");
+
+    let err = call(&agent, "wait", json!({ "ms": "soon" }))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("invalid type"), "{err}");
+}
+
+#[tokio::test]
+async fn tool_calls_are_cancelled() {
+    let agent = test_agent(&format!("sdk-cancel-agent-{}", std::process::id()));
+    let sock_path = agent.sock_path();
+    tokio::spawn(agent.serve());
+
+    // wait for the server to start:
+    let client = Client::ipc(&sock_path.to_string_lossy());
+    let mut started = false;
+    for _ in 0..50 {
+        if let Ok(res) = client.get("/ping").send().await
+            && res.status().is_success()
+        {
+            started = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(started, "the agent server isn't started");
+
+    let mut stream = client
+        .post("/tools/call/wait")
+        .header("Content-Type", "application/json")
+        .header("X-Call-Id", "call-1")
+        .json(&json!({ "ms": 30_000 }))
+        .stream::<Event>()
+        .await
+        .unwrap();
+
+    // the in-flight call is aborted by its id:
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let res = client.post("/tools/cancel/call-1").send().await.unwrap();
+    assert!(res.status().is_success());
+    assert_eq!(res.text().await.unwrap(), "The tool call was cancelled");
+
+    let mut events = vec![];
+    while let Some(event) = tokio::time::timeout(Duration::from_secs(5), stream.recv())
+        .await
+        .unwrap()
+        .unwrap()
+    {
+        events.push(event);
+    }
+    assert_eq!(events.len(), 1, "{events:?}");
+    assert_eq!(events[0].kind, EventKind::Cancelled);
+
+    // the completed or unknown call isn't found:
+    let res = client.post("/tools/cancel/call-1").send().await.unwrap();
+    assert_eq!(res.status(), 404);
+}
@@ -0,0 +1,80 @@
+//! The fake agent used by the kernel integration tests (installed as `ovsy-fake-agent`).
+
+use ovsy_agent_sdk::{Agent, Bytes, Event, Result, Sender, Skill, tool_args};
+use std::time::Duration;
+
+tool_args! {
+    /// The echo tool arguments
+    pub struct EchoArgs {
+        /// Text to repeat back.
+        text: String,
+    }
+}
+
+tool_args! {
+    /// The sleep tool arguments
+    pub struct SleepArgs {
+        /// Sleep duration in milliseconds.
+        ms: u64,
+    }
+}
+
+tool_args! {
+    /// The fail tool arguments
+    pub struct FailArgs {
+        /// The error message.
+        message: String,
+    }
+}
+
+async fn handle_echo(tx: Sender<Bytes>, args: EchoArgs) -> Result<()> {
+    // the worker output is captured into the agent log:
+    println!("echo: {}", args.text);
+    tx.send(Event::answer(args.text))?;
+    Ok(())
+}
+
+async fn handle_note(tx: Sender<Bytes>, args: EchoArgs) -> Result<()> {
+    tx.send(Event::answer(format!("noted: {}", args.text)))?;
+    Ok(())
+}
+
+async fn handle_sleep(tx: Sender<Bytes>, args: SleepArgs) -> Result<()> {
+    tokio::time::sleep(Duration::from_millis(args.ms)).await;
+    tx.send(Event::answer(format!("slept {}ms", args.ms)))?;
+    Ok(())
+}
+
+async fn handle_fail(_tx: Sender<Bytes>, args: FailArgs) -> Result<()> {
+    Err(args.message.into())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // the copies installed under other names are the distinct agents:
+    let exe = std::env::current_exe()?;
+    let name = exe
+        .file_stem()
+        .and_then(|stem| stem.to_str()?.strip_prefix("ovsy-"))
+        .unwrap_or("fake-agent")
+        .to_owned();
+    let description = format!("The {} for tests.", name.replace('-', " "));
+
+    Agent::new(&name)
+        .version("0.1.0")
+        .description(description)
+        .prompt("You are the Fake agent.")
+        .skill(
+            Skill::new("testing", "Echoing, sleeping and failing on demand.")
+                .tool("echo", "Repeats the text back.", handle_echo)
+                .tool("sleep", "Sleeps for the duration.", handle_sleep)
+                .tool("fail", "Fails with the message.", handle_fail),
+        )
+        .skill(Skill::new("notes", "Taking the text notes.").tool(
+            "note",
+            "Saves the text note.",
+            handle_note,
+        ))
+        .run()
+        .await
+}
@@ -4,5 +4,7 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Eq, PartialEq)]
 pub enum ChatAction {
     Query(String),
+    /// Regenerates the last answer (with the edited prompt, if given)
+    Regenerate(Option<String>),
     Cancel,
 }
@@ -34,6 +34,10 @@ impl AppState {
     pub fn new(session_id: SessionId, tx: UnboundedSender<ChatAction>) -> Self {
         let commands = vec![
             ("/new", "Create a new empty session"),
+            ("/retry", "Regenerate the last answer"),
+            ("/edit", "Edit the last prompt and regenerate"),
+            ("/fork", "Fork the session (optionally at a message index)"),
+            ("/search", "Search the past conversations"),
             ("/compact", "Compress the dialog context"),
             ("/clear", "Clear the dialog context"),
             ("/cancel", "Cancel the query handling"),
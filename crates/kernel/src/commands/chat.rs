@@ -1,6 +1,7 @@
 use super::{error, info, success};
 use crate::{
     chat::{self, AppState, ChatAction},
+    context,
     prelude::*,
 };
 
@@ -10,7 +11,10 @@ use crossterm::{
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use ovsy_share::{CompactQuery, Event, EventKind, HandleQuery, SessionId, UserSessionsQuery};
+use ovsy_share::{
+    CompactQuery, Event, EventKind, HandleQuery, HistoryHit, RegenerateQuery, SessionId,
+    SessionSummary, UserSessionsQuery,
+};
 use ratatui::{
     Terminal,
     backend::{Backend, CrosstermBackend},
@@ -24,7 +28,7 @@ use tokio::{
 };
 
 const FRAME_TIME: Duration = Duration::from_millis(33); // ~30 FPS
-const USER_ID: u128 = 0;
+pub const USER_ID: u128 = 0;
 
 /// Handles the CLI chat
 pub async fn handle_chat() -> Result<()> {
@@ -64,7 +68,7 @@ pub async fn handle_chat() -> Result<()> {
 
     // load last session or create new:
     let mut session_id = SessionId::new(USER_ID);
-    let sessions_query = UserSessionsQuery::new(1); // limit: 1
+    let sessions_query = UserSessionsQuery::new(1); // the last active session
     let base_url = str!("http://127.0.0.1:{port}");
     let sessions_url = str!("{base_url}/users/{USER_ID}/sessions");
 
@@ -74,10 +78,10 @@ pub async fn handle_chat() -> Result<()> {
         .send()
         .await
     {
-        if let Ok(active_sessions) = res.json::<Vec<SessionId>>().await
+        if let Ok(active_sessions) = res.json::<Vec<SessionSummary>>().await
             && let Some(last_session) = active_sessions.into_iter().next()
         {
-            session_id = last_session;
+            session_id = last_session.id;
         }
     }
 
@@ -300,8 +304,29 @@ async fn handle_input(app: &mut AppState) {
 
     // handle input:
     if trimmed.starts_with('/') {
-        match trimmed {
-            "/exit" => {}
+        let (command, arg) = trimmed.split_once(' ').unwrap_or((trimmed, ""));
+
+        match (command, arg.trim()) {
+            ("/exit", _) => {}
+            ("/retry", _) => regenerate(app, None).await,
+            // the last prompt is put to input for editing:
+            ("/edit", "") => {
+                let msgs = app.messages.lock().await;
+                let prompt = msgs
+                    .messages
+                    .iter()
+                    .rfind(|msg| msg.role.is_user())
+                    .and_then(context::extract_text_from_msg);
+                drop(msgs);
+
+                app.is_busy = false;
+                if let Some(prompt) = prompt {
+                    app.input = str!("/edit {prompt}");
+                    app.input_cursor = app.input.len();
+                }
+                return;
+            }
+            ("/edit", prompt) => regenerate(app, Some(prompt.to_owned())).await,
             _ => {
                 let _ = app.tx.send(ChatAction::Query(trimmed.into()));
             }
@@ -328,6 +353,100 @@ async fn handle_input(app: &mut AppState) {
     app.chat_scroll = u16::MAX;
 }
 
+/// Replaces the last answer (and the prompt, if edited) with the regenerated one
+async fn regenerate(app: &mut AppState, prompt: Option<String>) {
+    let mut msgs = app.messages.lock().await;
+    let Some(user_idx) = msgs.messages.iter().rposition(|msg| msg.role.is_user()) else {
+        app.is_busy = false;
+        return;
+    };
+
+    msgs.messages.truncate(user_idx + 1);
+    if let Some(prompt) = &prompt {
+        msgs.messages[user_idx] = Message::user(vec![prompt.as_str().into()]);
+    }
+    msgs.add_message(Message::assistant(vec![], vec![]));
+    msgs.count_tokens();
+    app.response_index = msgs.messages.len() - 1;
+    app.cycles = 0;
+
+    let _ = app.tx.send(ChatAction::Regenerate(prompt));
+}
+
+/// Searches the user conversations history
+async fn search_history(
+    client: &Client,
+    base_url: &str,
+    query: &str,
+) -> StdResult<Vec<HistoryHit>, String> {
+    if query.is_empty() {
+        return Err(str!("the search text is empty"));
+    }
+    let url =
+        reqwest::Url::parse_with_params(&str!("{base_url}/users/{USER_ID}/search"), [("q", query)])
+            .map_err(|e| str!(e))?;
+
+    match client.get(url.as_str()).send().await {
+        Ok(res) if res.status().is_success() => res.json().await.map_err(|e| str!(e)),
+        Ok(res) => Err(res.text().await.unwrap_or_default()),
+        Err(e) => Err(str!(e)),
+    }
+}
+
+/// Renders the history search results as the Markdown list
+fn format_hits(query: &str, hits: &[HistoryHit]) -> String {
+    if hits.is_empty() {
+        return str!("**History search:** nothing found for `{query}`");
+    }
+
+    let mut text = str!("**History search:** `{query}`\n");
+    for (i, hit) in hits.iter().enumerate() {
+        let title = hit
+            .title
+            .clone()
+            .unwrap_or(str!("Session {}", hit.session_id));
+        let date = hit
+            .timestamp
+            .map(|at| {
+                at.with_timezone(&Local)
+                    .format(" · %Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        text.push_str(&str!(
+            "\n{}. **{title}** · {}{date}\n   {}\n",
+            i + 1,
+            hit.role,
+            hit.text
+        ));
+    }
+
+    text
+}
+
+/// Streams the query events to the UI
+fn stream_query<T>(url: String, data: T, ui_tx: mpsc::UnboundedSender<Event>) -> JoinHandle<()>
+where
+    T: Serialize + Send + 'static,
+{
+    tokio::spawn(async move {
+        let res = Client::tcp().post(&url).json(&data).stream::<Event>().await;
+
+        match res {
+            Ok(mut stream) => {
+                while let Ok(Some(chunk)) = stream.recv().await {
+                    let _ = ui_tx.send(chunk);
+                }
+            }
+            Err(e) => {
+                let _ = ui_tx.send(Event::error(str!("Connection error: {}", e)));
+            }
+        }
+
+        let _ = ui_tx.send(Event::finish());
+    })
+}
+
 /// A worker for networking
 async fn chat_worker(
     session_id: Arc<State<SessionId>>,
@@ -372,7 +491,14 @@ async fn chat_worker(
             ChatAction::Cancel => {
                 if let Some(task) = current_task.take() {
                     task.abort();
-                    let _ = ui_tx.send(Event::finish());
+
+                    // cancel the query handling on the server side:
+                    let _ = client
+                        .post(&str!("{base_url}/sessions/{session_id}/cancel"))
+                        .send()
+                        .await;
+
+                    let _ = ui_tx.send(Event::cancelled());
                 }
                 continue;
             }
@@ -409,6 +535,69 @@ async fn chat_worker(
                             let _ = ui_tx.send(Event::finish());
                         }
 
+                        "/fork" => {
+                            let at = args.get(1).and_then(|i| i.trim().parse::<usize>().ok());
+                            let fork_url = match at {
+                                Some(at) => str!("{base_url}/sessions/{session_id}/fork?at={at}"),
+                                None => str!("{base_url}/sessions/{session_id}/fork"),
+                            };
+
+                            let forked = match client.post(&fork_url).send().await {
+                                Ok(res) if res.status().is_success() => {
+                                    res.json::<SessionSummary>().await.map_err(|e| str!(e))
+                                }
+                                Ok(res) => Err(res.text().await.unwrap_or_default()),
+                                Err(e) => Err(str!(e)),
+                            };
+
+                            match forked {
+                                Ok(summary) => {
+                                    // switching to the forked session:
+                                    session_id.set(summary.id).await;
+
+                                    let session_info = get_session_info();
+                                    if let Ok(res) = client
+                                        .post(&str!("{base_url}/sessions/{}/init", summary.id))
+                                        .json(&session_info)
+                                        .send()
+                                        .await
+                                        && let Ok(history) = res.json::<Vec<Message>>().await
+                                    {
+                                        let mut msgs = messages.lock().await;
+                                        msgs.messages = history;
+                                        msgs.count_tokens();
+                                        msgs.sync();
+                                    }
+                                }
+                                Err(e) => {
+                                    let _ = ui_tx.send(Event::error(str!(
+                                        "Failed to fork the session: {e}"
+                                    )));
+                                }
+                            }
+                        }
+
+                        "/search" => {
+                            let query = trimmed.split_once(' ').map_or("", |(_, q)| q.trim());
+
+                            match search_history(&client, &base_url, query).await {
+                                Ok(hits) => {
+                                    // the results are shown locally (they aren't a part of the history):
+                                    let mut msgs = messages.lock().await;
+                                    msgs.add_message(Message::assistant(
+                                        vec![format_hits(query, &hits).into()],
+                                        vec![],
+                                    ));
+                                    msgs.sync();
+                                }
+                                Err(e) => {
+                                    let _ = ui_tx.send(Event::error(str!(
+                                        "Failed to search the history: {e}"
+                                    )));
+                                }
+                            }
+                        }
+
                         "/clear" | "/clean" => {
                             let ui_tx = ui_tx.clone();
                             let base_url = base_url.clone();
@@ -503,31 +692,31 @@ async fn chat_worker(
                 };
 
                 if let Some(msg) = message_to_send {
-                    let base_url = base_url.clone();
-                    let ui_tx = ui_tx.clone();
-                    let session_id = session_id.clone();
-
-                    current_task = Some(tokio::spawn(async move {
-                        let res = Client::tcp()
-                            .post(&str!("{base_url}/sessions/{session_id}/query"))
-                            .json(&HandleQuery::new(msg))
-                            .stream::<Event>()
-                            .await;
-
-                        match res {
-                            Ok(mut stream) => {
-                                while let Ok(Some(chunk)) = stream.recv().await {
-                                    let _ = ui_tx.send(chunk);
-                                }
-                            }
-                            Err(e) => {
-                                let _ = ui_tx.send(Event::error(str!("Connection error: {}", e)));
-                            }
-                        }
+                    current_task = Some(stream_query(
+                        str!("{base_url}/sessions/{session_id}/query"),
+                        HandleQuery::new(msg),
+                        ui_tx.clone(),
+                    ));
+                }
+            }
 
-                        let _ = ui_tx.send(Event::finish());
-                    }));
+            ChatAction::Regenerate(prompt) => {
+                if let Some(task) = current_task.take() {
+                    task.abort();
+
+                    // the running query must be cancelled before its turn is regenerated:
+                    let _ = client
+                        .post(&str!("{base_url}/sessions/{session_id}/cancel"))
+                        .send()
+                        .await;
                 }
+
+                let message = prompt.map(|prompt| Message::user(vec![prompt.into()]));
+                current_task = Some(stream_query(
+                    str!("{base_url}/sessions/{session_id}/regenerate"),
+                    RegenerateQuery::new(message),
+                    ui_tx.clone(),
+                ));
             }
         }
     }
@@ -595,6 +784,34 @@ async fn handle_event(app: &mut AppState, msgs: &mut StateGuard<Messages>, event
             }
         }
 
+        EventKind::Cancelled => {
+            app.status.take();
+            app.is_busy = false;
+        }
+
+        EventKind::Cycle => {
+            let cycle: JsonValue = serde_json::from_str(&text).unwrap_or_default();
+            let (cycle, max_cycles) = (&cycle["cycle"], &cycle["max_cycles"]);
+
+            // the first cycle is the regular planning:
+            if cycle.as_u64().is_some_and(|cycle| cycle > 1) {
+                app.status.replace(match max_cycles.as_u64() {
+                    Some(max) if max > 0 => str!("Self-correction cycle {cycle}/{max}..."),
+                    _ => str!("Self-correction cycle {cycle}..."),
+                });
+            }
+        }
+
+        EventKind::Compacted => {
+            let compacted: JsonValue = serde_json::from_str(&text).unwrap_or_default();
+            let (before, after) = (&compacted["tokens_before"], &compacted["tokens_after"]);
+
+            app.status.replace(match compacted["mode"].as_str() {
+                Some("trim") => str!("Long tool outputs trimmed ({before} -> {after} tokens)"),
+                _ => str!("Context compacted ({before} -> {after} tokens)"),
+            });
+        }
+
         EventKind::Error => {
             let err_msg = str!("Error: {text}");
 
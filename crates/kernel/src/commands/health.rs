@@ -1,7 +1,7 @@
 use super::*;
 use crate::prelude::*;
 
-use ovsy_share::{AgentMetadata, StatusData};
+use ovsy_share::{AgentStatus, StatusData};
 use tokio::process::Command;
 
 /// API: Handles the server refreshing (hot-reload)
@@ -71,17 +71,42 @@ pub async fn handle_status() -> Result<()> {
                 .map_err(|e| str!("Failed to parse response: {e}"))?;
 
             match data {
-                StatusData::Success { agents } => {
+                StatusData::Success {
+                    agents,
+                    open_sessions,
+                } => {
+                    info("Open sessions", &open_sessions.to_string());
                     info("Agents", "");
 
                     if agents.is_empty() {
                         warn("No agents loaded");
                     } else {
-                        for AgentMetadata {
-                            name, description, ..
+                        for AgentStatus {
+                            name,
+                            description,
+                            resident,
+                            quarantined,
+                            crashes,
+                            ..
                         } in agents
                         {
-                            item(&name, &description.trim());
+                            let state = if quarantined {
+                                "quarantined".red()
+                            } else if resident {
+                                "resident".green()
+                            } else {
+                                "dormant".dim()
+                            };
+                            item(&name, &str!("[{state}] {}", description.trim()));
+
+                            if let Some(crash) = crashes.last() {
+                                warn(&str!(
+                                    "Crashes: {} (last at {}: {})",
+                                    crashes.len(),
+                                    crash.time.format("%Y-%m-%d %H:%M:%S"),
+                                    crash.reason
+                                ));
+                            }
                         }
                     }
                 }
@@ -0,0 +1,93 @@
+use super::*;
+use crate::{manager::AgentLog, prelude::*};
+
+use std::io::SeekFrom;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+    time::{Duration, sleep},
+};
+
+/// API: Prints the kernel (or agent) logs
+pub async fn handle_logs(agent: Option<String>, lines: usize, follow: bool) -> Result<()> {
+    let path = match &agent {
+        Some(name) => AgentLog::path(name.strip_prefix("ovsy-").unwrap_or(name)),
+        None => latest_kernel_log()
+            .await?
+            .ok_or_else(|| str!("No kernel logs found"))?,
+    };
+
+    if !path.exists() {
+        return Err(str!("No logs found at: {}", path.display()).into());
+    }
+
+    section(&str!("Logs of {}", agent.as_deref().unwrap_or("kernel")));
+    info("Path", &path.display().to_string().white().to_string());
+    println!();
+
+    // print the last lines:
+    let content = fs::read(&path).await?;
+    let text = String::from_utf8_lossy(&content);
+    let all_lines = text.lines().collect::<Vec<_>>();
+    for line in &all_lines[all_lines.len().saturating_sub(lines)..] {
+        println!("{line}");
+    }
+
+    if follow {
+        follow_file(&path, content.len() as u64).await?;
+    }
+
+    Ok(())
+}
+
+/// Prints the new lines appended to the log file
+async fn follow_file(path: &Path, mut offset: u64) -> Result<()> {
+    loop {
+        sleep(Duration::from_millis(250)).await;
+
+        let Ok(metadata) = fs::metadata(path).await else {
+            continue;
+        };
+
+        // the log file was rotated:
+        if metadata.len() < offset {
+            offset = 0;
+        }
+
+        if metadata.len() > offset {
+            let mut file = fs::File::open(path).await?;
+            file.seek(SeekFrom::Start(offset)).await?;
+
+            let mut buf = vec![];
+            file.read_to_end(&mut buf).await?;
+            offset += buf.len() as u64;
+
+            print!("{}", String::from_utf8_lossy(&buf));
+        }
+    }
+}
+
+/// Returns the latest kernel log file
+async fn latest_kernel_log() -> Result<Option<PathBuf>> {
+    let logs_dir = path!("$state$/logs");
+    if !logs_dir.exists() {
+        return Ok(None);
+    }
+
+    let mut latest = None;
+    let mut entries = fs::read_dir(logs_dir).await?;
+
+    // the log file names start with the creation datetime:
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "log")
+            && latest
+                .as_ref()
+                .is_none_or(|latest: &PathBuf| path > *latest)
+        {
+            latest = Some(path);
+        }
+    }
+
+    Ok(latest)
+}
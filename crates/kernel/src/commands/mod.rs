@@ -1,6 +1,8 @@
 pub mod chat;
 pub mod health;
+pub mod logs;
 pub mod server;
+pub mod session;
 
 use crossterm::style::Stylize;
 
@@ -0,0 +1,149 @@
+use super::*;
+use crate::{commands::chat::USER_ID, prelude::*};
+
+use ovsy_share::{PruneReason, PrunedSession, SessionExport, SessionId, SessionSummary};
+use tokio::fs;
+
+/// API: Handles the session history export (printed if no output file is given)
+pub async fn handle_export(sid: SessionId, format: String, output: Option<PathBuf>) -> Result<()> {
+    let port = Settings::get().server.port;
+
+    let response = Client::tcp()
+        .get(&str!(
+            "http://127.0.0.1:{port}/sessions/{sid}/export?format={format}"
+        ))
+        .send()
+        .await
+        .map_err(|_| str!("Server is offline"))?;
+
+    if !response.status().is_success() {
+        return Err(response.text().await?.into());
+    }
+    let body = response.text().await?;
+
+    match output {
+        Some(path) => {
+            fs::write(&path, body).await?;
+
+            section("Exporting Session");
+            success(&str!("Session {sid} exported to {}", path.display()));
+            println!();
+        }
+        None => print!("{body}"),
+    }
+
+    Ok(())
+}
+
+/// API: Handles the session history import from the JSON export file
+pub async fn handle_import(file: PathBuf) -> Result<()> {
+    let port = Settings::get().server.port;
+
+    let data: SessionExport = json::from_str(&fs::read_to_string(&file).await?)
+        .map_err(|e| str!("Failed to parse the session export: {e}"))?;
+
+    section("Importing Session");
+
+    let response = Client::tcp()
+        .post(&str!(
+            "http://127.0.0.1:{port}/users/{USER_ID}/sessions/import"
+        ))
+        .json(&data)
+        .send()
+        .await
+        .map_err(|_| str!("Server is offline"))?;
+
+    if !response.status().is_success() {
+        return Err(response.text().await?.into());
+    }
+    let summary: SessionSummary = response
+        .json()
+        .await
+        .map_err(|e| str!("Failed to parse response: {e}"))?;
+
+    info("Session", &summary.id.to_string());
+    if let Some(title) = &summary.title {
+        info("Title", title);
+    }
+    info("Messages", &summary.message_count.to_string());
+    success("Session imported.");
+
+    println!();
+    Ok(())
+}
+
+/// API: Handles the sessions pruning by the retention policy
+pub async fn handle_prune(dry_run: bool) -> Result<()> {
+    let port = Settings::get().server.port;
+
+    section(if dry_run {
+        "Pruning Sessions (dry run)"
+    } else {
+        "Pruning Sessions"
+    });
+
+    let response = Client::tcp()
+        .post(&str!(
+            "http://127.0.0.1:{port}/sessions/prune?dry_run={dry_run}"
+        ))
+        .send()
+        .await
+        .map_err(|_| str!("Server is offline"))?;
+
+    if !response.status().is_success() {
+        return Err(response.text().await?.into());
+    }
+    let pruned: Vec<PrunedSession> = response
+        .json()
+        .await
+        .map_err(|e| str!("Failed to parse response: {e}"))?;
+
+    if pruned.is_empty() {
+        success("Nothing to prune.");
+        println!();
+        return Ok(());
+    }
+
+    for session in &pruned {
+        let reason = match session.reason {
+            PruneReason::Age => "too old",
+            PruneReason::Count => "too many sessions",
+            PruneReason::DiskUsage => "too much disk usage",
+        };
+
+        info(
+            &session.id.to_string(),
+            session.title.as_deref().unwrap_or(""),
+        );
+        item(
+            "",
+            &str!(
+                "{reason}, last active {}, {}",
+                session.last_activity.format("%Y-%m-%d %H:%M"),
+                format_size(session.disk_usage)
+            ),
+        );
+    }
+
+    let total = format_size(pruned.iter().map(|s| s.disk_usage).sum());
+    if dry_run {
+        warn(&str!(
+            "Dry run: {} sessions ({total}) would be removed",
+            pruned.len()
+        ));
+    } else {
+        success(&str!("{} sessions ({total}) removed.", pruned.len()));
+    }
+
+    println!();
+    Ok(())
+}
+
+/// Formats the size in bytes
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => str!("{bytes} B"),
+        1024..1_048_576 => str!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => str!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{prelude::*, session::Session};
+use anylm::{api::Message, embeddings::EmbeddingSearch};
+use ovsy_share::Event;
+
+/// The cached query answer record
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedAnswer {
+    /// Text content of the original user query
+    pub query: String,
+    /// Text content of the final assistant answer
+    pub answer: String,
+    /// Unix timestamp (in seconds) when the answer was stored
+    pub created_at: u64,
+}
+
+impl CachedAnswer {
+    /// Returns true if the record lifetime is over
+    pub fn is_expired(&self, ttl: u64) -> bool {
+        ttl > 0 && unix_now().saturating_sub(self.created_at) > ttl
+    }
+}
+
+/// The cache candidate of the processing user query
+#[derive(Debug, Clone)]
+pub struct CacheQuery {
+    /// Text content of the user query
+    pub query: String,
+    /// Embedding vector of the user query
+    pub embedding: Vec<f32>,
+}
+
+impl CacheQuery {
+    /// Prepares the cache candidate for a user message (if caching is enabled)
+    ///
+    /// The answer depends on the conversation, so only the queries opening a session are cached.
+    pub async fn new(message: &Message, history: &[Message]) -> Option<Self> {
+        if !Settings::get().cache.enable || !message.role.is_user() {
+            return None;
+        }
+        if history.iter().any(|msg| !msg.role.is_system()) {
+            return None;
+        }
+
+        let query = super::extract_text_from_msg(message)?;
+        match super::generate_embedding(&query, EmbeddingSearch::Query).await {
+            Ok(embedding) => Some(Self { query, embedding }),
+            Err(e) => {
+                warn!("Failed to embed query for the answers cache: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Searches for a cached answer similar to the user query
+pub async fn handle_lookup(session: &Session, query: &CacheQuery) -> Option<CachedAnswer> {
+    let cfg = &Settings::get().cache;
+
+    match session
+        .search_cache(query.embedding.clone(), cfg.coefficient, cfg.ttl)
+        .await
+    {
+        Ok(Some(record)) => {
+            info!("Cache hit for query: '{}'", query.query);
+            Some(record)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            warn!("Failed to search the answers cache: {e}");
+            None
+        }
+    }
+}
+
+/// Saves the final assistant answer for the user query
+pub async fn handle_store(session: &Session, query: CacheQuery, answer: String) -> Result<()> {
+    if answer.trim().is_empty() {
+        return Ok(());
+    }
+
+    let record = CachedAnswer {
+        query: query.query,
+        answer,
+        created_at: unix_now(),
+    };
+
+    session.save_cache(query.embedding, record).await?;
+    info!("Saved the answer to cache");
+    Ok(())
+}
+
+/// Replays the cached answer to the client as answer chunks
+pub fn replay(tx: &Sender<Bytes>, record: &CachedAnswer) -> Result<()> {
+    for part in record.answer.split_inclusive(char::is_whitespace) {
+        tx.send(Event::answer(part))?;
+    }
+
+    tx.send(Event::finish())?;
+    Ok(())
+}
+
+/// Returns the current unix timestamp (in seconds)
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
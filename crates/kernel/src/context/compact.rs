@@ -0,0 +1,242 @@
+use crate::{manager::QueryHandle, prelude::*, session::Session};
+
+use anylm::{
+    api::{Content, Message, Messages},
+    completions::{Chunk, Completions},
+    utils,
+};
+use ovsy_share::{Event, SessionId};
+
+/// The end of the trimmed tool output
+const TRIM_MARKER: &str = " tokens of the output trimmed]";
+
+/// The sessions with the history compaction in progress
+static COMPACTING: State<HashSet<SessionId>> = State::default();
+
+/// Compresses the session history except the last preserved turns, returns the history tokens (before, after)
+///
+/// The summary text is streamed to the client, if the sender is given.
+pub async fn compact_history(
+    session: &Arc<Mutex<Session>>,
+    preserve: usize,
+    tx: Option<&Sender<Bytes>>,
+) -> Result<Option<(usize, usize)>> {
+    let sid = session.lock().await.id;
+    if !COMPACTING.lock().await.insert(sid) {
+        return Err(Error::CompactionInProgress(sid).into());
+    }
+
+    let result = summarize(session, preserve, tx).await;
+    COMPACTING.lock().await.remove(&sid);
+    result
+}
+
+/// Returns true if the session history is being compacted
+pub fn is_compacting(sid: &SessionId) -> bool {
+    COMPACTING.dirty_get().contains(sid)
+}
+
+/// Summarizes the session history with the compression model
+async fn summarize(
+    session: &Arc<Mutex<Session>>,
+    preserve: usize,
+    tx: Option<&Sender<Bytes>>,
+) -> Result<Option<(usize, usize)>> {
+    let settings = Settings::get();
+    let compression_cfg = &settings.compression;
+
+    // read the messages after logging into the session
+    let db_messages = session.lock().await.read_messages().await?;
+
+    let compress_count = db_messages.len();
+    if compress_count == 0 {
+        warn!("Nothing to compress, skip");
+        return Ok(None);
+    }
+
+    let mut messages = Messages::from(db_messages);
+    let tokens_before = messages.tokens_count;
+
+    // select the messages that need to be left untouched
+    let to_preserve: Vec<Message> = messages.slice(-(preserve as isize));
+
+    // creating a prompt to compress the history
+    let messages = messages
+        .user(vec![compression_cfg.prompt.as_str().into()])
+        .wrap();
+
+    // sending a request to the LLM
+    let ops = compression_cfg
+        .options
+        .clone()
+        .unwrap_or(settings.completions.options.clone());
+    let mut response = Completions::try_from(ops)?.send(messages).await?;
+
+    let mut full_compressed_text = String::new();
+
+    // stream the response to the user and collect the full text
+    while let Some(chunk) = response.next().await {
+        if let Chunk::Text(text_part) = chunk? {
+            if let Some(tx) = tx
+                && tx.send(Event::answer(text_part.clone())).is_err()
+            {
+                warn!("Stream receiver dropped by client, aborting compression");
+                return Ok(None);
+            }
+            full_compressed_text.push_str(&text_part);
+        }
+    }
+
+    // rewriting the history in the database: put the compressed version and shift the saved messages
+    let compressed_message = Message::assistant(vec![full_compressed_text.into()], vec![]);
+    let tokens_after = compressed_message.tokens_count
+        + to_preserve
+            .iter()
+            .map(|msg| msg.tokens_count)
+            .sum::<usize>();
+
+    session
+        .lock()
+        .await
+        .insert_and_shift(compressed_message, to_preserve, compress_count)
+        .await?;
+
+    Ok(Some((tokens_before, tokens_after)))
+}
+
+/// Keeps the query context within the model context window
+///
+/// On overflow the long tool outputs are trimmed first, then the session history is compacted
+/// and replaced in the context before the completion is sent.
+pub async fn check_window(
+    session: &Arc<Mutex<Session>>,
+    context: &Arc<Mutex<Messages>>,
+    query: &QueryHandle,
+    tx: &Sender<Bytes>,
+) {
+    let settings = Settings::get();
+    let compression_cfg = &settings.compression;
+    if !compression_cfg.auto {
+        return;
+    }
+
+    let limit = compression_cfg.context_limit(&settings.completions.options.model);
+    let mut lock = context.lock().await;
+    let tokens_before = lock.tokens_count;
+    if tokens_before <= limit {
+        return;
+    }
+
+    // 1. trimming the long tool outputs (the trimmed ones are kept as is):
+    trim_tool_outputs(&mut lock, compression_cfg.max_tool_tokens);
+    let tokens_after = lock.tokens_count;
+    if tokens_after < tokens_before {
+        warn!(
+            "The context overflows ({tokens_before} > {limit} tokens), tool outputs trimmed to {tokens_after} tokens"
+        );
+        tx.send(Event::compacted("trim", tokens_before, tokens_after))
+            .ok();
+    }
+    if tokens_after <= limit {
+        return;
+    }
+    drop(lock);
+
+    // 2. compacting the history before the completion:
+    let sid = session.lock().await.id;
+    warn!(
+        "The context overflows ({tokens_after} > {limit} tokens), compacting the session history.."
+    );
+
+    let preserve = settings.execution.preserve_messages;
+    match compact_history(session, preserve, None).await {
+        Ok(Some((before, after))) => {
+            info!("Session {sid} history compacted ({before} -> {after} tokens)");
+            tx.send(Event::compacted("summary", before, after)).ok();
+        }
+        Ok(None) => return,
+        Err(e) => {
+            error!("Failed to compact the session {sid} history: {e}");
+            return;
+        }
+    }
+
+    if let Err(e) = reload_history(session, context, query).await {
+        error!("Failed to reload the session {sid} compacted history: {e}");
+    }
+}
+
+/// Replaces the context history with the compacted session history (the turn messages are kept)
+async fn reload_history(
+    session: &Arc<Mutex<Session>>,
+    context: &Arc<Mutex<Messages>>,
+    query: &QueryHandle,
+) -> Result<()> {
+    let mut history = session.lock().await.read_messages().await?;
+    if history.first().is_some_and(|msg| msg.role.is_system()) {
+        history.remove(0);
+    }
+
+    // the history lies between the static prompt & the first volatile system message:
+    let mut context = context.lock().await;
+    let Some(end) = context
+        .messages
+        .iter()
+        .skip(1)
+        .position(|msg| msg.role.is_system())
+        .map(|pos| pos + 1)
+    else {
+        return Ok(());
+    };
+
+    let shift = history.len() as isize - (end - 1) as isize;
+    context.messages.splice(1..end, history);
+    context.tokens_count = context.messages.iter().map(|msg| msg.tokens_count).sum();
+    drop(context);
+
+    query.shift_turn(shift).await;
+    Ok(())
+}
+
+/// Trims the tool outputs longer than the limit, returns the number of trimmed outputs
+fn trim_tool_outputs(context: &mut Messages, max_tokens: usize) -> usize {
+    let mut trimmed = 0;
+
+    for msg in context
+        .messages
+        .iter_mut()
+        .filter(|msg| msg.role.is_tool() && msg.tokens_count > max_tokens && !is_trimmed(msg))
+    {
+        let ratio = max_tokens as f32 / msg.tokens_count as f32;
+        msg.map(|content| {
+            for part in content.iter_mut() {
+                if let Content::Text { text } = part {
+                    *text = trim_text(text, ratio);
+                }
+            }
+        });
+        trimmed += 1;
+    }
+
+    context.tokens_count = context.messages.iter().map(|msg| msg.tokens_count).sum();
+    trimmed
+}
+
+/// Returns true if the tool output was already trimmed
+fn is_trimmed(msg: &Message) -> bool {
+    msg.content.iter().any(|part| match part {
+        Content::Text { text } => text.ends_with(TRIM_MARKER),
+        _ => false,
+    })
+}
+
+/// Keeps the head of the text by ratio (at the char boundary)
+fn trim_text(text: &str, ratio: f32) -> String {
+    let mut end = (text.len() as f32 * ratio) as usize;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let cut_tokens = utils::count_tokens(&text[end..]);
+    str!("{}\n...[{cut_tokens}{TRIM_MARKER}", &text[..end])
+}
@@ -0,0 +1,269 @@
+use crate::{
+    prelude::*,
+    session::{Session, index, store::Store},
+};
+
+use anylm::{api::Message, embeddings::EmbeddingSearch};
+use cistern::{Cistern, Rag, RagRecord, RagTable};
+use ovsy_share::HistoryHit;
+
+/// The user RAG table of the embedded history messages
+const HISTORY_TABLE: &str = "history";
+/// The maximum embedded message text length in chars
+const MAX_EMBEDDED_LENGTH: usize = 2_000;
+
+/// The history table writes are sequential
+static EMBEDDING: Mutex<()> = Mutex::const_new(());
+
+/// The embedded history message record
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryRecord {
+    pub session_id: SessionId,
+    /// The message index in the session database
+    pub index: usize,
+    /// The embedded message text (it's compared with the message to detect the rewritten ones)
+    pub text: String,
+}
+
+/// Embeds the persisted session messages for the semantic history search (in background)
+pub fn embed(id: SessionId, messages: Vec<(usize, Message)>) {
+    if !Settings::get().history.semantic {
+        return;
+    }
+
+    let records = messages
+        .into_iter()
+        .filter_map(|(index, msg)| {
+            Some(HistoryRecord {
+                session_id: id,
+                index,
+                text: embedded_text(&index::text(&msg)?),
+            })
+        })
+        .collect::<Vec<_>>();
+    if records.is_empty() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let _lock = EMBEDDING.lock().await;
+        if let Err(e) = write_records(id.user_id, records).await {
+            warn!("Failed to embed the session {id} messages: {e}");
+        }
+    });
+}
+
+/// Searches the user conversations history by the exact terms and similar meaning
+///
+/// The active history of the skipped session isn't searched (it's in the context already).
+pub async fn search(
+    user_id: u128,
+    query: &str,
+    limit: usize,
+    skip_active: Option<SessionId>,
+) -> Result<Vec<HistoryHit>> {
+    let cfg = &Settings::get().history;
+    let limit = if limit == 0 { cfg.search_limit } else { limit };
+    let terms = index::terms(query);
+
+    // the exact terms matches:
+    let mut found = HashMap::<SessionId, HashMap<usize, f32>>::new();
+    if !terms.is_empty() {
+        // the sessions written before the store are indexed first:
+        let store = Store::open(user_id).await?;
+        store.summaries(user_id).await?;
+
+        for (id, matches) in store.find(&terms).await? {
+            found.entry(id).or_default().extend(matches);
+        }
+    }
+
+    // the similar meaning matches (scored by the distance order):
+    let mut similar = HashMap::new();
+    if cfg.semantic && !query.trim().is_empty() {
+        match search_similar(user_id, query, limit).await {
+            Ok(records) => {
+                let count = records.len();
+                for (rank, record) in records.into_iter().enumerate() {
+                    let key = (record.data.session_id, record.data.index);
+                    found.entry(key.0).or_default().entry(key.1).or_default();
+                    similar.insert(
+                        key,
+                        (
+                            record.id,
+                            record.data.text,
+                            1.0 - rank as f32 / count as f32,
+                        ),
+                    );
+                }
+            }
+            Err(e) => warn!("Failed to search the similar history messages: {e}"),
+        }
+    }
+
+    let (mut hits, mut stale) = (vec![], vec![]);
+    for (id, matches) in found {
+        let session_hits = match Session::read_hits(id, matches.into_iter().collect()).await {
+            Ok(session_hits) => session_hits,
+            Err(e) => {
+                // the records of the removed session are stale:
+                if Session::dir(&id).exists() {
+                    warn!("Failed to read the session {id} history: {e}");
+                    similar.retain(|(sid, _), _| *sid != id);
+                }
+                continue;
+            }
+        };
+
+        for mut hit in session_hits {
+            if let Some((record_id, text, score)) = similar.remove(&(id, hit.index)) {
+                // the rewritten message record is stale:
+                match text == embedded_text(&hit.text) {
+                    true => hit.score += score,
+                    false => stale.push(record_id),
+                }
+            }
+            if hit.score > 0.0 {
+                hits.push(hit);
+            }
+        }
+    }
+
+    // the rest records point to the removed messages:
+    stale.extend(similar.into_values().map(|(record_id, ..)| record_id));
+    remove_records(user_id, stale).await;
+
+    hits.retain(|hit| hit.compressed || Some(hit.session_id) != skip_active);
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.timestamp.cmp(&a.timestamp))
+    });
+
+    // the compaction copies of the same message are shown once:
+    let mut seen = HashSet::new();
+    hits.retain(|hit| seen.insert((hit.session_id, hit.role.clone(), hit.text.clone())));
+    hits.truncate(limit);
+
+    for hit in &mut hits {
+        hit.text = snippet(&hit.text, &terms, cfg.snippet_length);
+    }
+    Ok(hits)
+}
+
+/// Searches the past conversations for the planner (the current session history is skipped)
+pub async fn handle_search(sid: SessionId, query: &str, limit: Option<usize>) -> Result<String> {
+    let hits = search(sid.user_id, query, limit.unwrap_or_default(), Some(sid)).await?;
+    info!("Found {} history messages for '{query}'", hits.len());
+
+    if hits.is_empty() {
+        return Ok(format!("No past messages found for \"{query}\"."));
+    }
+
+    let mut text = format!("Found {} past messages for \"{query}\":\n", hits.len());
+    for hit in hits {
+        let title = hit.title.unwrap_or(str!("Session {}", hit.session_id));
+        let date = hit
+            .timestamp
+            .map(|at| at.format(", %Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_default();
+        text.push_str(&format!("\n* [{title}{date}] {}: {}\n", hit.role, hit.text));
+    }
+
+    Ok(text)
+}
+
+/// Opens the user history RAG table
+async fn history_table(user_id: u128) -> Result<RagTable> {
+    let rag_db = Cistern::<Rag>::connect(Session::facts_dir(user_id)).await?;
+    rag_db.open_table(HISTORY_TABLE).await
+}
+
+/// Writes the message records with the generated embeddings
+async fn write_records(user_id: u128, records: Vec<HistoryRecord>) -> Result<()> {
+    let mut batch = Vec::with_capacity(records.len());
+    for record in records {
+        let embedding = super::generate_embedding(&record.text, EmbeddingSearch::Document).await?;
+        batch.push((embedding, record));
+    }
+
+    history_table(user_id).await?.write_batch(batch).await?;
+    Ok(())
+}
+
+/// Searches the messages records similar to the query
+async fn search_similar(
+    user_id: u128,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<RagRecord<HistoryRecord>>> {
+    // the user without sessions has no records:
+    if !Session::facts_dir(user_id).exists() {
+        return Ok(vec![]);
+    }
+
+    let embedding = super::generate_embedding(query, EmbeddingSearch::Query).await?;
+    let similarity = Settings::get().history.similarity;
+
+    let records = history_table(user_id)
+        .await?
+        .read(embedding, limit, similarity)
+        .await?;
+    Ok(records.unwrap_or_default())
+}
+
+/// Removes the stale message records
+async fn remove_records(user_id: u128, ids: Vec<u64>) {
+    if ids.is_empty() {
+        return;
+    }
+
+    let _lock = EMBEDDING.lock().await;
+    let table = match history_table(user_id).await {
+        Ok(table) => table,
+        Err(e) => return warn!("Failed to open the user {user_id} history: {e}"),
+    };
+    for id in ids {
+        if let Err(e) = table.remove(id).await {
+            warn!("Failed to remove the stale history record #{id}: {e}");
+        }
+    }
+}
+
+/// Returns the message text part to be embedded
+fn embedded_text(text: &str) -> String {
+    text.chars().take(MAX_EMBEDDED_LENGTH).collect()
+}
+
+/// Cuts the text snippet around the first matched term
+fn snippet(text: &str, terms: &[String], length: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let chars = text.chars().collect::<Vec<_>>();
+    if length == 0 || chars.len() <= length {
+        return text;
+    }
+
+    // the chars are lowercased one by one (the positions are kept):
+    let lower = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect::<String>();
+    let pos = terms
+        .iter()
+        .filter_map(|term| lower.find(term.as_str()))
+        .min()
+        .map(|pos| lower[..pos].chars().count())
+        .unwrap_or_default();
+
+    let start = pos.saturating_sub(length / 4).min(chars.len() - length);
+    let end = start + length;
+
+    let mut snippet = chars[start..end].iter().collect::<String>();
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    snippet
+}
@@ -1,6 +1,13 @@
 pub mod fact;
 pub use fact::*;
 
+pub mod cache;
+pub use cache::{CacheQuery, CachedAnswer};
+
+pub mod compact;
+
+pub mod history;
+
 use crate::prelude::*;
 use anylm::{
     api::{Content, Message},
@@ -17,12 +17,55 @@ pub enum Error {
     #[display(fmt = "Failed to parse AgentInfo response payload: {0}")]
     AgentInfoParsingFailed(#[source] DynError),
 
+    #[from(skip)]
+    #[display(fmt = "Agent `{name}` has no tools of the skills [{skills}]")]
+    AgentToolsUnavailable { name: String, skills: String },
+
     #[display(fmt = "Unknown session id {0} has been received")]
     UnknownSessionId(SessionId),
 
+    #[from(skip)]
+    #[display(fmt = "The session {0} history is already being compacted")]
+    CompactionInProgress(SessionId),
+
+    #[from(skip)]
+    #[display(fmt = "The session {0} query is in progress")]
+    QueryInProgress(SessionId),
+
+    #[from(skip)]
+    #[display(fmt = "The session {0} has no turn to regenerate")]
+    NothingToRegenerate(SessionId),
+
+    #[from(skip)]
+    #[display(fmt = "The message index {index} is out of the {count} history messages")]
+    MessageIndexOutOfRange { index: usize, count: usize },
+
+    #[from(skip)]
+    #[display(fmt = "The message index {index} is inside a conversation turn")]
+    MessageIndexInsideTurn { index: usize },
+
+    #[from(skip)]
+    #[display(fmt = "Invalid session export: {0}")]
+    InvalidSessionExport(String),
+
     #[display(fmt = "The TypeScript runtime is not initialized, check logs")]
     RuntimeNotInitialized,
 
     #[display(fmt = "No embedding received from provider")]
     NoEmbeddingReceived,
+
+    #[display(fmt = "Tool `{tool}` of agent `{agent}` timed out after {secs}s")]
+    ToolTimeout {
+        agent: String,
+        tool: String,
+        secs: u64,
+    },
+
+    #[from(skip)]
+    #[display(fmt = "Duplicate task IDs in the plan: {ids:?}")]
+    DuplicateTaskIds { ids: Vec<i64> },
+
+    #[from(skip)]
+    #[display(fmt = "Task dependencies form a cycle: {cycle:?}")]
+    TaskDependencyCycle { cycle: Vec<i64> },
 }
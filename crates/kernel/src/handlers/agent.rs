@@ -0,0 +1,9 @@
+use crate::{Manager, prelude::*};
+
+/// API: Returns the agent details with the cached tools schemas
+pub async fn handle_info(Paths(name): Paths<String>) -> Response {
+    match Manager::agent_info(&arc!(name.clone())).await {
+        Some(info) => Response::ok().json(&info),
+        None => Response::not_found().text(str!("Unknown agent `{name}`")),
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{Manager, prelude::*};
+use crate::{Manager, prelude::*, session::Session};
 use ovsy_share::StatusData;
 
 /// API: Handles the server ping
@@ -9,7 +9,10 @@ pub async fn handle_ping() -> Response {
 /// Returns the server status & agents list
 pub async fn handle_status() -> Response {
     let agents = Manager::agents_list().await;
-    Response::ok().json(&StatusData::Success { agents })
+    Response::ok().json(&StatusData::Success {
+        agents,
+        open_sessions: Session::count(),
+    })
 }
 
 /// Refreshes the server settings & agents list
@@ -25,5 +28,8 @@ pub async fn handle_refresh() -> Response {
     }
 
     let agents = Manager::agents_list().await;
-    Response::ok().json(&StatusData::Success { agents })
+    Response::ok().json(&StatusData::Success {
+        agents,
+        open_sessions: Session::count(),
+    })
 }
@@ -1,3 +1,4 @@
+pub mod agent;
 pub mod health;
 pub mod query;
 pub mod session;
@@ -1,48 +1,200 @@
-use crate::{context, manager::*, prelude::*, runtime::Runtime, session::Session, skills};
+use crate::{
+    context::{self, CacheQuery},
+    manager::*,
+    prelude::*,
+    runtime::Runtime,
+    session::Session,
+    skills,
+};
 
 use anylm::{
-    api::{Content, Message, Messages},
+    api::{Content, Message, Messages, ToolCall, ToolCallFunction},
     completions::{Chunk, Completions},
     embeddings::EmbeddingSearch,
 };
 use chrono::FixedOffset;
-use ovsy_share::{Event, EventKind, HandleQuery, SessionInfo};
+use ovsy_share::{Event, EventKind, HandleQuery, RegenerateQuery, SessionInfo};
 use std::collections::HashSet;
-use tokio::task::JoinSet;
+use tokio::{sync::watch, task::JoinSet};
 
 /// API: The user query handler
 pub async fn handle_user_query(Paths(sid): Paths<SessionId>, data: Json<HandleQuery>) -> Response {
     let HandleQuery { message } = data.0;
 
     Response::ok().stream(move |tx| async move {
-        let result = match read_session(sid).await {
-            Ok((session, messages)) => {
-                handle_query(sid, tx.clone(), session, messages, message).await
-            }
-            Err(e) => Err(e),
+        if let Some(query) = start_query(sid, &tx).await {
+            run_query(query, message, tx, true).await;
+        }
+    })
+}
+
+/// API: Regenerates the last answer (with the edited user message, if given)
+pub async fn handle_regenerate(
+    Paths(sid): Paths<SessionId>,
+    data: Json<RegenerateQuery>,
+) -> Response {
+    let RegenerateQuery { message } = data.0;
+
+    Response::ok().stream(move |tx| async move {
+        // the query is registered first, so no other query of the session runs after the pop:
+        let Some(query) = start_query(sid, &tx).await else {
+            return;
         };
 
-        if let Err(e) = result {
-            error!("[handle_query{{sid={sid}}}] {e}");
-            tx.send(Event::error(str!(e))).ok();
+        match pop_last_turn(sid).await {
+            // the same query mustn't be answered from the cache:
+            Ok(last_message) => run_query(query, message.unwrap_or(last_message), tx, false).await,
+            Err(e) => {
+                error!("[handle_regenerate{{sid={sid}}}] {e}");
+                tx.send(Event::error(str!(e))).ok();
+
+                drop(tx);
+                query.watch().await;
+            }
         }
     })
 }
 
+/// Removes the last turn of the idle session, returns its user message
+#[log(skip_all, fields(sid = %sid))]
+async fn pop_last_turn(sid: SessionId) -> Result<Message> {
+    // the evicted session is re-opened transparently:
+    let session = Session::open(sid).await?;
+    if context::compact::is_compacting(&sid) {
+        return Err(Error::CompactionInProgress(sid).into());
+    }
+
+    let turn = session.lock().await.pop_turn().await?;
+    let Some(message) = turn.and_then(|turn| turn.into_iter().next()) else {
+        return Err(Error::NothingToRegenerate(sid).into());
+    };
+
+    info!("The last turn is removed for the regeneration");
+    Ok(message)
+}
+
+/// Registers the in-flight session query (reports the error to the client)
+async fn start_query(sid: SessionId, tx: &Sender<Bytes>) -> Option<Arc<QueryHandle>> {
+    match QueryHandle::start(sid, tx).await {
+        Ok(query) => Some(query),
+        Err(e) => {
+            warn!("[handle_query{{sid={sid}}}] {e}");
+            tx.send(Event::error(str!(e))).ok();
+            None
+        }
+    }
+}
+
+/// Handles the user query, streaming the events to the client
+async fn run_query(query: Arc<QueryHandle>, message: Message, tx: Sender<Bytes>, use_cache: bool) {
+    let sid = query.sid;
+    let worker_query = query.clone();
+
+    query
+        .spawn(async move {
+            let query = worker_query;
+            let result = match read_session(sid).await {
+                Ok((session, messages)) => {
+                    let history = messages.lock().await.messages.clone();
+                    let cache = CacheQuery::new(&message, &history).await;
+                    let replay = match use_cache {
+                        true => replay_cached(&tx, &session, &message, cache.as_ref()).await,
+                        false => Ok(false),
+                    };
+
+                    match replay {
+                        Ok(true) => Ok(()),
+                        Ok(false) => {
+                            handle_query(
+                                sid,
+                                tx.clone(),
+                                session,
+                                messages,
+                                message,
+                                cache,
+                                query.clone(),
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    }
+                }
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = result {
+                error!("[handle_query{{sid={sid}}}] {e}");
+                tx.send(Event::error(str!(e))).ok();
+
+                // the user has seen the failed turn too:
+                if let Err(e) = query.save_turn().await {
+                    error!("[handle_query{{sid={sid}}}] Failed to save the turn: {e}");
+                }
+            }
+        })
+        .await;
+
+    // cancel the query if the client drops the stream:
+    query.watch().await;
+}
+
+/// API: Cancels the in-flight session query
+#[log(skip_all, fields(sid = %sid))]
+pub async fn handle_cancel(Paths(sid): Paths<SessionId>) -> Response {
+    let Some(query) = QueryHandle::get(&sid) else {
+        return Response::not_found().text("There is no active query in this session");
+    };
+
+    if query.cancel().await {
+        Response::ok().text("The query was cancelled")
+    } else {
+        Response::ok().text("The query is already cancelled")
+    }
+}
+
 /// Helper method to read user session from database
 #[log(skip_all, fields(sid = %sid))]
 async fn read_session(sid: SessionId) -> Result<(Arc<Mutex<Session>>, Arc<Mutex<Messages>>)> {
     info!("Reading the user session...");
 
-    let Some(session) = Session::get(&sid) else {
-        return Err(Error::UnknownSessionId(sid).into());
-    };
+    // the evicted session is re-opened transparently:
+    let session = Session::open(sid).await?;
     let db_messages = session.lock().await.read_messages().await?;
     let messages = arc_mutex!(Messages::from(db_messages));
 
     Ok((session, messages))
 }
 
+/// Replays the cached answer of a similar query (returns true on cache hit)
+#[log(skip_all)]
+async fn replay_cached(
+    tx: &Sender<Bytes>,
+    session: &Arc<Mutex<Session>>,
+    message: &Message,
+    cache: Option<&CacheQuery>,
+) -> Result<bool> {
+    let Some(cache) = cache else {
+        return Ok(false);
+    };
+
+    let session = session.lock().await;
+    let Some(record) = context::cache::handle_lookup(&session, cache).await else {
+        return Ok(false);
+    };
+
+    context::cache::replay(tx, &record)?;
+
+    // save the replayed turn to database:
+    session
+        .write_turn(vec![
+            message.clone(),
+            Message::assistant(vec![Content::text(record.answer)], vec![]),
+        ])
+        .await?;
+
+    Ok(true)
+}
+
 /// Handles the user query with self-healing on planning/generation level
 #[log(skip_all, fields(sid = %sid))]
 async fn handle_query(
@@ -51,6 +203,8 @@ async fn handle_query(
     session: Arc<Mutex<Session>>,
     messages: Arc<Mutex<Messages>>,
     message: Message,
+    cache: Option<CacheQuery>,
+    query: Arc<QueryHandle>,
 ) -> Result<()> {
     info!("Processing the user query...");
 
@@ -59,13 +213,35 @@ async fn handle_query(
     let exec_options = &settings.execution;
     let context_options = &settings.context;
 
+    // 0. The self-correction loop budget
+    let cycle = query.next_cycle();
+    let max_cycles = exec_options.max_cycles;
+    if max_cycles > 0 && cycle > max_cycles {
+        warn!("The query cycles budget is exhausted ({max_cycles} cycles)");
+        let text = str!("I could not complete the request after {max_cycles} cycles.");
+        return finish_terminal(&tx, &messages, &query, text).await;
+    }
+    tx.send(Event::cycle(cycle, max_cycles))?;
+
     // 1. RAG: Search for relevant facts about the user
     let session_guard = session.lock().await;
     let mut facts_prompt = String::new();
 
     if let Some(user_text) = context::extract_text_from_msg(&message) {
-        if let Ok(query_vec) = context::generate_embedding(&user_text, EmbeddingSearch::Query).await
-        {
+        // reuse the query embedding, if it was already generated for cache:
+        let query_vec = match &cache {
+            Some(cache) => Ok(cache.embedding.clone()),
+            None => context::generate_embedding(&user_text, EmbeddingSearch::Query).await,
+        };
+
+        if let Ok(query_vec) = query_vec {
+            // pre-select the agent skills relevant to the request (kept for the control cycles):
+            if cycle == 1 {
+                query
+                    .set_selection(Selection::select(&query_vec).await)
+                    .await;
+            }
+
             if let Ok(facts) = session_guard
                 .search_facts(
                     query_vec,
@@ -95,38 +271,82 @@ async fn handle_query(
     }
 
     // 2. Preparing the context and system promptes
-    let raw_messages = messages.lock().await.messages.clone();
+    let mut raw_messages = messages.lock().await.messages.clone();
     let base_system_prompt = system_prompt(&session_guard.info, &settings);
     drop(session_guard);
 
+    // the pre-selected agents list depends on the request, so it goes late (the full list is the fallback):
+    let selection = query.selection().await;
+    let (agents_doc, late_agents_doc) = match (settings.selection.enable, &selection) {
+        (false, _) => (Manager::agents_list_doc().await.to_string(), str!()),
+        (true, Some(selection)) => (
+            str!(LATE_AGENTS_NOTE),
+            str!("\n\n{}", selection.doc().await),
+        ),
+        (true, None) => (
+            str!(LATE_AGENTS_NOTE),
+            str!("\n\n{}", Manager::agents_list_doc().await),
+        ),
+    };
+
+    // the static prompt heads the context (a prefix cacheable by the LLM server), the volatile system info goes late:
+    let static_prompt = Message::system(vec![
+        settings
+            .completions
+            .assist_prompt
+            .replace("{AGENTS_LIST}", &agents_doc)
+            .into(),
+    ]);
+    match raw_messages.first_mut() {
+        Some(first) if first.role.is_system() => *first = static_prompt,
+        _ => raw_messages.insert(0, static_prompt),
+    }
+
     let messages = Messages::from(raw_messages)
         .system(vec![
-            format!("{base_system_prompt}{facts_prompt}").into(),
-            settings
-                .completions
-                .assist_prompt
-                .replace("{AGENTS_LIST}", &Manager::agents_list_doc().await)
-                .into(),
+            format!("{base_system_prompt}{facts_prompt}{late_agents_doc}").into(),
         ])
         .message(message)
         .wrap();
+    query.begin_turn(&messages).await;
 
     let mut tasks_list = vec![];
     let mut evals_list = vec![];
     let mut memory_results = vec![];
+    let mut lookups = vec![];
+    let mut looked_up = false;
+    let mut text_response = str!();
+
+    // the tasks started while the planner is still streaming (with their signatures)
+    let mut early_tasks: Option<(Arc<Mutex<Tasks>>, watch::Sender<bool>)>;
+    let mut early_signatures = HashMap::new();
 
     let mut retry_count = 0;
+    let mut lookup_rounds = 0;
     let max_retries = exec_options.max_retries.max(1);
 
     // top-level generation cycle: task planning
     loop {
+        // the early tasks of the rejected plan stop before calling the agent model:
+        early_tasks = None;
+        early_signatures.clear();
+
         tasks_list.clear();
         evals_list.clear();
         memory_results.clear();
-        let mut text_response = str!();
+        lookups.clear();
+        text_response.clear();
+
+        // keep the context within the model context window (the lookups & retries grow it):
+        context::compact::check_window(&session, &messages, &query, &tx).await;
+
+        // the query cancelled before its loop was registered isn't sent to the model:
+        if query.is_cancelled() {
+            return Ok(());
+        }
 
         let mut response = match Completions::try_from(completions_options.clone())?
-            .tools(Manager::basic_tools().await)
+            .tools(Manager::planner_tools().await)
             .send(messages.clone())
             .await
         {
@@ -149,6 +369,8 @@ async fn handle_query(
 
         // read ai chunks and collect tool calls
         let mut chunk_error = None;
+        let mut direct_id = 0; // the direct tool calls tasks are numbered with negative IDs
+        let mut checked_tasks = 0;
         while let Some(chunk) = response.next().await {
             match chunk {
                 Ok(Chunk::Text(text_part)) => {
@@ -159,6 +381,26 @@ async fn handle_query(
 
                 Ok(Chunk::Tool(tool_call)) => match tool_call.func.name.as_ref() {
                     "handle_agent" => match tool_call.parse_args::<skills::task::TaskAction>() {
+                        // the planner delegates to the pre-selected agents only:
+                        Ok(task)
+                            if selection
+                                .as_ref()
+                                .is_some_and(|selection| !selection.contains(&task.agent_name)) =>
+                        {
+                            let agents = selection
+                                .as_ref()
+                                .map(Selection::agents)
+                                .unwrap_or_default();
+                            chunk_error = Some(
+                                str!(
+                                    "The agent `{}` is not available for this request, use one of: {}",
+                                    task.agent_name,
+                                    agents.join(", ")
+                                )
+                                .into(),
+                            );
+                            break;
+                        }
                         Ok(mut task) => {
                             task.tool_call_id = tool_call.id;
                             tasks_list.push(task);
@@ -215,7 +457,52 @@ async fn handle_query(
                             break;
                         }
                     },
-                    _ => {}
+
+                    "search_history" => match tool_call
+                        .parse_args::<skills::history::SearchHistoryAction>()
+                    {
+                        Ok(act) => lookups.push((tool_call.id, act)),
+                        Err(e) => {
+                            chunk_error = Some(str!("Failed to parse search_history: {e}").into());
+                            break;
+                        }
+                    },
+
+                    // the namespaced agent tool called directly:
+                    name => match skills::direct::split_name(name).and_then(|(agent, tool)| {
+                        Some((agent, tool, exec_options.direct_tools.get(agent)?))
+                    }) {
+                        Some((agent, tool, skills)) => {
+                            // the tool must be one of the configured skills tools:
+                            let known = Manager::agent_tools(&arc!(agent.to_owned()), skills)
+                                .await
+                                .unwrap_or_default()
+                                .iter()
+                                .any(|schema| skills::direct::is_named(schema, tool));
+                            if !known {
+                                chunk_error = Some(
+                                    str!("The tool `{name}` is not available to call directly")
+                                        .into(),
+                                );
+                                break;
+                            }
+
+                            direct_id -= 1;
+                            let call = skills::direct::DirectCall {
+                                tool: tool.to_owned(),
+                                args: tool_call.func.json_str.clone(),
+                            };
+
+                            tasks_list.push(skills::task::TaskAction::direct(
+                                direct_id,
+                                tool_call.id.clone(),
+                                agent,
+                                skills.clone(),
+                                call,
+                            ));
+                        }
+                        None => warn!("The planner called an unknown tool `{name}`, skipping it"),
+                    },
                 },
 
                 Err(e) => {
@@ -223,6 +510,30 @@ async fn handle_query(
                     break;
                 }
             }
+
+            // the new tasks without dependencies can be started right away:
+            for task in &tasks_list[checked_tasks..] {
+                if !task.depend_tasks.is_empty() || early_signatures.contains_key(&task.task_id) {
+                    continue;
+                }
+
+                let (tasks, _) = early_tasks.get_or_insert_with(|| {
+                    let (plan, plan_rx) = watch::channel(false);
+                    let tasks =
+                        Tasks::new(session.clone(), messages.clone(), query.clone(), plan_rx);
+                    (tasks, plan)
+                });
+                query.set_tasks(tasks).await;
+
+                info!("Starting agent task #{} early", task.task_id);
+                early_signatures.insert(task.task_id, task.signature());
+                tasks.lock().await.pending.insert(
+                    task.task_id,
+                    Task::new(tx.clone(), tasks.clone(), task.clone()),
+                );
+                handle_task(task.task_id, tx.clone(), tasks.clone()).await;
+            }
+            checked_tasks = tasks_list.len();
         }
 
         if let Some(err) = chunk_error {
@@ -238,6 +549,40 @@ async fn handle_query(
             }
         }
 
+        // the found past messages are fed back to the planner:
+        if !lookups.is_empty() {
+            looked_up = true;
+            for (tool_call_id, act) in lookups.drain(..) {
+                tx.send(
+                    Event::think(str!("Searching the history for \"{}\"...", act.query))
+                        .raw_task_info(0, tool_call_id.clone()),
+                )?;
+                let res_text = context::history::handle_search(sid, &act.query, act.limit)
+                    .await
+                    .unwrap_or_else(|e| str!("Failed to search the history: {e}"));
+                messages
+                    .lock()
+                    .await
+                    .push_content(Some(&tool_call_id), res_text);
+            }
+
+            // the planner answers with the found context (the lookup rounds are limited):
+            if tasks_list.is_empty() && evals_list.is_empty() && lookup_rounds < max_retries {
+                lookup_rounds += 1;
+                for (tool_call_id, res_text) in memory_results.drain(..) {
+                    messages
+                        .lock()
+                        .await
+                        .push_content(Some(&tool_call_id), res_text.clone());
+                    tx.send(Event::think(res_text).raw_task_info(0, tool_call_id))?;
+                }
+
+                // the lookup call is a part of the turn:
+                query.accept_answer().await;
+                continue;
+            }
+        }
+
         // hallucination check (if there is no text, no tasks, no JS calculations, and no memory operations)
         if tasks_list.is_empty()
             && evals_list.is_empty()
@@ -258,16 +603,85 @@ async fn handle_query(
             }
         }
 
+        // the evals over the task results become the target tasks transforms:
+        for (_, eval) in evals_list.extract_if(.., |(_, eval)| !eval.depend_tasks.is_empty()) {
+            let target = eval
+                .task_id
+                .and_then(|id| tasks_list.iter_mut().find(|task| task.task_id == id));
+
+            match target {
+                Some(task) => {
+                    task.depend_tasks.extend(eval.depend_tasks.iter().copied());
+                    task.transforms.push(eval);
+                }
+                None => warn!("The JS transform requires an existing target task_id, skipping it"),
+            }
+        }
+
+        // the tasks graph must be a DAG with unique task IDs
+        if let Err(e) = skills::task::validate_plan(&tasks_list) {
+            retry_count += 1;
+            if retry_count < max_retries {
+                warn!("Invalid tasks plan ({retry_count}/{max_retries}): {e}");
+                let feedback = match &e {
+                    Error::DuplicateTaskIds { ids } => json!({
+                        "error": "duplicate_task_ids",
+                        "task_ids": ids,
+                        "message": e.to_string(),
+                    }),
+                    Error::TaskDependencyCycle { cycle } => json!({
+                        "error": "dependency_cycle",
+                        "task_ids": cycle,
+                        "message": e.to_string(),
+                    }),
+                    _ => json!({ "error": "invalid_plan", "message": e.to_string() }),
+                };
+                messages.lock().await.add_user(vec![
+                    format!("The planned tasks were rejected: {feedback}. Please plan the tasks again: every task must have an unique task_id and the dependencies must not form cycles.").into()
+                ]);
+                continue;
+            } else {
+                return Err(e.into());
+            }
+        }
+
+        // the model repeats the already executed agent calls:
+        if query.is_repeated_plan(&tasks_list).await {
+            retry_count += 1;
+            if retry_count < max_retries {
+                warn!("Repeated tasks plan ({retry_count}/{max_retries})");
+                messages.lock().await.add_user(vec![
+                    "You planned the agent tasks that were already executed with identical arguments. Use their results above to answer the user, or change the strategy.".into()
+                ]);
+                continue;
+            } else {
+                let text = str!(
+                    "I could not complete the request after {cycle} cycles: the same tasks were planned repeatedly."
+                );
+                return finish_terminal(&tx, &messages, &query, text).await;
+            }
+        }
+        query.remember_plan(&tasks_list).await;
+
         break;
     }
+    query.accept_answer().await;
+
+    // only plain text answers can be cached (JS evals, memory operations and history lookups depend on state)
+    let is_cacheable = evals_list.is_empty() && memory_results.is_empty() && !looked_up;
 
     // if the model has performed memory operations, notify the user
     for (tool_call_id, res_text) in memory_results {
+        messages
+            .lock()
+            .await
+            .push_content(Some(&tool_call_id), res_text.clone());
         tx.send(Event::think(format!("{res_text}")).raw_task_info(0, tool_call_id))?;
     }
 
     // performing JS calculations (if any)
     if !evals_list.is_empty() {
+        let mut context = messages.lock().await;
         let mut runtime = Runtime::new();
 
         for (tool_call_id, eval) in evals_list {
@@ -279,22 +693,13 @@ async fn handle_query(
                     continue;
                 };
 
-                if let Some(parameter) = &eval.parameter {
-                    let placeholder = format!("{{{{{parameter}}}}}");
-
-                    if task.task_query.contains(&placeholder) {
-                        task.task_query = task.task_query.replace(&placeholder, &result);
-                    } else {
-                        warn!("Placeholder '{parameter}' not found in task #{task_id}");
-                    }
-                } else {
-                    if !task.task_query.ends_with('\n') {
-                        task.task_query.push('\n');
-                    }
-
-                    task.task_query.push_str(&result);
-                }
+                eval.inject(task_id, &mut task.task_query, &result);
+                context.push_content(
+                    Some(&tool_call_id),
+                    str!("Injected into task #{task_id}: {result}"),
+                );
             } else {
+                context.push_content(Some(&tool_call_id), result.clone());
                 tx.send(Event::answer(format!("\n\n{result}")).raw_task_info(0, tool_call_id))?;
             }
         }
@@ -309,22 +714,39 @@ async fn handle_query(
         }
 
         // send tool calls to client:
-        if let Some(msg) = (&*messages.lock().await).messages.last()
-            && msg.role.is_assistant()
+        if let Some(msg) = (&*messages.lock().await)
+            .messages
+            .iter()
+            .rfind(|msg| msg.role.is_assistant())
         {
             tx.send(Event::start(&msg.tool_calls))?;
         }
 
-        // delegate tasks:
+        // delegate tasks (to the graph of the early started ones):
         let tasks_len = tasks_list.len();
-        let tasks = Tasks::new(session, messages);
+        let (tasks, plan) = early_tasks.take().unwrap_or_else(|| {
+            let (plan, plan_rx) = watch::channel(false);
+            (Tasks::new(session, messages, query.clone(), plan_rx), plan)
+        });
+        query.set_tasks(&tasks).await;
 
         // collect tasks:
         let mut running = vec![];
+        let mut restarted = vec![];
         {
             let mut lock = tasks.lock().await;
 
             for task in tasks_list {
+                // the early started task keeps running, unless the plan has changed it:
+                if let Some(signature) = early_signatures.get(&task.task_id) {
+                    if *signature == task.signature() && task.depend_tasks.is_empty() {
+                        continue;
+                    }
+                    if let Some(child) = lock.working.remove(&task.task_id) {
+                        restarted.push(child);
+                    }
+                }
+
                 if task.depend_tasks.is_empty() {
                     running.push(task.task_id);
                 }
@@ -333,29 +755,63 @@ async fn handle_query(
                     .insert(task.task_id, Task::new(tx.clone(), tasks.clone(), task));
             }
         };
+        for child in restarted {
+            child.abort();
+        }
 
         // spawning tasks:
         info!("Spawning agent tasks ({tasks_len})");
         for task_id in running {
             handle_task(task_id, tx.clone(), tasks.clone()).await;
         }
+        plan.send_replace(true);
+        tokio::spawn(Tasks::watchdog(Arc::downgrade(&tasks)));
     } else {
         tx.send(Event::finish())?;
         info!("The user request was processed without agent tasks");
 
-        // save messages to database:
-        let to_save = messages.lock().await.slice(-1);
-        session.lock().await.write_messages(to_save).await?;
+        // save the whole turn to database:
+        query.save_turn().await?;
+
+        // save answer to cache:
+        let session = session.lock().await;
+        if let Some(cache) = cache
+            && is_cacheable
+            && let Err(e) = context::cache::handle_store(&session, cache, text_response).await
+        {
+            warn!("Failed to save the answer to cache: {e}");
+        }
     }
 
     Ok(())
 }
 
+/// Finishes the query with the terminal answer (when the self-correction can't go on)
+async fn finish_terminal(
+    tx: &Sender<Bytes>,
+    messages: &Arc<Mutex<Messages>>,
+    query: &QueryHandle,
+    text: String,
+) -> Result<()> {
+    messages
+        .lock()
+        .await
+        .add_assistant(vec![text.as_str().into()], vec![]);
+    query.accept_answer().await;
+
+    tx.send(Event::answer(text))?;
+    tx.send(Event::finish())?;
+    query.save_turn().await
+}
+
 /// Handles the agent task or pendings it
 #[async_recursion]
 #[log(skip_all, fields(tid))]
 pub async fn handle_task(tid: i64, tx: Sender<Bytes>, tasks: Arc<Mutex<Tasks>>) {
     let mut lock = tasks.lock().await;
+    if lock.query.is_cancelled() {
+        return;
+    }
     let Some(task) = lock.pending.remove(&tid) else {
         return;
     };
@@ -373,13 +829,23 @@ pub async fn handle_task(tid: i64, tx: Sender<Bytes>, tasks: Arc<Mutex<Tasks>>)
             if let Err(e) = handle_agent(
                 task.agent.clone(),
                 session,
-                messages,
+                messages.clone(),
                 tx.clone(),
                 task.clone(),
             )
             .await
             {
+                // the errors of the rejected plan tasks are dropped:
+                if !task.wait_plan().await {
+                    return;
+                }
+
                 error!("{e}");
+                messages
+                    .lock()
+                    .await
+                    .push_content(Some(&task.tool_call_id), str!("Error: {e}"));
+
                 // send error to client
                 task.tx
                     .send(Event::error(str!("{e}")).task_info(task.info()))
@@ -404,9 +870,13 @@ pub async fn handle_agent(
     session: Arc<Mutex<Session>>,
     messages: Arc<Mutex<Messages>>,
     tx: Sender<Bytes>,
-    task: Task,
+    mut task: Task,
 ) -> Result<()> {
     let arc_name = arc!(task.agent.clone());
+    let query = task.tasks.lock().await.query.clone();
+
+    // 0. Applying the JS transforms over the dependency results
+    task.query = task.transform_query().await?;
 
     // 1. Checking the agent for existence
     let (sock_path, prompt, _skills) = match Manager::ensure_agent(&arc_name).await {
@@ -416,18 +886,35 @@ pub async fn handle_agent(
         }
     };
 
-    // 2. Getting tools via IPC
+    // 2. Getting the cached agent tools
     let client = Client::ipc(&sock_path.to_string_lossy());
-    let response = client
-        .post("/tools/list")
-        .header("Content-Type", "application/json")
-        .json(&json!({ "skills": task.skills }))
-        .send()
-        .await?;
-    let tools = response.json::<Vec<anylm::api::Tool>>().await?;
+    let mut tools = Manager::agent_tools(&arc_name, &task.skills)
+        .await
+        .unwrap_or_default();
+
+    // the missing cache is refetched from the started worker:
+    if tools.is_empty() {
+        warn!("Agent `{}` tools cache is empty. Refetching...", task.agent);
+        Manager::refresh_tools(&arc_name).await?;
+        tools = Manager::agent_tools(&arc_name, &task.skills)
+            .await
+            .unwrap_or_default();
+    }
+    if tools.is_empty() {
+        return Err(Error::AgentToolsUnavailable {
+            name: task.agent.clone(),
+            skills: task.skills.join(", "),
+        }
+        .into());
+    }
 
     // warn!("Received Tools List: {tools:#?}"); // DEBUG
 
+    // the early started task waits for the plan acceptance (the agent tools have side effects)
+    if !task.wait_plan().await {
+        return Ok(());
+    }
+
     // logging and sending the start event
     let log_query = task
         .query
@@ -469,8 +956,8 @@ pub async fn handle_agent(
 
     let agent_messages = Messages::new()
         .system(vec![
-            format!("{}{}", system_pr, context_str).into(),
             prompt.trim().into(),
+            format!("{}{}", system_pr, context_str).into(),
         ])
         .user(vec![
             str!("{prompt}\n\n{query}",
@@ -482,125 +969,149 @@ pub async fn handle_agent(
 
     // warn!("{agent_messages:#?}"); // DEBUG
 
-    let mut tool_calls = vec![];
+    let mut agent_contents = vec![]; // the tool results (passed to the dependent tasks)
     let mut retry_count = 0;
     let max_retries = exec_options.max_retries.max(1);
-
-    // the self-healing cycle
+    let mut step = 0;
+    let max_steps = exec_options.max_agent_steps.max(1);
+    let mut direct_call = task.direct.clone().map(|call| ToolCall {
+        id: task.tool_call_id.clone(),
+        kind: str!("function"),
+        func: ToolCallFunction {
+            name: call.tool,
+            json_str: call.args,
+        },
+    });
+
+    // the self-healing (and tool calling) cycle
     loop {
-        tool_calls = vec![];
+        let mut tool_calls = vec![];
         let mut text_response = str!();
 
-        let response_res = Completions::try_from(options.clone())?
-            .tools(tools.clone())
-            .send(agent_messages.clone())
-            .await;
+        // the direct tool call is executed without the agent model:
+        if let Some(call) = direct_call.take() {
+            tool_calls.push(call);
+        } else {
+            let response_res = Completions::try_from(options.clone())?
+                .tools(tools.clone())
+                .send(agent_messages.clone())
+                .await;
+
+            match response_res {
+                Ok(mut response) => {
+                    let mut chunk_error = None;
+                    while let Some(chunk) = response.next().await {
+                        match chunk {
+                            Ok(Chunk::Text(text_part)) => {
+                                text_response.push_str(&text_part);
+                                tx.send(Event::answer(text_part).task_info(task.info()))?;
+                            }
+
+                            Ok(Chunk::Tool(tool_call)) => {
+                                tool_calls.push(tool_call);
+                            }
 
-        match response_res {
-            Ok(mut response) => {
-                let mut chunk_error = None;
-                while let Some(chunk) = response.next().await {
-                    match chunk {
-                        Ok(Chunk::Text(text_part)) => {
-                            text_response.push_str(&text_part);
-                            tx.send(Event::answer(text_part).task_info(task.info()))?;
+                            Err(e) => {
+                                chunk_error = Some(e);
+                                break;
+                            }
                         }
+                    }
 
-                        Ok(Chunk::Tool(tool_call)) => {
-                            tool_calls.push(tool_call);
+                    // self-healing in case of a stream error
+                    if let Some(err) = chunk_error {
+                        retry_count += 1;
+                        if retry_count < max_retries {
+                            warn!(
+                                "Error reading stream from agent `{}`. Retrying ({retry_count}/{max_retries}): {err}",
+                                task.agent
+                            );
+                            tx.send(
+                                Event::think(str!(
+                                    "Stream error. Healing and retrying `{}` agent execution...",
+                                    task.agent
+                                ))
+                                .task_info(task.info()),
+                            )
+                            .ok();
+                            agent_messages.lock().await.add_user(vec![
+                                format!("An error occurred during output generation: {err}. Please try again and complete the task using the available tools.").into()
+                            ]);
+                            continue;
+                        } else {
+                            return Err(str!(
+                                "Agent `{}` failed after stream error: {err}",
+                                task.agent
+                            )
+                            .into());
                         }
+                    }
 
-                        Err(e) => {
-                            chunk_error = Some(e);
-                            break;
+                    // self-healing with an empty response without calling tools (on the first step)
+                    if tool_calls.is_empty() && text_response.trim().is_empty() && step == 0 {
+                        retry_count += 1;
+                        if retry_count < max_retries {
+                            warn!(
+                                "Agent `{}` returned empty response and no tool calls. Retrying ({retry_count}/{max_retries})...",
+                                task.agent
+                            );
+                            tx.send(Event::think(str!("Agent `{}` returned empty response. Self-healing task execution...", task.agent)).task_info(task.info())).ok();
+                            agent_messages.lock().await.add_user(vec![
+                                "You did not call any tools. Please execute the requested task using the available tools now.".into()
+                            ]);
+                            continue;
+                        } else {
+                            return Err(str!(
+                                "Agent `{}` failed to execute task after {} retries: empty output",
+                                task.agent,
+                                max_retries
+                            )
+                            .into());
                         }
                     }
                 }
 
-                // self-healing in case of a stream error
-                if let Some(err) = chunk_error {
+                Err(e) => {
                     retry_count += 1;
                     if retry_count < max_retries {
                         warn!(
-                            "Error reading stream from agent `{}`. Retrying ({retry_count}/{max_retries}): {err}",
+                            "Failed to send request to Completions for agent `{}`. Retrying ({retry_count}/{max_retries}): {e}",
                             task.agent
                         );
                         tx.send(
                             Event::think(str!(
-                                "Stream error. Healing and retrying `{}` agent execution...",
+                                "Request error. Healing and retrying `{}` agent execution...",
                                 task.agent
                             ))
                             .task_info(task.info()),
                         )
                         .ok();
                         agent_messages.lock().await.add_user(vec![
-                            format!("An error occurred during output generation: {err}. Please try again and complete the task using the available tools.").into()
+                            format!("Failed to process request due to error: {e}. Please attempt to execute the task again using tools.").into()
                         ]);
                         continue;
                     } else {
                         return Err(str!(
-                            "Agent `{}` failed after stream error: {err}",
-                            task.agent
-                        )
-                        .into());
-                    }
-                }
-
-                // self-healing with an empty response without calling tools
-                if tool_calls.is_empty() && text_response.trim().is_empty() {
-                    retry_count += 1;
-                    if retry_count < max_retries {
-                        warn!(
-                            "Agent `{}` returned empty response and no tool calls. Retrying ({retry_count}/{max_retries})...",
+                            "Agent `{}` failed sending completions request: {e}",
                             task.agent
-                        );
-                        tx.send(Event::think(str!("Agent `{}` returned empty response. Self-healing task execution...", task.agent)).task_info(task.info())).ok();
-                        agent_messages.lock().await.add_user(vec![
-                            "You did not call any tools. Please execute the requested task using the available tools now.".into()
-                        ]);
-                        continue;
-                    } else {
-                        return Err(str!(
-                            "Agent `{}` failed to execute task after {} retries: empty output",
-                            task.agent,
-                            max_retries
                         )
                         .into());
                     }
                 }
             }
-
-            Err(e) => {
-                retry_count += 1;
-                if retry_count < max_retries {
-                    warn!(
-                        "Failed to send request to Completions for agent `{}`. Retrying ({retry_count}/{max_retries}): {e}",
-                        task.agent
-                    );
-                    tx.send(
-                        Event::think(str!(
-                            "Request error. Healing and retrying `{}` agent execution...",
-                            task.agent
-                        ))
-                        .task_info(task.info()),
-                    )
-                    .ok();
-                    agent_messages.lock().await.add_user(vec![
-                        format!("Failed to process request due to error: {e}. Please attempt to execute the task again using tools.").into()
-                    ]);
-                    continue;
-                } else {
-                    return Err(str!(
-                        "Agent `{}` failed sending completions request: {e}",
-                        task.agent
-                    )
-                    .into());
-                }
-            }
         }
 
         // if there are no tools to call - exit the generation cycle
         if tool_calls.is_empty() {
+            // the final agent answer is a part of the task results
+            if step > 0 && !text_response.trim().is_empty() {
+                let content_item: Content = text_response.into();
+                agent_contents.push(content_item.clone());
+                messages
+                    .lock()
+                    .await
+                    .push_content(Some(&task.tool_call_id), content_item);
+            }
             break;
         }
 
@@ -613,8 +1124,10 @@ pub async fn handle_agent(
             let arc_name = arc_name.clone();
             let tx = tx.clone();
             let task = task.clone();
+            let query = query.clone();
 
             workers.spawn(async move {
+                let call_id = tool_call.id;
                 let func = tool_call.func;
                 let log_json = func.json_str.replace('\n', "\\n");
 
@@ -632,82 +1145,133 @@ pub async fn handle_agent(
                 let request_path = format!("/tools/call/{}", func.name);
                 let request_body = func.parse_args::<JsonValue>()?;
 
-                // sending a request to the agent's server
-                let mut response = client
-                    .post(&request_path)
-                    .header("Content-Type", "application/json")
-                    .json(&request_body)
+                // the tool call deadline (propagated to the agent)
+                let timeout = Settings::get()
+                    .execution
+                    .timeouts
+                    .tool_timeout(&task.agent, &func.name);
+                let deadline = timeout
+                    .and_then(|timeout| chrono::Duration::from_std(timeout).ok())
+                    .map(|timeout| (Utc::now() + timeout).to_rfc3339());
+
+                query.add_call(&call_id, &sock_path).await;
+
+                // the agent isn't stopped as idle during the call:
+                let agent_call = Manager::begin_call(&arc_name).await;
+
+                let call = async {
+                    // sending a request to the agent's server
+                    let mut response = tool_request(
+                        &client,
+                        &request_path,
+                        &call_id,
+                        deadline.as_deref(),
+                        &request_body,
+                    )
                     .stream::<Event>()
                     .await;
 
-                // tactical restart
-                if response.is_err() {
-                    warn!(
-                        "Agent `{}` didn't respond. Attempting tactical restart...",
-                        task.agent
-                    );
-                    tx.send(
-                        Event::think(str!(
-                            "Connection lost. Restarting `{}` agent...",
+                    // tactical restart (the crash is accounted by the manager)
+                    if response.is_err() {
+                        warn!(
+                            "Agent `{}` didn't respond. Attempting tactical restart...",
                             task.agent
-                        ))
-                        .task_info(task.info()),
-                    )
-                    .ok();
-
-                    let _ = Manager::stop(arc_name.clone()).await;
+                        );
+                        tx.send(
+                            Event::think(str!(
+                                "Connection lost. Restarting `{}` agent...",
+                                task.agent
+                            ))
+                            .task_info(task.info()),
+                        )
+                        .ok();
 
-                    if let Ok(Some((_, _, _))) = Manager::ensure_agent(&arc_name).await {
-                        response = Client::ipc(&sock_path.to_string_lossy())
-                            .post(&request_path)
-                            .header("Content-Type", "application/json")
-                            .json(&request_body)
+                        if let Ok(Some((_, _, _))) = Manager::ensure_agent(&arc_name).await {
+                            response = tool_request(
+                                &Client::ipc(&sock_path.to_string_lossy()),
+                                &request_path,
+                                &call_id,
+                                deadline.as_deref(),
+                                &request_body,
+                            )
                             .stream::<Event>()
                             .await;
+                        }
                     }
-                }
 
-                let mut stream = match response {
-                    Ok(res) => res,
-                    Err(e) => {
-                        return Err(str!(
-                            "Agent `{}` crashed and failed to recover: {e}",
-                            task.agent
-                        )
-                        .into());
+                    let mut stream = response.map_err(|e| {
+                        str!("Agent `{}` crashed and failed to recover: {e}", task.agent)
+                    })?;
+
+                    let mut full_text = str!();
+                    while let Some(event) = stream.recv().await? {
+                        match event.kind {
+                            EventKind::Answer => {
+                                full_text.push_str(&event.text);
+                                tx.send(Event::answer(event.text).task_info(task.info()))?;
+                            }
+                            EventKind::Finish => {}
+                            _ => {
+                                tx.send(event.task_info(task.info()))?;
+                            }
+                        }
                     }
-                };
 
-                let mut full_text = str!();
+                    Ok::<String, DynError>(full_text)
+                };
 
-                while let Some(event) = stream.recv().await? {
-                    match event.kind {
-                        EventKind::Answer => {
-                            full_text.push_str(&event.text);
-                            tx.send(Event::answer(event.text).task_info(task.info()))?;
-                        }
-                        EventKind::Finish => {}
-                        _ => {
-                            tx.send(event.task_info(task.info()))?;
-                        }
+                let result = match timeout {
+                    Some(timeout) => {
+                        tokio::time::timeout(timeout, call)
+                            .await
+                            .unwrap_or_else(|_| {
+                                Err(Error::ToolTimeout {
+                                    agent: task.agent.clone(),
+                                    tool: func.name.clone(),
+                                    secs: timeout.as_secs(),
+                                }
+                                .into())
+                            })
                     }
-                }
+                    None => call.await,
+                };
 
-                Ok::<String, DynError>(full_text)
+                query.remove_call(&call_id).await;
+                drop(agent_call);
+                Manager::touch(&arc_name).await;
+                Ok::<_, DynError>((call_id, result))
             });
         }
 
         // collecting the results as they are completed and instantly recording them in the history
+        let mut timed_out = vec![];
         while let Some(worker_result) = workers.join_next().await {
-            let full_text: String =
+            let (call_id, result) =
                 worker_result.map_err(|e| str!("Worker task panicked: {e}"))??;
+            let full_text = match result {
+                Ok(full_text) => full_text,
+
+                // the timed out tool call is returned to the model as a structured error
+                Err(e) if matches!(e.downcast_ref::<Error>(), Some(Error::ToolTimeout { .. })) => {
+                    warn!("{e}");
+                    tx.send(Event::think(str!("{e}")).task_info(task.info()))
+                        .ok();
+
+                    let text = json!({ "error": "timeout", "message": e.to_string() }).to_string();
+                    timed_out.push(e.to_string());
+                    text
+                }
+
+                Err(e) => return Err(e),
+            };
             let content_item: Content = full_text.into();
+            agent_contents.push(content_item.clone());
 
             // write pointwise to the local context to continue generation in the loop
             agent_messages
                 .lock()
                 .await
-                .push_content(None, content_item.clone());
+                .push_content(Some(&call_id), content_item.clone());
 
             // save the intermediate stage to the global message history of the main chat
             messages
@@ -716,52 +1280,110 @@ pub async fn handle_agent(
                 .push_content(Some(&task.tool_call_id), content_item);
         }
 
-        // cycle has completed without errors, stopping it
-        break;
+        // the direct tool call is completed (its result is reviewed by the control query)
+        if task.direct.is_some() {
+            break;
+        }
+
+        // the tool calling steps limit
+        step += 1;
+        if step >= max_steps {
+            warn!(
+                "Agent `{}` reached the steps limit ({max_steps}), stopping the task",
+                task.agent
+            );
+            break;
+        }
+
+        // self-healing in case of the tool calls timeouts
+        if !timed_out.is_empty() {
+            retry_count += 1;
+            if retry_count < max_retries {
+                warn!(
+                    "Agent `{}` tool calls timed out. Retrying ({retry_count}/{max_retries})...",
+                    task.agent
+                );
+                agent_messages.lock().await.add_user(vec![
+                    format!("Some tool calls timed out: {}. Retry them if it makes sense, or use another strategy to complete the task.", timed_out.join("; ")).into()
+                ]);
+                continue;
+            } else {
+                return Err(str!(
+                    "Agent `{}` failed to execute task: {}",
+                    task.agent,
+                    timed_out.join("; ")
+                )
+                .into());
+            }
+        }
+
+        // the tool results are fed back to the agent for the next step
+        tx.send(
+            Event::think(str!(
+                "Agent `{}` step {}/{max_steps}...",
+                task.agent,
+                step + 1
+            ))
+            .task_info(task.info()),
+        )
+        .ok();
     }
 
-    // take the accumulated results
-    let agent_contents = {
-        let mut local_lock = agent_messages.lock().await;
-        std::mem::take(&mut local_lock.messages)
-            .into_iter()
-            .filter(|msg| !msg.role.is_assistant() && !msg.role.is_user())
-            .flat_map(|msg| msg.content)
-            .collect::<Vec<Content>>()
-    };
+    // keep the agent resident while it's in use
+    Manager::touch(&arc_name).await;
 
     // completing the task in the client and pool
     tx.send(Event::finish().task_info(task.info())).ok();
-    task.finish(agent_contents).await;
+    let is_last = task.finish(agent_contents).await;
 
     // send control query (self-correction loop)
-    if task.is_last().await {
+    if is_last && !query.is_cancelled() {
         info!("All parallel tasks completed. Launching control query...");
 
         let control_msg = Message::user(vec![settings.completions.control_prompt.as_str().into()]);
         let sid = session.lock().await.id;
-
-        if let Err(e) = handle_query(
-            sid,
-            tx.clone(),
-            session.clone(),
-            messages.clone(),
-            control_msg,
-        )
-        .await
-        {
-            error!("[verification_loop{{sid={sid}}}] Failed to restart query loop: {e}");
-            tx.send(Event::error(str!(e))).ok();
-        }
+        let (tx, session, messages) = (tx.clone(), session.clone(), messages.clone());
+
+        query
+            .clone()
+            .spawn(async move {
+                if let Err(e) =
+                    handle_query(sid, tx.clone(), session, messages, control_msg, None, query).await
+                {
+                    error!("[verification_loop{{sid={sid}}}] Failed to restart query loop: {e}");
+                    tx.send(Event::error(str!(e))).ok();
+                }
+            })
+            .await;
     }
 
     Ok(())
 }
 
+/// Builds the agent tool call request
+fn tool_request(
+    client: &Client,
+    path: &str,
+    call_id: &str,
+    deadline: Option<&str>,
+    body: &JsonValue,
+) -> reqwest::RequestBuilder {
+    let request = client
+        .post(path)
+        .header("Content-Type", "application/json")
+        .header("X-Call-Id", call_id);
+
+    match deadline {
+        Some(deadline) => request.header("X-Deadline", deadline),
+        None => request,
+    }
+    .json(body)
+}
+
 /// Generates the system prompt
 fn system_prompt(info: &SessionInfo, settings: &Settings) -> String {
-    let now_utc = Utc::now();
-    let now_local = now_local(info.timezone);
+    let now_utc = prompt_now(settings.completions.time_granularity);
+    let now_local = now_local(now_utc, info.timezone);
 
     settings
         .completions
@@ -787,12 +1409,20 @@ fn system_prompt(info: &SessionInfo, settings: &Settings) -> String {
         )
 }
 
+/// Returns the current time rounded down to the granularity in seconds
+fn prompt_now(granularity: u64) -> DateTime<Utc> {
+    let now = Utc::now();
+    let granularity = granularity.max(1) as i64;
+    let timestamp = now.timestamp() - now.timestamp().rem_euclid(granularity);
+
+    DateTime::from_timestamp(timestamp, 0).unwrap_or(now)
+}
+
 /// Returns the session local date time
-fn now_local(timezone_m: i16) -> DateTime<FixedOffset> {
+fn now_local(utc_now: DateTime<Utc>, timezone_m: i16) -> DateTime<FixedOffset> {
     let offset_seconds = (timezone_m as i32) * 60;
     let tz =
         FixedOffset::east_opt(offset_seconds).unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
 
-    let utc_now = Utc::now();
     utc_now.with_timezone(&tz)
 }
@@ -1,10 +1,12 @@
-use crate::{prelude::*, session::Session};
+use crate::{
+    context::compact,
+    prelude::*,
+    session::{Session, export, retention},
+};
 
-use anylm::{
-    api::{Message, Messages},
-    completions::{Chunk, Completions},
+use ovsy_share::{
+    CompactQuery, Event, ExportFormat, ExportQuery, ForkQuery, PruneQuery, SessionId, SessionInfo,
 };
-use ovsy_share::{CompactQuery, Event, SessionId, SessionInfo};
 
 /// Initializes the user session and returns its messages
 #[log(skip_all, fields(sid = %sid.0))]
@@ -63,102 +65,80 @@ pub async fn handle_compact(sid: Paths<SessionId>, data: Json<CompactQuery>) ->
             let preserve_count = preserve.unwrap_or(cfg.execution.preserve_messages);
             info!("Compressing session messages (preserve: {preserve_count})");
 
-            let compression_cfg = cfg.compression.clone();
-
             // get session from the global state
-            let Some(session_shared) = Session::get(&session_id) else {
-                let err_msg = format!("Undefined session id `{session_id}`");
-                error!("{err_msg}");
-                tx.send(Event::error(err_msg)).ok();
-                return;
-            };
-
-            // read the messages after logging into the session
-            let db_messages = match session_shared.lock().await.read_messages().await {
-                Ok(msgs) => msgs,
+            let session_shared = match Session::open(session_id).await {
+                Ok(session) => session,
                 Err(e) => {
-                    error!("Failed to read messages for compression: {e}");
+                    error!("Failed to open session {session_id}: {e}");
                     tx.send(Event::error(e.to_string())).ok();
                     return;
                 }
             };
 
-            let compress_count = db_messages.len();
-            if compress_count == 0 {
-                warn!("Nothing to compress, skip");
-                tx.send(Event::finish()).ok();
-                return;
-            }
-
-            let mut messages = Messages::from(db_messages);
-
-            // select the messages that need to be left untouched
-            let to_preserve: Vec<Message> = messages.slice(-(preserve_count as isize)).into();
-
-            // creating a prompt to compress the history
-            let messages = messages.user(vec![compression_cfg.prompt.into()]).wrap();
-
-            // sending a request to the LLM
-            let ops = compression_cfg
-                .options
-                .clone()
-                .unwrap_or(cfg.completions.options.clone());
-            let mut response = match Completions::try_from(ops) {
-                Ok(comp) => match comp.send(messages).await {
-                    Ok(res) => res,
-                    Err(e) => {
-                        error!("Failed to send compression request to LLM: {e}");
-                        tx.send(Event::error(e.to_string())).ok();
-                        return;
-                    }
-                },
+            // stream the summary to the user and rewrite the history
+            match compact::compact_history(&session_shared, preserve_count, Some(&tx)).await {
+                Ok(_) => {
+                    // successful finish the stream
+                    tx.send(Event::finish()).ok();
+                    info!("Compression finished successfully for session {session_id}");
+                }
                 Err(e) => {
-                    error!("Failed to prepare LLM completions config: {e}");
+                    error!("Failed to compress session history: {e}");
                     tx.send(Event::error(e.to_string())).ok();
-                    return;
-                }
-            };
-
-            let mut full_compressed_text = String::new();
-
-            // stream the response to the user and collect the full text
-            while let Some(chunk) = response.next().await {
-                match chunk {
-                    Ok(Chunk::Text(text_part)) => {
-                        if tx.send(Event::answer(text_part.clone())).is_err() {
-                            warn!("Stream receiver dropped by client, aborting compression");
-                            return;
-                        }
-                        full_compressed_text.push_str(&text_part);
-                    }
-                    Ok(_) => {}
-                    Err(e) => {
-                        error!("Error during LLM streaming: {e}");
-                        tx.send(Event::error(e.to_string())).ok();
-                        return;
-                    }
                 }
             }
+        }
+        .instrument(current)
+    })
+}
+
+/// Copies the session history into a new session
+#[log(skip_all, fields(sid = %sid.0))]
+pub async fn handle_fork(sid: Paths<SessionId>, query: Query<ForkQuery>) -> Response {
+    let session_id = sid.0;
+
+    match Session::fork(session_id, query.at).await {
+        Ok(summary) => {
+            info!("Forked session {session_id} into {}", summary.id);
+            Response::ok().json(&summary)
+        }
+        Err(e) => {
+            error!("Failed to fork session {session_id}: {e}");
+            Response::bad_request().text(e.to_string())
+        }
+    }
+}
+
+/// Removes the session with its database
+#[log(skip_all, fields(sid = %sid.0))]
+pub async fn handle_delete(sid: Paths<SessionId>) -> Response {
+    let session_id = sid.0;
 
-            // rewriting the history in the database: put the compressed version and shift the saved messages
-            let compressed_message = Message::assistant(vec![full_compressed_text.into()], vec![]);
-            if let Err(e) = session_shared
-                .lock()
-                .await
-                .insert_and_shift(compressed_message, to_preserve, compress_count)
-                .await
-            {
-                error!("Failed to update DB with compressed history: {e}");
-                tx.send(Event::error(e.to_string())).ok();
-                return;
+    match Session::delete(session_id).await {
+        Ok(_) => {
+            info!("Session {session_id} deleted");
+            Response::ok().text("Session deleted successfully")
+        }
+        Err(e) => match e.downcast_ref::<Error>() {
+            Some(Error::UnknownSessionId(_)) => Response::not_found().text(e.to_string()),
+            _ => {
+                error!("Failed to delete session {session_id}: {e}");
+                Response::error().text(e.to_string())
             }
+        },
+    }
+}
 
-            // successful finish the stream
-            tx.send(Event::finish()).ok();
-            info!("Compression finished successfully for session {session_id}");
+/// Removes the sessions out of the retention policy (only lists them, if dry run)
+#[log(skip_all)]
+pub async fn handle_prune(query: Query<PruneQuery>) -> Response {
+    match retention::prune(query.dry_run).await {
+        Ok(pruned) => Response::ok().json(&pruned),
+        Err(e) => {
+            error!("Failed to prune the sessions: {e}");
+            Response::bad_request().text(e.to_string())
         }
-        .instrument(current)
-    })
+    }
 }
 
 /// Completely clears the session message history
@@ -167,14 +147,41 @@ pub async fn handle_clear(sid: Paths<SessionId>) -> Response {
     let session_id = sid.0;
     info!("Clearing history for session: {session_id}");
 
-    if let Some(session_shared) = Session::get(&session_id) {
-        if let Err(e) = session_shared.lock().await.clear().await {
-            error!("Failed to clear session {session_id}: {e}");
-            return Response::bad_request().text(e.to_string());
+    match Session::open(session_id).await {
+        Ok(session_shared) => {
+            if let Err(e) = session_shared.lock().await.clear().await {
+                error!("Failed to clear session {session_id}: {e}");
+                return Response::bad_request().text(e.to_string());
+            }
         }
-    } else {
-        warn!("Attempted to clear non-existent session {session_id}");
+        Err(e) => warn!("Attempted to clear non-existent session {session_id}: {e}"),
     }
 
     Response::ok()
 }
+
+/// Exports the session history (JSON archive or Markdown transcript)
+#[log(skip_all, fields(sid = %sid.0))]
+pub async fn handle_export(sid: Paths<SessionId>, query: Query<ExportQuery>) -> Response {
+    let session_id = sid.0;
+
+    let data = match Session::export(session_id).await {
+        Ok(data) => data,
+        Err(e) => {
+            error!("Failed to export session {session_id}: {e}");
+            return Response::not_found().text(e.to_string());
+        }
+    };
+    info!(
+        "Exported {} messages as {:?}",
+        data.messages.len(),
+        query.format
+    );
+
+    match query.format {
+        ExportFormat::Json => Response::ok().json(&data),
+        ExportFormat::Markdown => Response::ok()
+            .content_type("text/markdown; charset=utf-8")
+            .body(export::to_markdown(&data)),
+    }
+}
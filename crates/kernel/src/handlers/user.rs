@@ -1,13 +1,17 @@
-use crate::prelude::*;
-use ovsy_share::{SessionId, UserSessionsQuery};
-use tokio::fs;
+use crate::{
+    context::history,
+    prelude::*,
+    session::{Session, store::Store},
+};
+use ovsy_share::{
+    HistorySearchQuery, SessionExport, SessionSummary, SessionsSort, UserSessionsQuery,
+};
+use std::cmp::Reverse;
 
 /// Handles the user sessions list
 #[log(skip_all, fields(uid = %uid.0))]
 pub async fn handle_list(uid: Paths<u128>, data: Json<UserSessionsQuery>) -> Response {
-    let UserSessionsQuery { limit } = data.0;
-
-    match search_sessions(uid.0, limit).await {
+    match list_sessions(uid.0, &data.0).await {
         Ok(sessions) => Response::ok().json(&sessions),
         Err(e) => {
             error!("{e}");
@@ -16,46 +20,49 @@ pub async fn handle_list(uid: Paths<u128>, data: Json<UserSessionsQuery>) -> Res
     }
 }
 
-/// Retrieves the session messages and initializes the session if it doesn't exist
-#[log(skip_all)]
-async fn search_sessions(user_id: u128, limit: usize) -> Result<Vec<SessionId>> {
-    let sessions_dir = path!("$share$/userdata/{user_id}/sessions");
-
-    if !sessions_dir.exists() {
-        return Ok(vec![]);
-    }
+/// Imports the exported session history as a new user session
+#[log(skip_all, fields(uid = %uid.0))]
+pub async fn handle_import(uid: Paths<u128>, data: Json<SessionExport>) -> Response {
+    let source_id = data.id;
 
-    // open sessions dir:
-    let mut entries = match fs::read_dir(&sessions_dir).await {
-        Ok(entries) => entries,
-        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-            return Ok(Vec::new());
+    match Session::import(uid.0, data.0).await {
+        Ok(summary) => {
+            info!("Imported session {source_id} as {}", summary.id);
+            Response::ok().json(&summary)
         }
-        Err(e) => return Err(e.into()),
-    };
-
-    let mut sessions = Vec::new();
-
-    // read all session ids:
-    while let Some(entry) = entries.next_entry().await? {
-        let file_type = entry.file_type().await?;
+        Err(e) => {
+            error!("Failed to import session {source_id}: {e}");
+            Response::bad_request().text(e.to_string())
+        }
+    }
+}
 
-        if file_type.is_dir() {
-            if let Some(file_name_str) = entry.file_name().to_str() {
-                if let Ok(session_id) = file_name_str.parse::<SessionId>() {
-                    sessions.push(session_id);
-                }
-            }
+/// Searches the user conversations history
+#[log(skip_all, fields(uid = %uid.0))]
+pub async fn handle_search(uid: Paths<u128>, query: Query<HistorySearchQuery>) -> Response {
+    match history::search(uid.0, &query.q, query.limit, None).await {
+        Ok(hits) => Response::ok().json(&hits),
+        Err(e) => {
+            error!("Failed to search the history for '{}': {e}", query.q);
+            Response::bad_request().text(e.to_string())
         }
     }
+}
 
-    // sorting by timestamp:
-    sessions.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+/// Returns the page of the user sessions summaries
+#[log(skip_all)]
+async fn list_sessions(user_id: u128, query: &UserSessionsQuery) -> Result<Vec<SessionSummary>> {
+    let mut sessions = Store::open(user_id).await?.summaries(user_id).await?;
 
-    // cut final list by limit:
-    if limit > 0 && sessions.len() > limit {
-        sessions.truncate(limit);
+    match query.sort {
+        SessionsSort::LastActivity => sessions.sort_by_key(|s| Reverse(s.last_activity)),
+        SessionsSort::Created => sessions.sort_by_key(|s| Reverse(s.id.timestamp)),
     }
 
-    Ok(sessions)
+    // cut the page (limit 0 = all the rest):
+    let page = sessions.into_iter().skip(query.offset);
+    Ok(match query.limit {
+        0 => page.collect(),
+        limit => page.take(limit).collect(),
+    })
 }
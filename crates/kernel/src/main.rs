@@ -30,7 +30,7 @@ pub mod chat;
 
 use clap::{Parser, Subcommand};
 use manager::Manager;
-use pearce::Server;
+use pearce::{Server, axum::routing::delete};
 use prelude::*;
 
 pub const APP_NAME: &str = "ovsy";
@@ -53,6 +53,17 @@ enum Commands {
     Status,
     /// Refreshes the server settings & agents list
     Refresh,
+    /// Show the kernel (or agent) logs
+    Logs {
+        /// The agent name (the kernel logs are shown if omitted)
+        agent: Option<String>,
+        /// Keep printing the new log lines
+        #[arg(short, long)]
+        follow: bool,
+        /// The number of last lines to show
+        #[arg(short = 'n', long, default_value_t = 50)]
+        lines: usize,
+    },
 
     /// Serve the kernel server
     #[command(hide = true)]
@@ -77,12 +88,44 @@ enum Commands {
 
     /// Enter interactive AI chat mode
     Chat,
+    /// Manage the chat sessions
+    Session {
+        #[command(subcommand)]
+        command: SessionCommands,
+    },
 
     /// Open settings.toml in the default system editor
     #[command(alias = "conf")]
     Config,
 }
 
+/// The Ovsy CLI session commands
+#[derive(Subcommand)]
+enum SessionCommands {
+    /// Export the session history
+    Export {
+        /// The session id
+        sid: SessionId,
+        /// The export format
+        #[arg(short, long, default_value = "json", value_parser = ["json", "markdown"])]
+        format: String,
+        /// The output file (the history is printed if omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Import the session history from the JSON export file
+    Import {
+        /// The JSON export file path
+        file: PathBuf,
+    },
+    /// Remove the sessions out of the retention policy
+    Prune {
+        /// Only report the sessions to be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     use commands as cmds;
@@ -103,10 +146,26 @@ async fn main() -> Result<()> {
         //     HEALTH
         Commands::Status => cmds::health::handle_status().await,
         Commands::Refresh => cmds::health::handle_refresh().await,
+        Commands::Logs {
+            agent,
+            follow,
+            lines,
+        } => cmds::logs::handle_logs(agent, lines, follow).await,
         Commands::Config => cmds::health::handle_config().await,
 
         //     CHAT
         Commands::Chat => cmds::chat::handle_chat().await,
+
+        //     SESSIONS
+        Commands::Session { command } => match command {
+            SessionCommands::Export {
+                sid,
+                format,
+                output,
+            } => cmds::session::handle_export(sid, format, output).await,
+            SessionCommands::Import { file } => cmds::session::handle_import(file).await,
+            SessionCommands::Prune { dry_run } => cmds::session::handle_prune(dry_run).await,
+        },
     } {
         cmds::error(e);
         std::process::exit(1);
@@ -122,21 +181,43 @@ async fn serve() -> Result<()> {
     Logger::init(path!("$state$/logs"), Settings::get().server.max_logs).await?;
     Manager::init().await?;
 
+    // recover the interrupted sessions compactions:
+    if let Err(e) = session::retention::recover().await {
+        error!("Failed to recover the session databases: {e}");
+    }
+
+    // evict the idle sessions & collect the sessions garbage in background:
+    tokio::spawn(session::Session::supervise());
+    tokio::spawn(session::retention::run());
+
     // start server:
     Server::new()
         //    HEALTH
         .get("/ping", hands::health::handle_ping)
         .get("/status", hands::health::handle_status)
         .get("/refresh", hands::health::handle_refresh)
+        //    AGENTS
+        .get("/agents/{name}", hands::agent::handle_info)
         //    USERS
         .post("/users/{uid}/sessions", hands::user::handle_list)
+        .post("/users/{uid}/sessions/import", hands::user::handle_import)
+        .get("/users/{uid}/search", hands::user::handle_search)
         //    SESSIONS
+        .post("/sessions/prune", hands::session::handle_prune)
+        .route("/sessions/{sid}", delete(hands::session::handle_delete))
         .post("/sessions/{sid}/init", hands::session::handle_init)
         .post("/sessions/{sid}/finish", hands::session::handle_finish)
         .post("/sessions/{sid}/compact", hands::session::handle_compact)
         .post("/sessions/{sid}/clear", hands::session::handle_clear)
+        .post("/sessions/{sid}/fork", hands::session::handle_fork)
+        .get("/sessions/{sid}/export", hands::session::handle_export)
         //    QUERY
         .post("/sessions/{sid}/query", hands::query::handle_user_query)
+        .post(
+            "/sessions/{sid}/regenerate",
+            hands::query::handle_regenerate,
+        )
+        .post("/sessions/{sid}/cancel", hands::query::handle_cancel)
         .run(Settings::get().server.port)
         .await?;
 
@@ -1,12 +1,13 @@
-use super::Manager;
-use crate::prelude::*;
+use super::AgentLog;
+use crate::{context, prelude::*};
 
-use ovsy_share::AgentMetadata;
+use anylm::{api::Tool, embeddings::EmbeddingSearch};
+use ovsy_share::{AgentCrash, AgentInfo, AgentMetadata, AgentStatus};
 use pearce::Client;
 use std::{
-    process::Stdio,
-    sync::Arc,
-    time::{Duration, SystemTime},
+    process::{ExitStatus, Stdio},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::{
     net::UnixStream,
@@ -16,20 +17,37 @@ use tokio::{
 };
 
 /// The AI agent
-#[derive(Default, Debug, Clone)]
+#[derive(Debug)]
 pub struct Agent {
     pub exec_path: PathBuf,
     pub sock_path: PathBuf,
     pub metadata: AgentMetadata,
-    _started: Option<SystemTime>,
-    _child: Arc<Mutex<Option<Child>>>,
+    /// The binary modification time the metadata was fetched for
+    modified: Option<SystemTime>,
+    /// The running worker process (None = dormant)
+    process: Mutex<Option<Child>>,
+    /// Serializes the worker starts (the process lock is free while it wakes up)
+    starting: Mutex<()>,
+    /// The worker tools schemas cache (by skill name)
+    tools: Mutex<HashMap<String, Vec<Tool>>>,
+    /// The skills descriptions embeddings (by skill name, generated once)
+    embeddings: Mutex<HashMap<String, Vec<f32>>>,
+    /// The last agent use time
+    last_used: Mutex<Option<Instant>>,
+    /// The in-flight tool calls count (the busy agent isn't idle)
+    calls: AtomicUsize,
+    /// The recent worker crashes
+    crashes: Mutex<Vec<AgentCrash>>,
+    /// True if the agent crashed too often
+    quarantined: AtomicBool,
 }
 
-impl Agent {
-    /// Runs the agent server
-    pub async fn run(exec_path: impl Into<PathBuf>) -> Result<Option<Self>> {
-        let exec_path = exec_path.into();
+/// The maximum number of stored crash records
+const MAX_CRASH_RECORDS: usize = 20;
 
+impl Agent {
+    /// Returns the agent name by its binary path
+    pub fn name_of(exec_path: &Path) -> Result<String> {
         // extract file name
         let file_name = exec_path
             .file_stem()
@@ -38,15 +56,17 @@ impl Agent {
             .to_string();
 
         // remove the "ovsy-" prefix to get the clean agent name
-        let name = file_name
+        Ok(file_name
             .strip_prefix("ovsy-")
             .unwrap_or(&file_name)
-            .to_string();
+            .to_string())
+    }
 
-        // check agent for already running:
-        if Manager::contains(&arc!(name.clone())).await {
-            return Ok(None);
-        }
+    /// Loads the agent metadata (without running the worker)
+    pub async fn load(exec_path: impl Into<PathBuf>) -> Result<Self> {
+        let exec_path = exec_path.into();
+        let name = Self::name_of(&exec_path)?;
+        let modified = tokio::fs::metadata(&exec_path).await?.modified().ok();
 
         // fetch metadata before running the server
         let meta_output = Command::new(&exec_path).arg("metadata").output().await?;
@@ -64,10 +84,44 @@ impl Agent {
         // setup Unix Domain Socket path
         let sock_path = path!("$temp$/uds/{}.sock", name);
 
+        Ok(Self {
+            exec_path,
+            sock_path,
+            metadata,
+            modified,
+            process: Mutex::new(None),
+            starting: Mutex::new(()),
+            tools: Mutex::new(map! {}),
+            embeddings: Mutex::new(map! {}),
+            last_used: Mutex::new(None),
+            calls: AtomicUsize::new(0),
+            crashes: Mutex::new(vec![]),
+            quarantined: AtomicBool::new(false),
+        })
+    }
+
+    /// Runs the agent worker server
+    pub async fn start(&self) -> Result<()> {
+        let _starting = self.starting.lock().await;
+
+        // the worker was already started by a concurrent call:
+        {
+            let mut process = self.process.lock().await;
+            if let Some(child) = process.as_mut() {
+                if matches!(child.try_wait(), Ok(None)) {
+                    return Ok(());
+                }
+                process.take();
+            }
+        }
+
         // build server execution command
-        let mut cmd = Command::new(&exec_path);
+        let mut cmd = Command::new(&self.exec_path);
         cmd.arg("serve");
-        cmd.stdin(Stdio::piped()).kill_on_drop(true);
+        cmd.stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
         #[cfg(target_os = "linux")]
         {
@@ -81,10 +135,22 @@ impl Agent {
             }
         }
 
-        let child = cmd.spawn()?;
+        let mut child = cmd.spawn()?;
+
+        // capture the worker output:
+        let name = &self.metadata.name;
+        let pid = child.id().unwrap_or_default();
+        let log = arc_mutex!(AgentLog::open(name).await?);
+
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(AgentLog::capture(log.clone(), str!("{name}:{pid}"), stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(AgentLog::capture(log, str!("{name}:{pid}:stderr"), stderr));
+        }
 
-        // 4. Ping the server via POST /ping until it wakes up
-        let client = Client::ipc(&sock_path.to_string_lossy());
+        // ping the server via GET /ping until it wakes up
+        let client = Client::ipc(&self.sock_path.to_string_lossy());
         let mut attempts = 0;
 
         loop {
@@ -95,14 +161,13 @@ impl Agent {
 
             match request_result {
                 Ok(Ok(response)) if response.status().is_success() => {
-                    // check if response body is "pong" or returns successfully
                     break;
                 }
                 _ => {
                     if attempts >= 50 {
                         return Err(Error::AgentStartFailed {
-                            name,
-                            sock_path: sock_path.to_string_lossy().to_string(),
+                            name: self.metadata.name.clone(),
+                            sock_path: self.sock_path.to_string_lossy().to_string(),
                         }
                         .into());
                     }
@@ -113,39 +178,225 @@ impl Agent {
             }
         }
 
-        let agent = Self {
-            exec_path,
-            sock_path,
-            metadata,
-            _started: Some(SystemTime::now()),
-            _child: arc_mutex!(Some(child)),
-        };
+        // cache the tools schemas of the started worker:
+        self.fetch_tools(&client).await?;
 
-        Ok(Some(agent))
+        self.process.lock().await.replace(child);
+        Ok(())
     }
 
-    /// Returns true if needs to be updated
-    pub async fn check(&self) -> Result<bool> {
-        let is_alive = time::timeout(
-            Duration::from_millis(100),
-            UnixStream::connect(&self.sock_path),
-        )
-        .await;
+    /// Fetches the worker tools schemas to the cache
+    async fn fetch_tools(&self, client: &Client) -> Result<()> {
+        let mut tools = map! {};
+
+        for skill in &self.metadata.skills {
+            let list = client
+                .post("/tools/list")
+                .header("Content-Type", "application/json")
+                .json(&json!({ "skills": [skill.name] }))
+                .send()
+                .await?
+                .json::<Vec<Tool>>()
+                .await?;
 
-        if is_alive.is_err() || is_alive.unwrap().is_err() {
-            // agent not responding..
-            return Ok(true);
+            tools.insert(skill.name.clone(), list);
         }
 
-        // check file metadata:
-        let metadata = tokio::fs::metadata(&self.exec_path).await?;
+        *self.tools.lock().await = tools;
+        Ok(())
+    }
 
-        if let Ok(modified_at) = metadata.modified()
-            && let Some(started_at) = self._started
-        {
-            Ok(modified_at > started_at)
+    /// Returns the skills descriptions embeddings (generating the missing ones)
+    pub async fn skill_embeddings(&self) -> Result<HashMap<String, Vec<f32>>> {
+        let mut embeddings = self.embeddings.lock().await;
+
+        for skill in &self.metadata.skills {
+            if embeddings.contains_key(&skill.name) {
+                continue;
+            }
+
+            let text = str!(
+                "{}: {}\n{}: {}",
+                self.metadata.name,
+                self.metadata.description.trim(),
+                skill.name,
+                skill.description
+            );
+            let embedding = context::generate_embedding(&text, EmbeddingSearch::Document).await?;
+            embeddings.insert(skill.name.clone(), embedding);
+        }
+
+        Ok(embeddings.clone())
+    }
+
+    /// Returns the cached tools of the selected skills (or all tools)
+    pub async fn tools(&self, skills: &[String]) -> Vec<Tool> {
+        let cache = self.tools.lock().await;
+
+        self.metadata
+            .skills
+            .iter()
+            .filter(|skill| skills.is_empty() || skills.contains(&skill.name))
+            .filter_map(|skill| cache.get(&skill.name))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Refetches the tools schemas from the running worker
+    pub async fn refresh_tools(&self) -> Result<()> {
+        let client = Client::ipc(&self.sock_path.to_string_lossy());
+        self.fetch_tools(&client).await
+    }
+
+    /// Returns true if the worker tools schemas were fetched (they're kept after the stop)
+    pub async fn has_tools(&self) -> bool {
+        !self.tools.lock().await.is_empty()
+    }
+
+    /// Stops the agent worker server, returns false if it wasn't running
+    pub async fn stop(&self) -> bool {
+        match self.process.lock().await.take() {
+            Some(mut child) => {
+                child.kill().await.ok();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns true if the agent worker process is running
+    pub async fn is_resident(&self) -> bool {
+        self.process
+            .lock()
+            .await
+            .as_mut()
+            .is_some_and(|child| matches!(child.try_wait(), Ok(None)))
+    }
+
+    /// Takes the exit status of the unexpectedly exited worker process
+    pub async fn take_exited(&self) -> Option<ExitStatus> {
+        let mut process = self.process.lock().await;
+        let status = process.as_mut()?.try_wait().ok().flatten()?;
+        process.take();
+        Some(status)
+    }
+
+    /// Records the worker crash, returns true if the agent became quarantined
+    pub async fn record_crash(&self, reason: String) -> bool {
+        let settings = &Settings::get().agents;
+        let mut crashes = self.crashes.lock().await;
+
+        crashes.push(AgentCrash {
+            time: Utc::now(),
+            reason,
+        });
+        if crashes.len() > MAX_CRASH_RECORDS {
+            crashes.remove(0);
+        }
+
+        let recent = Self::count_recent(&crashes, settings.crash_window);
+        if settings.max_crashes > 0 && recent >= settings.max_crashes {
+            !self.quarantined.swap(true, Ordering::SeqCst)
         } else {
-            Ok(false)
+            false
         }
     }
+
+    /// Returns the restart delay (doubled for every recent crash)
+    pub async fn backoff(&self) -> Duration {
+        let settings = &Settings::get().agents;
+        let recent = Self::count_recent(&self.crashes.lock().await, settings.crash_window);
+        let exp = recent.saturating_sub(1).min(16) as u32;
+
+        Duration::from_millis(
+            settings
+                .restart_backoff
+                .saturating_mul(2u64.pow(exp))
+                .min(settings.max_backoff),
+        )
+    }
+
+    /// Returns true if the agent crashed too often
+    pub fn is_quarantined(&self) -> bool {
+        self.quarantined.load(Ordering::SeqCst)
+    }
+
+    /// Counts the crashes within the window
+    fn count_recent(crashes: &[AgentCrash], window: u64) -> usize {
+        let since = Utc::now() - chrono::Duration::seconds(window as i64);
+        crashes.iter().filter(|crash| crash.time >= since).count()
+    }
+
+    /// Returns true if the agent worker accepts connections
+    pub async fn is_alive(&self) -> bool {
+        matches!(
+            time::timeout(
+                Duration::from_millis(100),
+                UnixStream::connect(&self.sock_path),
+            )
+            .await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// Returns true if the agent binary was changed (or removed) since the metadata was fetched
+    pub async fn is_outdated(&self) -> bool {
+        match tokio::fs::metadata(&self.exec_path).await {
+            Ok(metadata) => metadata.modified().ok() != self.modified,
+            Err(_) => true,
+        }
+    }
+
+    /// Marks the agent as just used
+    pub async fn touch(&self) {
+        self.last_used.lock().await.replace(Instant::now());
+    }
+
+    /// Returns the time since the last agent use
+    pub async fn idle_time(&self) -> Option<Duration> {
+        self.last_used.lock().await.map(|at| at.elapsed())
+    }
+
+    /// Marks the tool call as in-flight until the returned guard is dropped
+    pub fn begin_call(self: &Arc<Self>) -> AgentCall {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        AgentCall(self.clone())
+    }
+
+    /// Returns true if the agent has the in-flight tool calls
+    pub fn is_busy(&self) -> bool {
+        self.calls.load(Ordering::SeqCst) > 0
+    }
+
+    /// Returns the agent worker status
+    pub async fn status(&self) -> AgentStatus {
+        AgentStatus {
+            name: self.metadata.name.clone(),
+            description: self.metadata.description.clone(),
+            version: self.metadata.version.clone(),
+            resident: self.is_resident().await,
+            idle_secs: self.idle_time().await.map(|time| time.as_secs()),
+            quarantined: self.is_quarantined(),
+            crashes: self.crashes.lock().await.clone(),
+        }
+    }
+
+    /// Returns the agent details with the cached tools schemas
+    pub async fn info(&self) -> AgentInfo {
+        AgentInfo {
+            status: self.status().await,
+            skills: self.metadata.skills.clone(),
+            tools: self.tools.lock().await.clone(),
+        }
+    }
+}
+
+/// The in-flight agent tool call guard
+pub struct AgentCall(Arc<Agent>);
+
+impl Drop for AgentCall {
+    fn drop(&mut self) {
+        self.0.calls.fetch_sub(1, Ordering::SeqCst);
+    }
 }
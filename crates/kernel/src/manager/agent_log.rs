@@ -0,0 +1,99 @@
+use crate::prelude::*;
+
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+};
+
+/// The rotating agent output log
+pub struct AgentLog {
+    name: String,
+    file: File,
+    size: u64,
+}
+
+impl AgentLog {
+    /// Returns the agents logs directory
+    pub fn dir() -> PathBuf {
+        path!("$state$/logs/agents")
+    }
+
+    /// Returns the actual log file path of the agent
+    pub fn path(name: &str) -> PathBuf {
+        Self::dir().join(format!("{name}.log"))
+    }
+
+    /// Returns the rotated log file path of the agent
+    fn rotated_path(name: &str, index: usize) -> PathBuf {
+        Self::dir().join(format!("{name}.{index}.log"))
+    }
+
+    /// Opens the agent log file for appending
+    pub async fn open(name: &str) -> Result<Self> {
+        fs::create_dir_all(Self::dir()).await?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::path(name))
+            .await?;
+        let size = file.metadata().await?.len();
+
+        Ok(Self {
+            name: name.to_owned(),
+            file,
+            size,
+        })
+    }
+
+    /// Writes the line to the log, rotating the file if it's too large
+    pub async fn write(&mut self, line: &str) -> Result<()> {
+        let max_size = Settings::get().agents.log_max_size;
+        if max_size > 0 && self.size > 0 && self.size + line.len() as u64 > max_size {
+            self.rotate().await?;
+        }
+
+        self.file.write_all(line.as_bytes()).await?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Shifts the rotated files & starts a new log file
+    async fn rotate(&mut self) -> Result<()> {
+        let max_files = Settings::get().agents.log_max_files;
+        let path = Self::path(&self.name);
+
+        for index in (1..max_files.saturating_sub(1)).rev() {
+            let from = Self::rotated_path(&self.name, index);
+            if from.exists() {
+                fs::rename(from, Self::rotated_path(&self.name, index + 1)).await?;
+            }
+        }
+
+        if max_files > 1 {
+            fs::rename(&path, Self::rotated_path(&self.name, 1)).await?;
+        } else {
+            fs::remove_file(&path).await?;
+        }
+
+        *self = Self::open(&self.name).await?;
+        Ok(())
+    }
+
+    /// Captures the worker output stream into the log, tagging lines with the agent name & PID
+    pub async fn capture<R>(log: Arc<Mutex<Self>>, tag: String, reader: R)
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let time = Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+            let line = format!("{time} [{tag}] {line}\n");
+
+            if let Err(e) = log.lock().await.write(&line).await {
+                warn!("Failed to write agent log: {e}");
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 pub mod agent;
-pub use agent::Agent;
+pub use agent::{Agent, AgentCall};
+
+pub mod agent_log;
+pub use agent_log::AgentLog;
 
 pub mod tasks;
 pub use tasks::Tasks;
@@ -7,10 +10,19 @@ pub use tasks::Tasks;
 pub mod task;
 pub use task::Task;
 
+pub mod query_handle;
+pub use query_handle::QueryHandle;
+
+pub mod supervisor;
+pub use supervisor::Supervisor;
+
+pub mod selection;
+pub use selection::{LATE_AGENTS_NOTE, Selection};
+
 use crate::{prelude::*, skills};
 
 use anylm::api::Tool;
-use ovsy_share::{AgentMetadata, Skill};
+use ovsy_share::{AgentInfo, AgentStatus, Skill};
 use std::fmt::Write;
 use tokio::task::JoinSet;
 
@@ -26,8 +38,21 @@ pub struct Manager {
 }
 
 impl Manager {
-    /// Initializes & runs the agents management
+    /// Initializes the agents management
     pub async fn init() -> Result<()> {
+        Self::scan().await?;
+
+        // gen task delegation tool:
+        Self::gen_basic_tools().await;
+
+        // supervise the agent workers in background:
+        tokio::spawn(Supervisor::run());
+
+        Ok(())
+    }
+
+    /// Scans the agent binaries & loads their metadata (the workers are started on demand)
+    pub async fn scan() -> Result<()> {
         let scan_dir = path!("$/");
 
         // check scan dir:
@@ -37,6 +62,7 @@ impl Manager {
         }
 
         let mut set = JoinSet::new();
+        let mut found = HashSet::new();
         let mut reader = Dir::read(scan_dir).await?;
 
         info!("Scanning for agent binaries...");
@@ -52,30 +78,75 @@ impl Manager {
 
             // check if it's an agent binary (starts with "ovsy-")
             if file_name.starts_with("ovsy-") {
-                // spawn agent running:
-                set.spawn(async move { Self::run(path).await });
+                let name = arc!(Agent::name_of(&path)?);
+                found.insert(name.clone());
+
+                // skip the actual agents (the quarantined ones are reloaded):
+                if let Some(agent) = Self::get(&name).await
+                    && !agent.is_outdated().await
+                    && !agent.is_quarantined()
+                {
+                    continue;
+                }
+
+                // spawn agent metadata loading:
+                set.spawn(async move { Self::load(path).await });
             }
         }
 
         // check results:
         while let Some(task_res) = set.join_next().await {
-            if let Err(e) = task_res {
-                error!("Agent startup task panicked: {e}");
+            match task_res {
+                Ok(Err(e)) => error!("Failed to load agent: {e}"),
+                Err(e) => error!("Agent loading task panicked: {e}"),
+                _ => {}
             }
         }
 
-        // gen task delegation tool:
-        Self::gen_basic_tools().await;
+        // forget the removed agents:
+        let removed = MANAGER
+            .get()
+            .await
+            .agents
+            .keys()
+            .filter(|name| !found.contains(*name))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for name in removed {
+            if let Some(agent) = MANAGER.lock().await.agents.remove(&name) {
+                agent.stop().await;
+                info!("Agent `{name}` binary removed, agent forgotten");
+            }
+        }
 
+        Self::update_doc().await?;
+        Self::fetch_direct_tools().await;
         Ok(())
     }
 
+    /// Fetches the tools schemas of the direct tools agents (starting the never fetched ones once)
+    async fn fetch_direct_tools() {
+        for name in Settings::get().execution.direct_tools.keys() {
+            let name = arc!(name.clone());
+
+            let fetched = match Self::get(&name).await {
+                Some(agent) => agent.is_quarantined() || agent.has_tools().await,
+                None => true,
+            };
+            if !fetched && !matches!(Self::ensure_agent(&name).await, Ok(Some(_))) {
+                warn!("Failed to fetch the `{name}` agent tools, its direct tools are skipped");
+            }
+        }
+    }
+
     /// Generates & sets the basic tools schemes
     pub async fn gen_basic_tools() {
         let tools = vec![
             skills::eval::tools_list(),
             skills::task::tools_list(),
             skills::fact::tools_list(),
+            skills::history::tools_list(),
         ]
         .into_iter()
         .flatten()
@@ -86,93 +157,138 @@ impl Manager {
 
     /// Ensures the agent is running and healthy, spawning it if necessary
     pub async fn ensure_agent(name: &Arc<String>) -> Result<Option<(PathBuf, String, Vec<Skill>)>> {
-        let needs_start = {
-            let guard = MANAGER.get().await;
-            if let Some(agent) = guard.agents.get(name) {
-                agent.check().await.unwrap_or(true)
-            } else {
-                true
+        // reload the agent metadata if its binary was changed:
+        let agent = match Self::get(name).await {
+            Some(agent) if !agent.is_outdated().await => agent,
+            _ => {
+                let agent_bin = path!("$/").join(format!("ovsy-{name}"));
+
+                if !agent_bin.exists() {
+                    warn!("Agent `{name}` requested but binary not found at {agent_bin:?}");
+                    return Ok(None);
+                }
+
+                match Self::load(agent_bin).await {
+                    Ok(agent) => agent,
+                    Err(e) => {
+                        error!("Failed to load agent `{name}`: {e}");
+                        return Ok(None);
+                    }
+                }
             }
         };
 
-        if needs_start {
-            let binary_name = format!("ovsy-{name}",);
-            let agent_bin = path!("$/").join(binary_name);
+        if agent.is_quarantined() {
+            warn!("Agent `{name}` requested but it's quarantined");
+            return Ok(None);
+        }
+
+        // kill the unresponsive agent:
+        if agent.is_resident().await && !agent.is_alive().await {
+            warn!("Agent `{name}` is unresponsive. Restarting...");
+            agent.stop().await;
 
-            if !agent_bin.exists() {
-                warn!("Agent `{name}` requested but binary not found at {agent_bin:?}");
+            if Supervisor::crashed(name, &agent, str!("Worker is unresponsive")).await {
                 return Ok(None);
             }
+        }
 
-            info!("Agent `{name}` is missing or unresponsive. Attempting to start...");
-
-            let _ = Self::stop(name.clone()).await;
+        // start the dormant agent:
+        if !agent.is_resident().await {
+            info!("Agent `{name}` is dormant. Starting...");
 
-            if let Err(e) = Self::run(agent_bin).await {
-                error!("Failed to recover agent `{name}`: {e}");
+            if let Err(e) = agent.start().await {
+                error!("Failed to start agent `{name}`: {e}");
+                Supervisor::crashed(name, &agent, str!("Start failed: {e}")).await;
                 return Ok(None);
             }
+            info!("Agent `{name}` started");
         }
 
+        agent.touch().await;
+
         Ok(Self::agent_options(name).await)
     }
 
-    /// Runs the AI agent server
-    pub async fn run(bin_path: impl Into<PathBuf>) -> Result<()> {
+    /// Loads the AI agent metadata & registers it as dormant
+    pub async fn load(bin_path: impl Into<PathBuf>) -> Result<Arc<Agent>> {
         let path: PathBuf = bin_path.into();
-        info!("Starting agent {:?}...", path.display());
+        info!("Loading agent {:?}...", path.display());
 
-        if let Some(agent) = Agent::run(path.clone()).await? {
-            let name = arc!(agent.metadata.name.clone());
-            let mut lock = MANAGER.lock().await;
+        let agent = arc!(Agent::load(path).await?);
+        let name = arc!(agent.metadata.name.clone());
 
-            if !lock.agents.contains_key(&name) {
-                lock.agents.insert(name.clone(), arc!(agent));
-                info!("Agent `{name}` added to manager");
-            } else {
-                warn!("Agent `{name}` is already running, skipping");
-            }
+        // replace the outdated agent:
+        let prev = MANAGER
+            .lock()
+            .await
+            .agents
+            .insert(name.clone(), agent.clone());
+
+        if let Some(prev) = prev {
+            prev.stop().await;
+            info!("Agent `{name}` reloaded");
+        } else {
+            info!("Agent `{name}` added to manager");
         }
 
         Self::update_doc().await?;
-        Ok(())
+        Ok(agent)
     }
 
-    /// Stops the AI agent server
+    /// Stops the AI agent worker (the agent stays known as dormant)
     pub async fn stop(name: Arc<String>) -> Result<()> {
-        let mut lock = MANAGER.lock().await;
-        if lock.agents.remove(&name).is_some() {
-            info!("Agent `{name}` stopped and removed");
-        } else {
-            warn!("Attempted to stop unknown `{name}` agent");
+        match Self::get(&name).await {
+            Some(agent) => {
+                if agent.stop().await {
+                    info!("Agent `{name}` stopped");
+                }
+            }
+            None => warn!("Attempted to stop unknown `{name}` agent"),
         }
 
-        Self::update_doc().await?;
         Ok(())
     }
 
-    /// Updates the AI agents list
-    pub async fn update() -> Result<()> {
-        info!("Starting agents update cycle...");
+    /// Marks the agent as just used
+    pub async fn touch(name: &Arc<String>) {
+        if let Some(agent) = Self::get(name).await {
+            agent.touch().await;
+        }
+    }
 
-        // collect the list of all the outdated agents:
-        let mut to_restart = Vec::new();
-        {
-            let guard = MANAGER.get().await;
-            for (name, agent) in &guard.agents {
-                if agent.check().await? {
-                    to_restart.push(name.clone());
-                }
-            }
+    /// Marks the agent tool call as in-flight until the returned guard is dropped
+    pub async fn begin_call(name: &Arc<String>) -> Option<AgentCall> {
+        Self::get(name).await.map(|agent| agent.begin_call())
+    }
+
+    /// Stops the agent workers idle beyond the configured timeout (the busy ones are kept)
+    pub async fn evict_idle() {
+        let idle_timeout = Settings::get().agents.idle_timeout;
+        if idle_timeout == 0 {
+            return;
         }
 
-        // stop all the outdated agents:
-        for name in to_restart {
-            warn!("Agent `{}` needs update, restarting...", name);
-            Self::stop(name).await?;
+        let agents = MANAGER.get().await.agents.clone();
+        for (name, agent) in agents {
+            if !agent.is_resident().await || agent.is_busy() {
+                continue;
+            }
+
+            let idle_time = agent.idle_time().await.unwrap_or_default();
+            if idle_time.as_secs() >= idle_timeout && agent.stop().await {
+                info!(
+                    "Agent `{name}` was idle for {}s, stopped",
+                    idle_time.as_secs()
+                );
+            }
         }
+    }
 
-        Self::init().await?;
+    /// Updates the AI agents list
+    pub async fn update() -> Result<()> {
+        info!("Starting agents update cycle...");
+        Self::scan().await?;
         info!("Agents update cycle completed");
         Ok(())
     }
@@ -181,56 +297,62 @@ impl Manager {
     pub async fn update_doc() -> Result<()> {
         let guard = MANAGER.get().await;
 
+        // sorted by name, so the doc (a part of the prompt prefix) is stable:
+        let mut agents = guard
+            .agents
+            .values()
+            .filter(|agent| !agent.is_quarantined())
+            .collect::<Vec<_>>();
+        agents.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
+
         // gen message, if agents not found:
-        if guard.agents.is_empty() {
+        if agents.is_empty() {
             MANAGER.lock().await.agents_doc = arc!("No active agents available.".to_string());
             return Ok(());
         }
 
         // gen agents doc:
         let mut doc_builder = String::from("Available Agents:\n");
-        for agent in guard.agents.values() {
-            let _ = writeln!(
-                doc_builder,
-                "* Agent `{}`: \n  Description: \"{}\"\n  Skills: {}",
-                agent.metadata.name,
-                agent.metadata.description.trim().replace("\n", ""),
-                agent
-                    .metadata
-                    .skills
-                    .iter()
-                    .map(|s| str!("    * `{}`: {}", s.name, s.description))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            );
+        for agent in &agents {
+            let skills = agent.metadata.skills.iter().collect::<Vec<_>>();
+            Self::write_agent_doc(&mut doc_builder, agent, &skills);
         }
 
         MANAGER.lock().await.agents_doc = arc!(doc_builder);
-        info!(
-            "Documentation updated ({} agents listed)",
-            guard.agents.len()
-        );
+        info!("Documentation updated ({} agents listed)", agents.len());
         Ok(())
     }
 
-    /// Returns the all agents list
-    pub async fn agents_list() -> Vec<AgentMetadata> {
-        MANAGER
-            .get()
-            .await
-            .agents
-            .iter()
-            .map(|(_, agent)| AgentMetadata {
-                name: agent.metadata.name.clone(),
-                description: agent.metadata.description.clone(),
-                ..Default::default()
-            })
-            .collect()
+    /// Writes the agent with its skills to the agents list prompt part
+    pub fn write_agent_doc(doc: &mut String, agent: &Agent, skills: &[&Skill]) {
+        let _ = writeln!(
+            doc,
+            "* Agent `{}`: \n  Description: \"{}\"\n  Skills: {}",
+            agent.metadata.name,
+            agent.metadata.description.trim().replace("\n", ""),
+            skills
+                .iter()
+                .map(|s| str!("    * `{}`: {}", s.name, s.description))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    /// Returns the all agents statuses list
+    pub async fn agents_list() -> Vec<AgentStatus> {
+        let agents = MANAGER.get().await.agents.clone();
+
+        let mut list = vec![];
+        for agent in agents.values() {
+            list.push(agent.status().await);
+        }
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
     }
 
-    /// Returns true if agent with this name is already on running
-    pub async fn contains(name: &Arc<String>) -> bool {
-        MANAGER.get().await.agents.contains_key(name)
+    /// Returns the agent by name
+    pub async fn get(name: &Arc<String>) -> Option<Arc<Agent>> {
+        MANAGER.get().await.agents.get(name).cloned()
     }
 
     /// Returns the agents list prompt part
@@ -238,6 +360,47 @@ impl Manager {
         MANAGER.get().await.agents_doc.clone()
     }
 
+    /// Returns the planner tools list (the basic & direct agent tools)
+    pub async fn planner_tools() -> Vec<Tool> {
+        let mut tools = Self::basic_tools().await;
+        tools.extend(Self::direct_tools().await);
+        tools
+    }
+
+    /// Returns the namespaced agent tools called by the planner directly (from the cached schemas)
+    pub async fn direct_tools() -> Vec<Tool> {
+        let mut direct = Settings::get()
+            .execution
+            .direct_tools
+            .clone()
+            .into_iter()
+            .collect::<Vec<_>>();
+        direct.sort();
+
+        let mut tools = vec![];
+        for (name, skills) in direct {
+            let name = arc!(name);
+
+            // the schemas are fetched on scan, the agents aren't started here:
+            let cached = match Self::get(&name).await {
+                Some(agent) => !agent.is_quarantined() && agent.has_tools().await,
+                None => false,
+            };
+            if !cached {
+                continue;
+            }
+
+            let agent_tools = Self::agent_tools(&name, &skills).await.unwrap_or_default();
+            tools.extend(
+                agent_tools
+                    .iter()
+                    .filter_map(|tool| skills::direct::namespaced(&name, tool)),
+            );
+        }
+
+        tools
+    }
+
     /// Returns the bsic tools list
     pub async fn basic_tools() -> Vec<Tool> {
         (*MANAGER.get().await.tools).clone()
@@ -253,23 +416,24 @@ impl Manager {
             .map(|agent| agent.metadata.prompt.clone())
     }
 
-    /// Returns the agent tools list
-    pub async fn agent_tools(name: &Arc<String>, skill: Option<&str>) -> Result<Option<Vec<Tool>>> {
-        let mngr = MANAGER.get().await;
-        let Some(agent) = mngr.agents.get(name) else {
-            return Ok(None);
-        };
-
-        let client = Client::ipc(&agent.sock_path.to_string_lossy());
+    /// Returns the agent cached tools list of the selected skills (or all tools)
+    pub async fn agent_tools(name: &Arc<String>, skills: &[String]) -> Option<Vec<Tool>> {
+        let agent = Self::get(name).await?;
+        Some(agent.tools(skills).await)
+    }
 
-        let mut request = client.post("/tools/list");
-        if let Some(skill) = skill {
-            request = request.json(&json!({ "skill": skill }));
+    /// Refetches the agent tools schemas from its running worker
+    pub async fn refresh_tools(name: &Arc<String>) -> Result<()> {
+        match Self::get(name).await {
+            Some(agent) => agent.refresh_tools().await,
+            None => Ok(()),
         }
+    }
 
-        let tools = request.send().await?.json().await?;
-
-        Ok(Some(tools))
+    /// Returns the agent details
+    pub async fn agent_info(name: &Arc<String>) -> Option<AgentInfo> {
+        let agent = Self::get(name).await?;
+        Some(agent.info().await)
     }
 
     /// Returns the agent options (port, prompt, tools)
@@ -0,0 +1,275 @@
+use super::{Selection, Tasks};
+use crate::{
+    prelude::*,
+    session::{Session, Turn, title},
+    skills::{self, task::TaskAction},
+};
+
+use anylm::api::Messages;
+use ovsy_share::{Event, SessionId};
+use std::sync::{
+    Weak,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use tokio::{
+    sync::mpsc::WeakUnboundedSender,
+    task::{AbortHandle, JoinHandle},
+};
+
+/// The in-flight user queries (by session)
+static QUERIES: State<HashMap<SessionId, Arc<QueryHandle>>> = State::default();
+
+/// The in-flight user query handle
+pub struct QueryHandle {
+    pub sid: SessionId,
+    /// Weak client stream sender (doesn't keep the stream open)
+    tx: Option<WeakUnboundedSender<StdResult<Bytes, DynError>>>,
+    cancelled: AtomicBool,
+    /// The running planning (or control) loop
+    root: Mutex<Option<AbortHandle>>,
+    /// The active agent tasks graph
+    tasks: Mutex<Weak<Mutex<Tasks>>>,
+    /// The in-flight agent tool calls (call id -> agent socket)
+    calls: Mutex<HashMap<String, PathBuf>>,
+    /// The conversation turn being handled
+    turn: Mutex<Turn>,
+    /// The planning/control cycles counter
+    cycles: AtomicUsize,
+    /// The signatures of the already planned agent calls
+    planned: Mutex<HashSet<String>>,
+    /// The agent skills pre-selected for the query (None = all)
+    selection: Mutex<Option<Selection>>,
+}
+
+impl QueryHandle {
+    /// Registers a new in-flight query of the session (only one query of the session runs at a time)
+    pub async fn start(sid: SessionId, tx: &Sender<Bytes>) -> Result<Arc<Self>> {
+        let weak_tx = match tx {
+            Sender::Unbounded(tx) => Some(tx.downgrade()),
+            Sender::Bounded(_) => None,
+        };
+
+        let this = arc!(Self {
+            sid,
+            tx: weak_tx,
+            cancelled: AtomicBool::new(false),
+            root: Mutex::new(None),
+            tasks: Mutex::new(Weak::new()),
+            calls: Mutex::new(map! {}),
+            turn: Mutex::new(Turn::default()),
+            cycles: AtomicUsize::new(0),
+            planned: Mutex::new(set![]),
+            selection: Mutex::new(None),
+        });
+
+        let mut queries = QUERIES.lock().await;
+        if queries.get(&sid).is_some_and(|query| query.is_running()) {
+            return Err(Error::QueryInProgress(sid).into());
+        }
+        queries.insert(sid, this.clone());
+
+        Ok(this)
+    }
+
+    /// Returns the in-flight query of the session
+    pub fn get(sid: &SessionId) -> Option<Arc<Self>> {
+        QUERIES.dirty_get().get(sid).cloned()
+    }
+
+    /// Returns true if the query was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the query is still being handled (the client stream is open)
+    pub fn is_running(&self) -> bool {
+        match &self.tx {
+            Some(tx) => tx.upgrade().is_some_and(|tx| !tx.is_closed()),
+            None => !self.is_cancelled(),
+        }
+    }
+
+    /// Spawns the query loop, so it can be aborted on cancellation
+    pub async fn spawn<F>(&self, future: F) -> JoinHandle<()>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // the cancellation takes the root handle under the same lock:
+        let mut root = self.root.lock().await;
+        let handle = tokio::spawn(future);
+        if self.is_cancelled() {
+            handle.abort();
+        } else {
+            root.replace(handle.abort_handle());
+        }
+        handle
+    }
+
+    /// Sets the active agent tasks graph
+    pub async fn set_tasks(&self, tasks: &Arc<Mutex<Tasks>>) {
+        *self.tasks.lock().await = Arc::downgrade(tasks);
+    }
+
+    /// Starts the next planning/control cycle, returns its number
+    pub fn next_cycle(&self) -> usize {
+        self.cycles.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Returns true if all the agent calls were already planned in the previous cycles
+    pub async fn is_repeated_plan(&self, tasks: &[TaskAction]) -> bool {
+        let planned = self.planned.lock().await;
+        !tasks.is_empty()
+            && skills::task::plan_signatures(tasks)
+                .iter()
+                .all(|signature| planned.contains(signature))
+    }
+
+    /// Remembers the planned agent calls
+    pub async fn remember_plan(&self, tasks: &[TaskAction]) {
+        self.planned
+            .lock()
+            .await
+            .extend(skills::task::plan_signatures(tasks));
+    }
+
+    /// Sets the agent skills pre-selected for the query
+    pub async fn set_selection(&self, selection: Option<Selection>) {
+        *self.selection.lock().await = selection;
+    }
+
+    /// Returns the agent skills pre-selected for the query
+    pub async fn selection(&self) -> Option<Selection> {
+        self.selection.lock().await.clone()
+    }
+
+    /// Starts (or continues) the turn in the query context
+    pub async fn begin_turn(&self, context: &Arc<Mutex<Messages>>) {
+        self.turn.lock().await.begin(context).await;
+    }
+
+    /// Shifts the turn messages in the context (after its history was replaced)
+    pub async fn shift_turn(&self, shift: isize) {
+        self.turn.lock().await.shift(shift);
+    }
+
+    /// Marks the last assistant answer as a part of the turn
+    pub async fn accept_answer(&self) {
+        self.turn.lock().await.accept().await;
+    }
+
+    /// Persists the turn to the session (once)
+    pub async fn save_turn(&self) -> Result<()> {
+        let Some(messages) = self.turn.lock().await.collect().await else {
+            return Ok(());
+        };
+        let session = Session::open(self.sid).await?;
+
+        let turn_idx = session.lock().await.write_turn(messages.clone()).await?;
+        info!("Saved the turn #{turn_idx} of session {}", self.sid);
+
+        // the first turn names the session:
+        if turn_idx == 0 && Settings::get().sessions.generate_titles {
+            let sid = self.sid;
+            tokio::spawn(async move {
+                if let Err(e) = title::generate(session, messages).await {
+                    warn!("Failed to generate the session {sid} title: {e}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Registers the in-flight agent tool call
+    pub async fn add_call(&self, call_id: &str, sock_path: &Path) {
+        self.calls
+            .lock()
+            .await
+            .insert(call_id.to_owned(), sock_path.to_owned());
+    }
+
+    /// Unregisters the completed agent tool call
+    pub async fn remove_call(&self, call_id: &str) {
+        self.calls.lock().await.remove(call_id);
+    }
+
+    /// Cancels the query: aborts the loop and tasks, notifies the agents and the client
+    pub async fn cancel(&self) -> bool {
+        if self.cancelled.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        info!("Cancelling the query of session {}...", self.sid);
+
+        // the client stream is kept open until it's notified (aborting drops the query senders):
+        let tx = self.tx.as_ref().and_then(WeakUnboundedSender::upgrade);
+
+        // abort the planning loop:
+        if let Some(root) = self.root.lock().await.take() {
+            root.abort();
+        }
+
+        // abort the agent tasks:
+        if let Some(tasks) = self.tasks.lock().await.upgrade() {
+            Tasks::finish_all(&tasks).await;
+        }
+
+        // propagate cancellation to the agents:
+        let calls = std::mem::take(&mut *self.calls.lock().await);
+        for (call_id, sock_path) in calls {
+            let request = Client::ipc(&sock_path.to_string_lossy())
+                .post(&str!("/tools/cancel/{call_id}"))
+                .send();
+
+            match tokio::time::timeout(Duration::from_millis(500), request).await {
+                Ok(Ok(res)) if res.status().is_success() => {
+                    info!("Tool call `{call_id}` cancelled")
+                }
+                _ => warn!("Failed to cancel the tool call `{call_id}` on {sock_path:?}"),
+            }
+        }
+
+        // notify the client:
+        if let Some(tx) = tx {
+            Sender::from(tx).send(Event::cancelled()).ok();
+        }
+
+        true
+    }
+
+    /// Watches the client stream: cancels the query if the client has dropped the receiver
+    pub async fn watch(self: Arc<Self>) {
+        if let Some(weak_tx) = self.tx.clone() {
+            loop {
+                // all the query senders are dropped, so the query is completed:
+                let Some(tx) = weak_tx.upgrade() else {
+                    break;
+                };
+
+                if tx.is_closed() {
+                    warn!("Stream receiver dropped by client, cancelling the query");
+                    self.cancel().await;
+                    break;
+                }
+
+                drop(tx);
+                tokio::time::sleep(Duration::from_millis(200)).await;
+            }
+        }
+
+        self.finish().await;
+    }
+
+    /// Unregisters the query (persisting the unfinished turn)
+    async fn finish(self: &Arc<Self>) {
+        if let Err(e) = self.save_turn().await {
+            error!("Failed to save the turn of session {}: {e}", self.sid);
+        }
+
+        let mut queries = QUERIES.lock().await;
+        if queries
+            .get(&self.sid)
+            .is_some_and(|query| Arc::ptr_eq(query, self))
+        {
+            queries.remove(&self.sid);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use super::{MANAGER, Manager};
+use crate::prelude::*;
+
+use std::collections::BTreeMap;
+
+/// The agents list placeholder of the static prompt (the request agents list goes late)
+pub const LATE_AGENTS_NOTE: &str =
+    "The agents available for the request are listed in the last system message.";
+
+/// The agent skills pre-selected for the query planning
+#[derive(Default, Debug, Clone)]
+pub struct Selection {
+    /// The selected skills names (by agent name)
+    pub skills: BTreeMap<Arc<String>, Vec<String>>,
+}
+
+impl Selection {
+    /// Selects the agent skills most relevant to the query (None = the full list is offered)
+    pub async fn select(query_vec: &[f32]) -> Option<Self> {
+        let options = Settings::get().selection.clone();
+        if !options.enable {
+            return None;
+        }
+
+        let agents = MANAGER
+            .get()
+            .await
+            .agents
+            .clone()
+            .into_iter()
+            .filter(|(_, agent)| !agent.is_quarantined())
+            .collect::<Vec<_>>();
+
+        // there is nothing to cut off:
+        let top_k = options.top_k.max(1);
+        let skills_count = agents
+            .iter()
+            .map(|(_, agent)| agent.metadata.skills.len())
+            .sum::<usize>();
+        if skills_count <= top_k {
+            return None;
+        }
+
+        let mut scored = vec![];
+        for (name, agent) in &agents {
+            let embeddings = match agent.skill_embeddings().await {
+                Ok(embeddings) => embeddings,
+                Err(e) => {
+                    warn!("Failed to embed the `{name}` agent skills: {e}");
+                    return None;
+                }
+            };
+
+            for (skill, embedding) in embeddings {
+                let score = cosine_similarity(query_vec, &embedding);
+                scored.push((score, name.clone(), skill));
+            }
+        }
+
+        // the low confidence selection can miss the required skills:
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        if scored[0].0 < options.min_similarity {
+            info!(
+                "Low agent skills relevance ({:.2}), offering the full agents list",
+                scored[0].0
+            );
+            return None;
+        }
+
+        let mut selection = Self::default();
+        for (_, name, skill) in scored
+            .into_iter()
+            .take(top_k)
+            .take_while(|(score, ..)| *score >= options.min_similarity)
+        {
+            selection.skills.entry(name).or_default().push(skill);
+        }
+
+        info!("Pre-selected agent skills: {:?}", selection.skills);
+        Some(selection)
+    }
+
+    /// Returns true if the agent is selected
+    pub fn contains(&self, agent: &str) -> bool {
+        self.skills.keys().any(|name| name.as_str() == agent)
+    }
+
+    /// Returns the selected agents names
+    pub fn agents(&self) -> Vec<String> {
+        self.skills.keys().map(|name| name.to_string()).collect()
+    }
+
+    /// Returns the agents list prompt part of the selected agents & skills (with the allowed agent names)
+    pub async fn doc(&self) -> String {
+        let guard = MANAGER.get().await;
+
+        let mut doc = String::from("Available Agents:\n");
+        for (name, selected) in &self.skills {
+            let Some(agent) = guard.agents.get(name) else {
+                continue;
+            };
+            let skills = agent
+                .metadata
+                .skills
+                .iter()
+                .filter(|skill| selected.contains(&skill.name))
+                .collect::<Vec<_>>();
+            Manager::write_agent_doc(&mut doc, agent, &skills);
+        }
+
+        let names = self
+            .skills
+            .keys()
+            .map(|name| str!("`{name}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        doc.push_str(&format!(
+            "\nThe `agent_name` of the `handle_agent` tool must be one of: {names}.\n"
+        ));
+        doc
+    }
+}
+
+/// Returns the cosine similarity of the vectors
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
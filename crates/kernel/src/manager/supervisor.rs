@@ -0,0 +1,82 @@
+use super::{Agent, MANAGER, Manager};
+use crate::prelude::*;
+
+use tokio::time;
+
+/// The agent workers supervisor
+pub struct Supervisor;
+
+impl Supervisor {
+    /// Runs the supervision loop: watches the workers exits & stops the idle ones
+    pub async fn run() {
+        let mut exits = time::interval(Duration::from_millis(500));
+        let idle_timeout = Settings::get().agents.idle_timeout;
+        let mut idle = time::interval(Duration::from_secs(idle_timeout.clamp(1, 10)));
+
+        loop {
+            tokio::select! {
+                _ = exits.tick() => Self::check_exits().await,
+                _ = idle.tick() => Manager::evict_idle().await,
+            }
+        }
+    }
+
+    /// Checks the workers for unexpected exits
+    async fn check_exits() {
+        let agents = MANAGER.get().await.agents.clone();
+
+        for (name, agent) in agents {
+            if let Some(status) = agent.take_exited().await {
+                warn!("Agent `{name}` worker exited unexpectedly ({status})");
+
+                if !Self::crashed(&name, &agent, str!("Worker exited: {status}")).await {
+                    tokio::spawn(Self::restart(name, agent));
+                }
+            }
+        }
+    }
+
+    /// Records the agent crash, returns true if the agent is quarantined
+    pub async fn crashed(name: &Arc<String>, agent: &Arc<Agent>, reason: String) -> bool {
+        if agent.record_crash(reason).await {
+            error!("Agent `{name}` crashed too often and was quarantined");
+
+            if let Err(e) = Manager::update_doc().await {
+                error!("Failed to update agents doc: {e}");
+            }
+        }
+
+        agent.is_quarantined()
+    }
+
+    /// Restarts the crashed agent worker with exponential backoff
+    async fn restart(name: Arc<String>, agent: Arc<Agent>) {
+        loop {
+            let backoff = agent.backoff().await;
+            info!("Restarting agent `{name}` in {}ms...", backoff.as_millis());
+            time::sleep(backoff).await;
+
+            // the agent was reloaded, stopped or restarted on demand meanwhile:
+            let is_actual = Manager::get(&name)
+                .await
+                .is_some_and(|actual| Arc::ptr_eq(&actual, &agent));
+
+            if !is_actual || agent.is_quarantined() || agent.is_resident().await {
+                return;
+            }
+
+            match agent.start().await {
+                Ok(_) => {
+                    info!("Agent `{name}` restarted");
+                    return;
+                }
+                Err(e) => {
+                    error!("Failed to restart agent `{name}`: {e}");
+                    if Self::crashed(&name, &agent, str!("Restart failed: {e}")).await {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
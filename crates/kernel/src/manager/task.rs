@@ -1,5 +1,9 @@
 use super::Tasks;
-use crate::{prelude::*, skills::task::TaskAction};
+use crate::{
+    prelude::*,
+    runtime::Runtime,
+    skills::{direct::DirectCall, eval::EvalAction, task::TaskAction},
+};
 
 use anylm::api::Content;
 use ovsy_share::{Event, EventTaskInfo};
@@ -14,6 +18,8 @@ pub struct Task {
     pub skills: Vec<String>,
     pub query: String,
     pub depends: HashSet<i64>,
+    pub transforms: Vec<EvalAction>,
+    pub direct: Option<DirectCall>,
 
     pub tasks: Arc<Mutex<Tasks>>,
     pub tx: Sender<Bytes>,
@@ -29,6 +35,8 @@ impl Task {
             skills: data.agent_skills,
             query: data.task_query,
             depends: data.depend_tasks,
+            transforms: data.transforms,
+            direct: data.direct,
 
             tasks,
             tx,
@@ -43,15 +51,14 @@ impl Task {
         }
     }
 
-    /// Returns true if this task is last
-    pub async fn is_last(&self) -> bool {
-        let lock = self.tasks.lock().await;
-        lock.pending.is_empty() && lock.working.len() <= 1
-        // <= 1 because current task is still listed in the working list before finish
+    /// Waits for the tasks plan acceptance, returns false if the plan was discarded
+    pub async fn wait_plan(&self) -> bool {
+        let mut plan = self.tasks.lock().await.plan.clone();
+        plan.wait_for(|accepted| *accepted).await.is_ok()
     }
 
-    /// Finishes the agent handling
-    pub async fn finish(&self, agent_messages: Vec<Content>) {
+    /// Finishes the agent handling, returns true if it was the last task
+    pub async fn finish(&self, agent_messages: Vec<Content>) -> bool {
         let mut lock = self.tasks.lock().await;
 
         lock.working.remove(&self.id);
@@ -62,42 +69,32 @@ impl Task {
             lock.results.insert(self.id, agent_messages);
         }
 
-        // check pending tasks:
-        if !lock.pending.is_empty() {
-            let mut ready_ids = vec![];
-            for (id, task) in lock.pending.iter() {
-                if lock.check(&task) {
-                    ready_ids.push(*id);
-                }
-            }
-
-            let ready_to_run: Vec<Task> = ready_ids
-                .into_iter()
-                .filter_map(|id| lock.pending.remove(&id))
-                .collect();
+        // check pending tasks (they leave the pending list in handle_task):
+        let ready_ids: Vec<i64> = lock
+            .pending
+            .iter()
+            .filter(|(_, task)| lock.check(task))
+            .map(|(id, _)| *id)
+            .collect();
 
-            drop(lock);
+        let is_last = lock.pending.is_empty() && lock.working.is_empty();
+        drop(lock);
 
-            for task in ready_to_run {
-                let tx = self.tx.clone();
-                let tasks = self.tasks.clone();
+        for id in ready_ids {
+            let tx = self.tx.clone();
+            let tasks = self.tasks.clone();
 
-                tokio::spawn(async move {
-                    crate::handlers::query::handle_task(task.id, tx, tasks).await;
-                });
-            }
+            tokio::spawn(async move {
+                crate::handlers::query::handle_task(id, tx, tasks).await;
+            });
         }
+
+        is_last
     }
 
     /// Finishes the all agent tasks
     pub async fn finish_all(&self) {
-        let mut lock = self.tasks.lock().await;
-        let working_tasks: Vec<_> = lock.working.drain().map(|(_, task)| task).collect();
-        drop(lock);
-
-        for task in working_tasks {
-            task.abort();
-        }
+        Tasks::finish_all(&self.tasks).await;
     }
 
     /// Finishes the entire task chain
@@ -124,10 +121,13 @@ impl Task {
 
             for id in dependents {
                 if let Some(task) = lock.pending.remove(&id) {
-                    let _ = self.tx.send(
-                        Event::error(str!("Cancelled: dependency task {} failed", task.id))
-                            .task_info(self.info()),
-                    );
+                    let error = str!("Cancelled: dependency task {current_id} failed");
+                    lock.messages
+                        .lock()
+                        .await
+                        .push_content(Some(&task.tool_call_id), str!("Error: {error}"));
+
+                    let _ = self.tx.send(Event::error(error).task_info(task.info()));
                     to_remove.push(id);
                 }
             }
@@ -141,6 +141,43 @@ impl Task {
         }
     }
 
+    /// Applies the JS transforms over the dependency results to the task query
+    pub async fn transform_query(&self) -> Result<String> {
+        let mut query = self.query.clone();
+        if self.transforms.is_empty() {
+            return Ok(query);
+        }
+
+        let lock = self.tasks.lock().await;
+        let mut runtime = Runtime::new();
+
+        for eval in &self.transforms {
+            for id in &eval.depend_tasks {
+                let result = lock
+                    .results
+                    .get(id)
+                    .map(|contents| {
+                        contents
+                            .iter()
+                            .filter_map(|content| match content {
+                                Content::Text { text } => Some(text.as_str()),
+                                _ => None,
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    })
+                    .unwrap_or_default();
+
+                runtime.set_var(&EvalAction::task_var(*id), &result)?;
+            }
+
+            let result = runtime.eval(&eval.code)?;
+            eval.inject(self.id, &mut query, &result);
+        }
+
+        Ok(query)
+    }
+
     /// Returns parent tasks context
     pub async fn context(&self) -> Vec<Vec<Content>> {
         let lock = self.tasks.lock().await;
@@ -1,8 +1,13 @@
-use super::Task;
+use super::{QueryHandle, Task};
 use crate::{prelude::*, session::Session};
 
 use anylm::api::{Content, Messages};
-use tokio::task::JoinHandle;
+use ovsy_share::Event;
+use std::sync::Weak;
+use tokio::{sync::watch, task::JoinHandle, time};
+
+/// The stuck tasks check interval
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(1);
 
 /// The agent tasks worflow
 pub struct Tasks {
@@ -12,11 +17,19 @@ pub struct Tasks {
     pub results: HashMap<i64, Vec<Content>>,
     pub session: Arc<Mutex<Session>>,
     pub messages: Arc<Mutex<Messages>>,
+    pub query: Arc<QueryHandle>,
+    /// The plan acceptance signal (false while the planner is streaming)
+    pub plan: watch::Receiver<bool>,
 }
 
 impl Tasks {
     /// Creates a new workflow
-    pub fn new(session: Arc<Mutex<Session>>, messages: Arc<Mutex<Messages>>) -> Arc<Mutex<Self>> {
+    pub fn new(
+        session: Arc<Mutex<Session>>,
+        messages: Arc<Mutex<Messages>>,
+        query: Arc<QueryHandle>,
+        plan: watch::Receiver<bool>,
+    ) -> Arc<Mutex<Self>> {
         arc!(Mutex::new(Self {
             pending: map! {},
             working: map! {},
@@ -24,9 +37,76 @@ impl Tasks {
             results: map! {},
             session,
             messages,
+            query,
+            plan,
         }))
     }
 
+    /// Drops the pending tasks and aborts the working ones
+    pub async fn finish_all(tasks: &Arc<Mutex<Self>>) {
+        let mut lock = tasks.lock().await;
+        lock.pending.clear();
+        let working_tasks: Vec<_> = lock.working.drain().map(|(_, task)| task).collect();
+        drop(lock);
+
+        for task in working_tasks {
+            task.abort();
+        }
+    }
+
+    /// Watches the tasks graph: drops the pending tasks (finishing the stream) if they got stuck
+    pub async fn watchdog(tasks: Weak<Mutex<Self>>) {
+        let mut interval = time::interval(WATCHDOG_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            // the graph is completed (or dropped):
+            let Some(tasks) = tasks.upgrade() else {
+                break;
+            };
+            let mut lock = tasks.lock().await;
+            if lock.query.is_cancelled() || (lock.pending.is_empty() && lock.working.is_empty()) {
+                break;
+            }
+
+            if !lock.is_stuck() {
+                continue;
+            }
+
+            let stuck = std::mem::take(&mut lock.pending);
+            drop(lock);
+
+            warn!(
+                "Agent tasks got stuck waiting for dependencies: {:?}",
+                stuck.keys()
+            );
+            for task in stuck.values() {
+                task.tx
+                    .send(
+                        Event::error(str!(
+                            "Cancelled: task {} is stuck waiting for dependencies",
+                            task.id
+                        ))
+                        .task_info(task.info()),
+                    )
+                    .ok();
+                task.tx.send(Event::finish().task_info(task.info())).ok();
+            }
+            if let Some(task) = stuck.values().next() {
+                task.tx.send(Event::finish()).ok();
+            }
+            break;
+        }
+    }
+
+    /// Returns true if the pending tasks can never start (nothing is working or ready)
+    pub fn is_stuck(&self) -> bool {
+        self.working.is_empty()
+            && !self.pending.is_empty()
+            && !self.pending.values().any(|task| self.check(task))
+    }
+
     /// Returns true if task ready to start
     pub fn check(&self, task: &Task) -> bool {
         task.depends.is_empty() || task.depends.iter().all(|id| self.finished.contains(id))
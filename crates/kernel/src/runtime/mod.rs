@@ -31,6 +31,17 @@ impl Runtime {
         js_to_string(&value, &mut self.context).map_err(|e| e.to_string().into())
     }
 
+    /// Binds the string value to the global variable.
+    pub fn set_var(&mut self, name: &str, value: &str) -> Result<()> {
+        self.context
+            .register_global_property(
+                boa_engine::JsString::from(name),
+                boa_engine::JsString::from(value),
+                boa_engine::property::Attribute::all(),
+            )
+            .map_err(|e| format!("JS Execution Error: {e}").into())
+    }
+
     /// Evaluates JavaScript and converts the result to a Rust type.
     pub fn eval_json<T>(&mut self, code: &str) -> Result<T>
     where
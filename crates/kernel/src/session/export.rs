@@ -0,0 +1,118 @@
+use crate::prelude::*;
+
+use anylm::api::{Content, Message};
+use ovsy_share::SessionExport;
+
+/// Checks the imported session data consistency
+pub fn validate(data: &SessionExport) -> Result<()> {
+    let count = data.messages.len();
+
+    if data.compressed_until > count {
+        return Err(Error::InvalidSessionExport(str!(
+            "the compression boundary {} is out of {count} messages",
+            data.compressed_until
+        ))
+        .into());
+    }
+
+    if data.turns.windows(2).any(|pair| pair[0] >= pair[1])
+        || data
+            .turns
+            .last()
+            .is_some_and(|&turn| turn as usize >= count)
+    {
+        return Err(Error::InvalidSessionExport(str!(
+            "the turns are unordered or out of {count} messages"
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Renders the session history as the human-readable Markdown transcript
+pub fn to_markdown(data: &SessionExport) -> String {
+    let mut md = String::new();
+
+    let title = data.title.clone().unwrap_or(str!("Session {}", data.id));
+    md.push_str(&str!("# {title}\n\n"));
+    md.push_str(&str!("- **Session:** `{}`\n", data.id));
+    md.push_str(&str!(
+        "- **Created:** {}\n",
+        data.id.created_at().to_rfc3339()
+    ));
+    if let Some(last_activity) = data.last_activity {
+        md.push_str(&str!(
+            "- **Last activity:** {}\n",
+            last_activity.to_rfc3339()
+        ));
+    }
+
+    for (i, msg) in data.messages.iter().enumerate() {
+        if i > 0 && i == data.compressed_until {
+            md.push_str("\n---\n\n> The messages above are compressed into the summary below.\n");
+        } else if data.turns.contains(&(i as u64)) {
+            md.push_str("\n---\n");
+        }
+        md.push_str(&render_message(msg));
+    }
+
+    md
+}
+
+/// Renders the message section
+fn render_message(msg: &Message) -> String {
+    let role = if msg.role.is_user() {
+        "User"
+    } else if msg.role.is_assistant() {
+        "Assistant"
+    } else if msg.role.is_tool() {
+        "Tool result"
+    } else {
+        "System"
+    };
+
+    let mut md = match msg.tool_call_id.as_str() {
+        "" => str!("\n## {role}\n"),
+        call_id => str!("\n## {role} (`{call_id}`)\n"),
+    };
+    if let Some(timestamp) = msg.timestamp {
+        md.push_str(&str!("\n_{}_\n", timestamp.to_rfc3339()));
+    }
+
+    for part in &msg.content {
+        match part {
+            // the tool outputs are kept verbatim:
+            Content::Text { text } if msg.role.is_tool() => md.push_str(&code_block("", text)),
+            Content::Text { text } => md.push_str(&str!("\n{}\n", text.trim_end())),
+            Content::Image { .. } => md.push_str("\n_[image]_\n"),
+        }
+    }
+
+    for call in &msg.tool_calls {
+        md.push_str(&str!(
+            "\n**Tool call** `{}` (`{}`):\n",
+            call.func.name,
+            call.id
+        ));
+
+        let args = json::from_str::<JsonValue>(&call.func.json_str)
+            .and_then(|args| json::to_string_pretty(&args))
+            .unwrap_or(call.func.json_str.clone());
+        md.push_str(&code_block("json", &args));
+    }
+
+    md
+}
+
+/// Wraps the text into the code block (the fence is longer than any backticks inside)
+fn code_block(lang: &str, text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest.max(2) + 1);
+
+    str!("\n{fence}{lang}\n{}\n{fence}\n", text.trim_end())
+}
@@ -0,0 +1,26 @@
+use crate::context::extract_text_from_msg;
+
+use anylm::api::Message;
+
+/// The maximum indexed term length in chars
+const MAX_TERM_LENGTH: usize = 40;
+
+/// Splits the text into the unique lowercase search terms
+pub fn terms(text: &str) -> Vec<String> {
+    let mut terms = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(|word| word.to_lowercase().chars().take(MAX_TERM_LENGTH).collect())
+        .collect::<Vec<String>>();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// Returns the searchable message text (the system messages aren't indexed)
+pub fn text(msg: &Message) -> Option<String> {
+    if msg.role.is_system() {
+        return None;
+    }
+    extract_text_from_msg(msg)
+}
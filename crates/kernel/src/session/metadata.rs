@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use ovsy_share::{SessionInfo, SessionSummary};
 
 /// The session metadata
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -6,6 +7,24 @@ pub struct Metadata {
     pub session_id: SessionId,
     pub message_count: u64,
     pub compressed_until: usize,
+    /// The first message index of every conversation turn
+    #[serde(default)]
+    pub turns: Vec<u64>,
+    /// The session title (set after the first turn)
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The last history change time
+    #[serde(default)]
+    pub last_activity: Option<DateTime<Utc>>,
+    /// The history tokens count (after compaction)
+    #[serde(default)]
+    pub token_count: u64,
+    /// The last database compaction time
+    #[serde(default)]
+    pub compacted_at: Option<DateTime<Utc>>,
+    /// The last client session info (used to re-open the session)
+    #[serde(default)]
+    pub info: Option<SessionInfo>,
 }
 
 impl Metadata {
@@ -15,6 +34,28 @@ impl Metadata {
             session_id,
             message_count: 0,
             compressed_until: 0,
+            turns: vec![],
+            title: None,
+            last_activity: None,
+            token_count: 0,
+            compacted_at: None,
+            info: None,
+        }
+    }
+
+    /// Returns the session listing entry
+    pub fn summary(&self) -> SessionSummary {
+        let created_at = self.session_id.created_at();
+
+        SessionSummary {
+            id: self.session_id,
+            title: self.title.clone(),
+            created_at,
+            last_activity: self.last_activity.unwrap_or(created_at),
+            message_count: self
+                .message_count
+                .saturating_sub(self.compressed_until as u64),
+            token_count: self.token_count,
         }
     }
 }
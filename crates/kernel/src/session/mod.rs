@@ -4,16 +4,63 @@ use key::Key;
 pub mod metadata;
 use metadata::Metadata;
 
-use crate::{context::UserFact, prelude::*};
+pub mod turn;
+pub use turn::Turn;
+
+pub mod title;
+
+pub mod export;
+
+pub mod index;
+
+pub mod retention;
+
+pub mod store;
+use store::Store;
+
+use crate::{
+    context::{CachedAnswer, UserFact, compact, extract_text_from_msg, history},
+    manager::QueryHandle,
+    prelude::*,
+};
 
 use anylm::api::Message;
-use cistern::{Cistern, Kv, Rag};
-use ovsy_share::{SessionId, SessionInfo};
+use cistern::{Cistern, Kv, KvTable, Rag};
+use ovsy_share::{HistoryHit, SessionExport, SessionId, SessionInfo, SessionSummary};
+use std::sync::{
+    Weak,
+    atomic::{AtomicI64, Ordering},
+};
+use tokio::{fs, sync::OwnedMutexGuard};
+
+static SESSIONS: State<HashMap<SessionId, OpenSession>> = State::default();
 
-static SESSIONS: State<HashMap<SessionId, SharedSession>> = State::default();
+/// The session database open guards (the sessions list isn't locked while a database is opened)
+static OPENING: State<HashMap<SessionId, Weak<Mutex<()>>>> = State::default();
 
 type SharedSession = Arc<Mutex<Session>>;
 
+/// The session open in memory
+#[derive(Clone)]
+struct OpenSession {
+    session: SharedSession,
+    /// The last access time (unix seconds)
+    last_access: Arc<AtomicI64>,
+}
+
+impl OpenSession {
+    /// Marks the session as just accessed
+    fn touch(&self) {
+        self.last_access
+            .store(Utc::now().timestamp(), Ordering::Relaxed);
+    }
+
+    /// Returns the time in seconds since the last session access
+    fn idle_secs(&self) -> i64 {
+        Utc::now().timestamp() - self.last_access.load(Ordering::Relaxed)
+    }
+}
+
 /// The user session manager
 #[derive(Clone)]
 pub struct Session {
@@ -30,15 +77,33 @@ pub struct Session {
 impl Session {
     /// Initializes the user session instance
     pub async fn init(id: SessionId, info: SessionInfo) -> Result<SharedSession> {
-        let user_dir = path!("$share$/userdata/{}", id.user_id);
+        // the session database is opened by one task at a time:
+        let _guard = Self::open_guard(id).await;
+        Self::connect(id, info).await
+    }
+
+    /// Opens the session databases (the caller holds the opening guard)
+    async fn connect(id: SessionId, info: SessionInfo) -> Result<SharedSession> {
+        if let Some(session) = Self::get(&id) {
+            return Ok(session);
+        }
 
         // session kv database path
-        let session_dir = user_dir.join("sessions").join(id.to_string());
-        let kv_db = arc!(Cistern::connect(session_dir).await?);
+        let session_dir = Self::dir(&id);
+        let kv_db = arc!(Cistern::<Kv>::connect(session_dir).await?);
 
-        // global user rag database path
-        let facts_dir = user_dir.join("facts");
-        let rag_db = arc!(Cistern::connect(facts_dir).await?);
+        // the client info is kept for re-opening:
+        let table = kv_db.open_table(&Self::table_name(&id)).await?;
+        let mut meta: Metadata = table
+            .read(Key::Metadata)
+            .await?
+            .unwrap_or(Metadata::new(id));
+        meta.info.replace(info.clone());
+        Self::write_metadata(&table, meta).await?;
+        table.flush().await?;
+
+        // global user rag database
+        let rag_db = arc!(Cistern::connect(Self::facts_dir(id.user_id)).await?);
 
         let this = arc_mutex!(Self {
             id,
@@ -47,20 +112,226 @@ impl Session {
             rag_db,
         });
 
-        SESSIONS.lock().await.insert(id, this.clone());
+        SESSIONS.lock().await.insert(
+            id,
+            OpenSession {
+                session: this.clone(),
+                last_access: arc!(AtomicI64::new(Utc::now().timestamp())),
+            },
+        );
         Ok(this)
     }
 
+    /// Locks the session database opening (it's released with the returned guard)
+    async fn open_guard(id: SessionId) -> OwnedMutexGuard<()> {
+        let guard = {
+            let mut guards = OPENING.lock().await;
+            guards.retain(|_, guard| guard.strong_count() > 0);
+
+            match guards.get(&id).and_then(Weak::upgrade) {
+                Some(guard) => guard,
+                None => {
+                    let guard = arc!(Mutex::new(()));
+                    guards.insert(id, Arc::downgrade(&guard));
+                    guard
+                }
+            }
+        };
+
+        guard.lock_owned().await
+    }
+
+    /// Returns the open session, re-opening the evicted (or closed) one with the last client info
+    pub async fn open(id: SessionId) -> Result<SharedSession> {
+        if let Some(session) = Self::get(&id) {
+            return Ok(session);
+        }
+        if !Self::dir(&id).exists() {
+            return Err(Error::UnknownSessionId(id).into());
+        }
+
+        let info = Self::with_table(id, async |table| {
+            let meta: Option<Metadata> = table.read(Key::Metadata).await?;
+            Ok(meta.and_then(|meta| meta.info))
+        })
+        .await?;
+
+        // the session could be deleted meanwhile, its database mustn't be re-created:
+        let _guard = Self::open_guard(id).await;
+        if !Self::dir(&id).exists() && !Self::is_open(&id) {
+            return Err(Error::UnknownSessionId(id).into());
+        }
+
+        info!("Re-opening the session {id}");
+        Self::connect(id, info.unwrap_or_default()).await
+    }
+
+    /// Checks the session is open in memory (without marking it as accessed)
+    pub fn is_open(id: &SessionId) -> bool {
+        SESSIONS.dirty_get().contains_key(id)
+    }
+
+    /// Returns the number of the sessions open in memory
+    pub fn count() -> usize {
+        SESSIONS.dirty_get().len()
+    }
+
+    /// Flushes and closes the sessions idle beyond the configured timeout
+    pub async fn evict_idle() {
+        let idle_timeout = Settings::get().sessions.idle_timeout;
+        if idle_timeout == 0 {
+            return;
+        }
+
+        let is_idle = |id: &SessionId, open: &OpenSession| {
+            open.idle_secs() >= idle_timeout as i64
+                && !QueryHandle::get(id).is_some_and(|query| query.is_running())
+                && !compact::is_compacting(id)
+        };
+
+        let idle = SESSIONS
+            .dirty_get()
+            .iter()
+            .filter(|(id, open)| is_idle(id, open))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        for id in idle {
+            // the session can't be re-opened until its database is closed:
+            let _guard = Self::open_guard(id).await;
+            let open = {
+                let mut sessions = SESSIONS.lock().await;
+                match sessions.get(&id) {
+                    Some(open) if is_idle(&id, open) => sessions.remove(&id),
+                    _ => None,
+                }
+            };
+            let Some(open) = open else {
+                continue;
+            };
+
+            let table_name = Self::table_name(&id);
+            let kv_db = open.session.lock().await.kv_db.clone();
+            match kv_db.open_table(&table_name).await {
+                Ok(table) => {
+                    if let Err(e) = table.flush().await {
+                        warn!("Failed to flush the session {id}: {e}");
+                    }
+                }
+                Err(e) => warn!("Failed to flush the session {id}: {e}"),
+            }
+            info!("Session {id} was idle for {}s, closed", open.idle_secs());
+        }
+    }
+
+    /// Evicts the idle sessions periodically
+    pub async fn supervise() {
+        loop {
+            let idle_timeout = Settings::get().sessions.idle_timeout;
+            if idle_timeout == 0 {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                continue;
+            }
+
+            tokio::time::sleep(Duration::from_secs(idle_timeout.clamp(1, 10))).await;
+            Self::evict_idle().await;
+        }
+    }
+
+    /// Returns the session database directory
+    pub fn dir(id: &SessionId) -> PathBuf {
+        path!("$share$/userdata/{}/sessions/{id}", id.user_id)
+    }
+
+    /// Returns the user RAG database directory (shared by the user sessions)
+    pub fn facts_dir(user_id: u128) -> PathBuf {
+        path!("$share$/userdata/{user_id}/facts")
+    }
+
     /// Returns the user session instance
     pub fn get(id: &SessionId) -> Option<SharedSession> {
-        SESSIONS.dirty_get().get(id).map(Clone::clone)
+        SESSIONS.dirty_get().get(id).map(|open| {
+            open.touch();
+            open.session.clone()
+        })
+    }
+
+    /// Retrieves the user session ids by the session directories
+    pub async fn search(user_id: u128) -> Result<Vec<SessionId>> {
+        let sessions_dir = path!("$share$/userdata/{user_id}/sessions");
+
+        // open sessions dir:
+        let mut entries = match fs::read_dir(&sessions_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut sessions = Vec::new();
+
+        // read all session ids:
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir()
+                && let Some(file_name_str) = entry.file_name().to_str()
+                && let Ok(session_id) = file_name_str.parse::<SessionId>()
+            {
+                sessions.push(session_id);
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    /// Removes the session with its database (the in-flight query is cancelled)
+    pub async fn delete(id: SessionId) -> Result<()> {
+        if let Some(query) = QueryHandle::get(&id) {
+            query.cancel().await;
+        }
+
+        let _guard = Self::open_guard(id).await;
+        let session_dir = Self::dir(&id);
+        if !session_dir.exists() {
+            return Err(Error::UnknownSessionId(id).into());
+        }
+
+        SESSIONS.lock().await.remove(&id);
+        fs::remove_dir_all(session_dir).await?;
+        Store::open(id.user_id).await?.remove(id).await
+    }
+
+    /// Removes the closed session with its database, returns false if it's open
+    ///
+    /// The session can't be re-opened while it's being removed.
+    pub async fn delete_if_closed(id: SessionId) -> Result<bool> {
+        let _guard = Self::open_guard(id).await;
+        if Self::is_open(&id) {
+            return Ok(false);
+        }
+
+        let session_dir = Self::dir(&id);
+        if !session_dir.exists() {
+            return Err(Error::UnknownSessionId(id).into());
+        }
+        fs::remove_dir_all(session_dir).await?;
+        Store::open(id.user_id).await?.remove(id).await?;
+        Ok(true)
     }
 
     /// Finishes the user session
     pub async fn finish(id: &SessionId) -> Result<()> {
-        if let Some(session) = SESSIONS.lock().await.remove(id) {
+        let _guard = Self::open_guard(*id).await;
+        let open = SESSIONS.lock().await.remove(id);
+        if let Some(open) = open {
             let table_name = Self::table_name(id);
-            let table = session.lock().await.kv_db.open_table(&table_name).await?;
+            let table = open
+                .session
+                .lock()
+                .await
+                .kv_db
+                .open_table(&table_name)
+                .await?;
             table.flush().await?;
         }
         Ok(())
@@ -74,6 +345,196 @@ impl Session {
         table.read(Key::Metadata).await
     }
 
+    /// Reads the session table (the closed session database is opened for a moment)
+    async fn with_table<T>(
+        id: SessionId,
+        read: impl AsyncFnOnce(&KvTable) -> Result<T>,
+    ) -> Result<T> {
+        let _guard = Self::open_guard(id).await;
+        if let Some(session) = SESSIONS
+            .dirty_get()
+            .get(&id)
+            .map(|open| open.session.clone())
+        {
+            let kv_db = session.lock().await.kv_db.clone();
+            let table = kv_db.open_table(&Self::table_name(&id)).await?;
+            return read(&table).await;
+        }
+
+        // the missing database mustn't be created:
+        if !Self::dir(&id).exists() {
+            return Err(Error::UnknownSessionId(id).into());
+        }
+        let kv_db = Cistern::<Kv>::connect(Self::dir(&id)).await?;
+        let table = kv_db.open_table(&Self::table_name(&id)).await?;
+        read(&table).await
+    }
+
+    /// Exports all the session messages with the compression boundary
+    pub async fn export(id: SessionId) -> Result<SessionExport> {
+        Self::with_table(id, async |table| Self::read_archive(table, id).await).await
+    }
+
+    /// Imports the exported session history as a new user session
+    pub async fn import(user_id: u128, data: SessionExport) -> Result<SessionSummary> {
+        export::validate(&data)?;
+
+        let id = SessionId::new(user_id);
+        let _guard = Self::open_guard(id).await;
+        let kv_db = Cistern::<Kv>::connect(Self::dir(&id)).await?;
+        let table = kv_db.open_table(&Self::table_name(&id)).await?;
+
+        let messages = data
+            .messages
+            .iter()
+            .cloned()
+            .enumerate()
+            .collect::<Vec<_>>();
+        let meta = Self::write_archive(&table, id, data).await?;
+        let summary = meta.summary();
+
+        // the summary is written after the messages are indexed:
+        let store = Store::open(user_id).await?;
+        for (idx, msg) in &messages {
+            store.index(id, *idx, msg).await?;
+        }
+        Self::write_metadata(&table, meta).await?;
+        table.flush().await?;
+        store.flush().await?;
+
+        history::embed(id, messages);
+        Ok(summary)
+    }
+
+    /// Reads all the session messages with the metadata
+    async fn read_archive(table: &KvTable, id: SessionId) -> Result<SessionExport> {
+        let meta: Metadata = table
+            .read(Key::Metadata)
+            .await?
+            .unwrap_or(Metadata::new(id));
+
+        let mut messages = Vec::with_capacity(meta.message_count as usize);
+        for i in 0..meta.message_count as usize {
+            if let Some(msg) = table.read::<_, Message>(Key::Message(i)).await? {
+                messages.push(msg);
+            }
+        }
+
+        Ok(SessionExport {
+            id,
+            title: meta.title,
+            last_activity: meta.last_activity,
+            compressed_until: meta.compressed_until,
+            turns: meta.turns,
+            messages,
+        })
+    }
+
+    /// Writes the session messages, returns the metadata to be written
+    async fn write_archive(
+        table: &KvTable,
+        id: SessionId,
+        data: SessionExport,
+    ) -> Result<Metadata> {
+        let mut meta = Metadata::new(id);
+        for (i, mut message) in data.messages.into_iter().enumerate() {
+            if message.tokens_count == 0 {
+                message.count_tokens();
+            }
+            // the compressed originals are out of the history tokens:
+            if i >= data.compressed_until {
+                meta.token_count += message.tokens_count as u64;
+            }
+            table.write(Key::Message(i), message).await?;
+            meta.message_count += 1;
+        }
+
+        meta.compressed_until = data.compressed_until;
+        meta.turns = data.turns;
+        meta.title = data.title;
+        meta.last_activity = data.last_activity;
+
+        Ok(meta)
+    }
+
+    /// Copies the history before the message index (after compaction) into a new session
+    pub async fn fork(id: SessionId, at: Option<usize>) -> Result<SessionSummary> {
+        let mut data = Self::export(id).await?;
+
+        let count = data.messages.len() - data.compressed_until;
+        let at = at.unwrap_or(count);
+        if at > count {
+            return Err(Error::MessageIndexOutOfRange { index: at, count }.into());
+        }
+
+        // the turn isn't split (the sessions written before the turns tracking aren't checked):
+        let end = data.compressed_until + at;
+        let is_boundary = at == 0 || at == count || data.turns.contains(&(end as u64));
+        if !is_boundary && !data.turns.is_empty() {
+            return Err(Error::MessageIndexInsideTurn { index: at }.into());
+        }
+
+        data.messages.truncate(end);
+        data.turns.retain(|&turn| (turn as usize) < end);
+        data.last_activity.replace(Utc::now());
+
+        Self::import(id.user_id, data).await
+    }
+
+    /// Reads the found session messages as the search hits (scored by the given scores)
+    pub async fn read_hits(id: SessionId, found: Vec<(usize, f32)>) -> Result<Vec<HistoryHit>> {
+        Self::with_table(id, async |table| {
+            let meta: Metadata = table
+                .read(Key::Metadata)
+                .await?
+                .unwrap_or(Metadata::new(id));
+
+            let mut hits = Vec::with_capacity(found.len());
+            for (idx, score) in found {
+                // the index may point beyond the removed messages:
+                if idx >= meta.message_count as usize {
+                    continue;
+                }
+                let Some(msg) = table.read::<_, Message>(Key::Message(idx)).await? else {
+                    continue;
+                };
+                let Some(text) = index::text(&msg) else {
+                    continue;
+                };
+
+                hits.push(HistoryHit {
+                    session_id: id,
+                    title: meta.title.clone(),
+                    index: idx,
+                    role: str!(json::to_value(&msg.role)?.as_str().unwrap_or_default()),
+                    text,
+                    timestamp: msg.timestamp,
+                    compressed: idx < meta.compressed_until,
+                    score,
+                });
+            }
+
+            Ok(hits)
+        })
+        .await
+    }
+
+    /// Sets the session title
+    pub async fn set_title(&self, title: String) -> Result<()> {
+        let table_name = Self::table_name(&self.id);
+        let table = self.kv_db.open_table(&table_name).await?;
+
+        let mut meta: Metadata = table
+            .read(Key::Metadata)
+            .await?
+            .unwrap_or(Metadata::new(self.id));
+        meta.title.replace(title);
+
+        Self::write_metadata(&table, meta).await?;
+        table.flush().await?;
+        Ok(())
+    }
+
     /// Reads all the session messages
     pub async fn read_messages(&self) -> Result<Vec<Message>> {
         let table_name = Self::table_name(&self.id);
@@ -83,7 +544,7 @@ impl Session {
             Some(meta) => meta,
             None => {
                 let new_meta = Metadata::new(self.id);
-                table.write(Key::Metadata, new_meta.clone()).await?;
+                Self::write_metadata(&table, new_meta.clone()).await?;
                 table.flush().await?;
                 new_meta
             }
@@ -113,18 +574,25 @@ impl Session {
             .await?
             .unwrap_or(Metadata::new(self.id));
 
-        let msg_key = Key::Message(meta.message_count as usize);
-        table.write(msg_key, message).await?;
+        let idx = meta.message_count as usize;
+        meta.token_count += message.tokens_count as u64;
+        Store::open(self.id.user_id)
+            .await?
+            .index(self.id, idx, &message)
+            .await?;
+        table.write(Key::Message(idx), message.clone()).await?;
 
         meta.message_count += 1;
-        table.write(Key::Metadata, meta).await?;
+        meta.last_activity.replace(Utc::now());
+        Self::write_metadata(&table, meta).await?;
         table.flush().await?;
 
+        history::embed(self.id, vec![(idx, message)]);
         Ok(())
     }
 
-    /// Writes new messages to the session
-    pub async fn write_messages(&self, messages: Vec<Message>) -> Result<()> {
+    /// Writes the conversation turn messages at once, returns the turn index
+    pub async fn write_turn(&self, messages: Vec<Message>) -> Result<usize> {
         let table_name = Self::table_name(&self.id);
         let table = self.kv_db.open_table(&table_name).await?;
 
@@ -132,20 +600,84 @@ impl Session {
             .read(Key::Metadata)
             .await?
             .unwrap_or(Metadata::new(self.id));
+        meta.turns.push(meta.message_count);
+
+        // the first user message is the default title:
+        if meta.title.is_none()
+            && let Some(text) = messages
+                .iter()
+                .find(|msg| msg.role.is_user())
+                .and_then(extract_text_from_msg)
+        {
+            meta.title.replace(title::truncate(&text));
+        }
 
+        let store = Store::open(self.id.user_id).await?;
+        let mut written = Vec::with_capacity(messages.len());
         for message in messages {
-            let msg_key = Key::Message(meta.message_count as usize);
-            table.write(msg_key, message).await?;
+            let idx = meta.message_count as usize;
+            meta.token_count += message.tokens_count as u64;
+            store.index(self.id, idx, &message).await?;
+            table.write(Key::Message(idx), message.clone()).await?;
             meta.message_count += 1;
+            written.push((idx, message));
         }
+        meta.last_activity.replace(Utc::now());
 
-        table.write(Key::Metadata, meta).await?;
+        // the turn becomes visible with the metadata update only:
+        let turn_idx = meta.turns.len() - 1;
+        Self::write_metadata(&table, meta).await?;
         table.flush().await?;
 
-        Ok(())
+        history::embed(self.id, written);
+        Ok(turn_idx)
+    }
+
+    /// Removes the last conversation turn from the history, returns its messages
+    ///
+    /// The compressed history summary isn't removed (it isn't a regular turn).
+    pub async fn pop_turn(&self) -> Result<Option<Vec<Message>>> {
+        let table_name = Self::table_name(&self.id);
+        let table = self.kv_db.open_table(&table_name).await?;
+
+        let Some(mut meta) = table.read::<_, Metadata>(Key::Metadata).await? else {
+            return Ok(None);
+        };
+        let Some(&start) = meta.turns.last() else {
+            return Ok(None);
+        };
+
+        let mut messages = vec![];
+        for i in start as usize..meta.message_count as usize {
+            if let Some(msg) = table.read::<_, Message>(Key::Message(i)).await? {
+                messages.push(msg);
+            }
+        }
+        if !messages.first().is_some_and(|msg| msg.role.is_user()) {
+            return Ok(None);
+        }
+
+        let store = Store::open(self.id.user_id).await?;
+        for (i, msg) in (start as usize..).zip(&messages) {
+            store.unindex(self.id, i, msg).await?;
+            table.remove(Key::Message(i)).await?;
+        }
+        let tokens = messages.iter().map(|msg| msg.tokens_count as u64).sum();
+
+        meta.turns.pop();
+        meta.message_count = start;
+        meta.token_count = meta.token_count.saturating_sub(tokens);
+        meta.last_activity.replace(Utc::now());
+
+        Self::write_metadata(&table, meta).await?;
+        table.flush().await?;
+
+        Ok(Some(messages))
     }
 
     /// Inserts a message after the compressed originals and shifts the preserve messages
+    ///
+    /// The messages written while compressing are moved after the preserved ones.
     pub async fn insert_and_shift(
         &self,
         compressed_msg: Message,
@@ -160,29 +692,62 @@ impl Session {
             .await?
             .unwrap_or(Metadata::new(self.id));
 
+        let store = Store::open(self.id.user_id).await?;
         let insert_idx = current_meta.compressed_until + compress_count;
+        let mut appended = vec![];
+        for i in insert_idx..current_meta.message_count as usize {
+            if let Some(msg) = table.read::<_, Message>(Key::Message(i)).await? {
+                store.unindex(self.id, i, &msg).await?;
+                appended.push(msg);
+            }
+        }
+
+        let shift = 1 + preserve_msgs.len() as u64;
+        let preserved = preserve_msgs.len();
         let mut current_idx = insert_idx;
+        let mut token_count = 0;
+        let mut moved = vec![];
 
-        table
-            .write(Key::Message(current_idx), compressed_msg)
-            .await?;
-        current_idx += 1;
+        for (i, msg) in std::iter::once(compressed_msg)
+            .chain(preserve_msgs)
+            .chain(appended)
+            .enumerate()
+        {
+            token_count += msg.tokens_count as u64;
+            store.index(self.id, current_idx, &msg).await?;
+            table.write(Key::Message(current_idx), msg.clone()).await?;
 
-        for msg in preserve_msgs {
-            table.write(Key::Message(current_idx), msg).await?;
+            // the preserved messages are the copies of the already embedded originals:
+            if i == 0 || i > preserved {
+                moved.push((current_idx, msg));
+            }
             current_idx += 1;
         }
 
-        let new_message_count = std::cmp::max(current_meta.message_count, current_idx as u64);
+        // the compressed history starts a new turn (the later turns are shifted):
+        let (mut turns, later): (Vec<_>, Vec<_>) = current_meta
+            .turns
+            .into_iter()
+            .partition(|turn| *turn < insert_idx as u64);
+        turns.push(insert_idx as u64);
+        turns.extend(later.into_iter().map(|turn| turn + shift));
+
         let new_meta = Metadata {
             session_id: self.id,
-            message_count: new_message_count,
+            message_count: current_idx as u64,
             compressed_until: insert_idx,
+            turns,
+            title: current_meta.title,
+            last_activity: Some(Utc::now()),
+            token_count,
+            compacted_at: current_meta.compacted_at,
+            info: current_meta.info,
         };
 
-        table.write(Key::Metadata, new_meta).await?;
+        Self::write_metadata(&table, new_meta).await?;
         table.flush().await?;
 
+        history::embed(self.id, moved);
         Ok(())
     }
 
@@ -191,22 +756,35 @@ impl Session {
         let table_name = Self::table_name(&self.id);
         let table = self.kv_db.open_table(&table_name).await?;
 
-        if let Some(meta) = table.read::<_, Metadata>(Key::Metadata).await? {
-            let start_idx = meta.compressed_until;
-            let end_idx = meta.message_count as usize;
+        let mut fresh_meta = Metadata::new(self.id);
+        fresh_meta.last_activity.replace(Utc::now());
 
-            for i in start_idx..end_idx {
+        // the compressed originals are removed too:
+        if let Some(meta) = table.read::<_, Metadata>(Key::Metadata).await? {
+            let store = Store::open(self.id.user_id).await?;
+            for i in 0..meta.message_count as usize {
+                if let Some(msg) = table.read::<_, Message>(Key::Message(i)).await? {
+                    store.unindex(self.id, i, &msg).await?;
+                }
                 table.remove(Key::Message(i)).await?;
             }
+            fresh_meta.info = meta.info;
         }
-
-        let fresh_meta = Metadata::new(self.id);
-        table.write(Key::Metadata, fresh_meta).await?;
+        Self::write_metadata(&table, fresh_meta).await?;
         table.flush().await?;
 
         Ok(())
     }
 
+    /// Writes the session metadata with its listing entry
+    async fn write_metadata(table: &KvTable, meta: Metadata) -> Result<()> {
+        Store::open(meta.session_id.user_id)
+            .await?
+            .write_summary(&meta)
+            .await?;
+        table.write(Key::Metadata, meta).await
+    }
+
     fn table_name(session_id: &SessionId) -> String {
         str!("{session_id}")
     }
@@ -279,3 +857,60 @@ impl Session {
         Ok(())
     }
 }
+
+// Global user answers cache methods
+impl Session {
+    fn cache_table_name() -> String {
+        "cache".into()
+    }
+
+    /// Searches for the most similar non-expired cached answer
+    pub async fn search_cache(
+        &self,
+        query_embedding: Vec<f32>,
+        coefficient: f32,
+        ttl: u64,
+    ) -> Result<Option<CachedAnswer>> {
+        let table_name = Self::cache_table_name();
+        let table = self.rag_db.open_table(&table_name).await?;
+
+        let records = table
+            .read::<CachedAnswer>(query_embedding, 5, coefficient)
+            .await?
+            .unwrap_or_default();
+
+        let mut found = None;
+        for record in records {
+            // remove outdated answers:
+            if record.data.is_expired(ttl) {
+                let _ = table.remove(record.id).await;
+                continue;
+            }
+
+            if found.is_none() {
+                found = Some(record.data);
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Saves the answer to cache while removing previous similar entries
+    pub async fn save_cache(&self, embedding: Vec<f32>, record: CachedAnswer) -> Result<()> {
+        let table_name = Self::cache_table_name();
+        let table = self.rag_db.open_table(&table_name).await?;
+        let coefficient = Settings::get().cache.coefficient;
+
+        if let Ok(Some(similar)) = table
+            .read::<CachedAnswer>(embedding.clone(), 5, coefficient)
+            .await
+        {
+            for old in similar {
+                let _ = table.remove(old.id).await;
+            }
+        }
+
+        table.write(embedding, record).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+use super::{Key, Metadata, Session, Store};
+use crate::prelude::*;
+
+use chrono::TimeDelta;
+use cistern::{Cistern, Kv};
+use ovsy_share::{PruneReason, PrunedSession};
+use std::cmp::Reverse;
+use tokio::fs;
+
+/// Runs the sessions garbage collection periodically
+pub async fn run() {
+    loop {
+        let interval = Settings::get().retention.gc_interval;
+        if interval == 0 {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            continue;
+        }
+
+        collect().await;
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+/// Enforces the retention policy and compacts the changed session databases
+#[log(skip_all)]
+pub async fn collect() {
+    match prune(false).await {
+        Ok(pruned) if !pruned.is_empty() => info!("Pruned {} sessions", pruned.len()),
+        Ok(_) => {}
+        Err(e) => error!("Failed to prune the sessions: {e}"),
+    }
+
+    let mut compacted = 0;
+    for id in search_all().await {
+        match compact(id).await {
+            Ok(true) => compacted += 1,
+            Ok(false) => {}
+            Err(e) => warn!("Failed to compact the session {id} database: {e}"),
+        }
+    }
+    if compacted > 0 {
+        info!("Compacted {compacted} session databases");
+    }
+}
+
+/// Removes the sessions out of the retention policy (only lists them, if dry run)
+pub async fn prune(dry_run: bool) -> Result<Vec<PrunedSession>> {
+    let mut pruned = vec![];
+    for user_id in search_users().await? {
+        pruned.extend(select(user_id).await?);
+    }
+
+    if !dry_run {
+        let mut deleted = Vec::with_capacity(pruned.len());
+        for session in pruned {
+            match Session::delete_if_closed(session.id).await {
+                Ok(true) => deleted.push(session),
+                Ok(false) => info!("Session {} was re-opened, kept", session.id),
+                Err(e) => warn!("Failed to delete the session {}: {e}", session.id),
+            }
+        }
+        pruned = deleted;
+    }
+
+    Ok(pruned)
+}
+
+/// Selects the user sessions out of the retention policy (the active sessions are kept)
+async fn select(user_id: u128) -> Result<Vec<PrunedSession>> {
+    let cfg = &Settings::get().retention;
+
+    let mut sessions = vec![];
+    for summary in Store::open(user_id).await?.summaries(user_id).await? {
+        let disk_usage = dir_size(&Session::dir(&summary.id)).await?;
+        sessions.push((summary, disk_usage));
+    }
+    // the recently active sessions are kept first:
+    sessions.sort_by_key(|(summary, _)| Reverse(summary.last_activity));
+
+    let now = Utc::now();
+    let max_age = TimeDelta::days(cfg.max_age as i64);
+    let max_disk_usage = cfg.max_disk_usage * 1024 * 1024;
+
+    let (mut kept, mut usage) = (0, 0);
+    let mut pruned = vec![];
+
+    for (summary, disk_usage) in sessions {
+        let reason = if Session::is_open(&summary.id) {
+            None
+        } else if cfg.max_age > 0 && now - summary.last_activity > max_age {
+            Some(PruneReason::Age)
+        } else if cfg.max_sessions > 0 && kept >= cfg.max_sessions {
+            Some(PruneReason::Count)
+        } else if max_disk_usage > 0 && usage + disk_usage > max_disk_usage {
+            Some(PruneReason::DiskUsage)
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => pruned.push(PrunedSession {
+                id: summary.id,
+                title: summary.title,
+                last_activity: summary.last_activity,
+                disk_usage,
+                reason,
+            }),
+            None => {
+                kept += 1;
+                usage += disk_usage;
+            }
+        }
+    }
+
+    Ok(pruned)
+}
+
+/// Rewrites the closed session database changed since the last compaction (sled files never shrink)
+///
+/// Returns false if the session is active or unchanged.
+async fn compact(id: SessionId) -> Result<bool> {
+    // the session can't be opened while compacting:
+    let _guard = Session::open_guard(id).await;
+    if Session::is_open(&id) {
+        return Ok(false);
+    }
+
+    let session_dir = Session::dir(&id);
+    let table_name = Session::table_name(&id);
+
+    let (data, info) = {
+        let kv_db = Cistern::<Kv>::connect(&session_dir).await?;
+        let table = kv_db.open_table(&table_name).await?;
+
+        let Some(meta) = table.read::<_, Metadata>(Key::Metadata).await? else {
+            return Ok(false);
+        };
+        if meta
+            .compacted_at
+            .is_some_and(|at| meta.last_activity.is_none_or(|last| at >= last))
+        {
+            return Ok(false);
+        }
+
+        (Session::read_archive(&table, id).await?, meta.info)
+    };
+
+    // writing the live data to a fresh database:
+    let fresh_dir = session_dir.with_extension("compact");
+    fs::remove_dir_all(&fresh_dir).await.ok();
+    {
+        let kv_db = Cistern::<Kv>::connect(&fresh_dir).await?;
+        let table = kv_db.open_table(&table_name).await?;
+
+        let mut meta = Session::write_archive(&table, id, data).await?;
+        meta.compacted_at.replace(Utc::now());
+        meta.info = info;
+        table.write(Key::Metadata, meta).await?;
+        table.flush().await?;
+    }
+
+    // the old database is removed only after the fresh one is in place:
+    let old_dir = session_dir.with_extension("old");
+    fs::rename(&session_dir, &old_dir).await?;
+    if let Err(e) = fs::rename(&fresh_dir, &session_dir).await {
+        fs::rename(&old_dir, &session_dir).await?;
+        return Err(e.into());
+    }
+    fs::remove_dir_all(&old_dir).await?;
+
+    Ok(true)
+}
+
+/// Recovers the session databases left by the interrupted compactions
+pub async fn recover() -> Result<()> {
+    for user_id in search_users().await? {
+        let sessions_dir = path!("$share$/userdata/{user_id}/sessions");
+        let mut entries = match fs::read_dir(&sessions_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let session_dir = path.with_extension("");
+
+            match path.extension().and_then(|ext| ext.to_str()) {
+                // the swap was interrupted before the fresh database was moved in:
+                Some("old") if !session_dir.exists() => {
+                    warn!("Restoring the session database {}", session_dir.display());
+                    fs::rename(&path, &session_dir).await?;
+                }
+                Some("old") => fs::remove_dir_all(&path).await?,
+                // the fresh database was written completely (the old one was removed):
+                Some("compact")
+                    if !session_dir.exists() && !path.with_extension("old").exists() =>
+                {
+                    warn!(
+                        "Restoring the compacted session database {}",
+                        session_dir.display()
+                    );
+                    fs::rename(&path, &session_dir).await?;
+                }
+                Some("compact") => fs::remove_dir_all(&path).await?,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Retrieves the ids of the users having the sessions
+async fn search_users() -> Result<Vec<u128>> {
+    let mut entries = match fs::read_dir(path!("$share$/userdata")).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut users = vec![];
+    while let Some(entry) = entries.next_entry().await? {
+        if let Some(Ok(user_id)) = entry.file_name().to_str().map(str::parse) {
+            users.push(user_id);
+        }
+    }
+
+    Ok(users)
+}
+
+/// Retrieves all the session ids
+async fn search_all() -> Vec<SessionId> {
+    let mut sessions = vec![];
+    for user_id in search_users().await.unwrap_or_default() {
+        sessions.extend(Session::search(user_id).await.unwrap_or_default());
+    }
+    sessions
+}
+
+/// Returns the directory files size in bytes
+async fn dir_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    let mut dirs = vec![dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let meta = entry.metadata().await?;
+            if meta.is_dir() {
+                dirs.push(entry.path());
+            } else {
+                size += meta.len();
+            }
+        }
+    }
+
+    Ok(size)
+}
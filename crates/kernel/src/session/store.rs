@@ -0,0 +1,210 @@
+use super::{Key, Metadata, Session, index};
+use crate::prelude::*;
+
+use anylm::api::Message;
+use cistern::{Cistern, Kv, KvTable};
+use ovsy_share::SessionSummary;
+
+/// The open user stores (by user id)
+static STORES: State<HashMap<u128, Arc<Store>>> = State::default();
+
+/// The user store table key
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKey {
+    /// The session listing entry (written after the session messages are indexed)
+    Summary(SessionId),
+    /// The search index term (the indexes of the messages containing it, by session)
+    Term(String),
+}
+
+/// The search index term postings
+type Postings = HashMap<SessionId, Vec<usize>>;
+
+/// The per-user store of the sessions summaries & the history search index
+///
+/// The listing and the history search read it instead of opening every session database.
+pub struct Store {
+    _db: Cistern<Kv>,
+    table: KvTable,
+    /// The index updates are read-modify-write, so they're sequential
+    lock: Mutex<()>,
+}
+
+impl Store {
+    /// Opens the user store (it stays open)
+    pub async fn open(user_id: u128) -> Result<Arc<Self>> {
+        if let Some(store) = STORES.dirty_get().get(&user_id) {
+            return Ok(store.clone());
+        }
+
+        let mut stores = STORES.lock().await;
+        if let Some(store) = stores.get(&user_id) {
+            return Ok(store.clone());
+        }
+
+        let db = Cistern::<Kv>::connect(Self::dir(user_id)).await?;
+        let table = db.open_table("sessions").await?;
+        let store = arc!(Self {
+            _db: db,
+            table,
+            lock: Mutex::new(()),
+        });
+
+        stores.insert(user_id, store.clone());
+        Ok(store)
+    }
+
+    /// Returns the user store directory
+    pub fn dir(user_id: u128) -> PathBuf {
+        path!("$share$/userdata/{user_id}/store")
+    }
+
+    /// Returns the user sessions summaries (the missing ones are rebuilt from the session databases)
+    pub async fn summaries(&self, user_id: u128) -> Result<Vec<SessionSummary>> {
+        let mut summaries = vec![];
+        for id in Session::search(user_id).await? {
+            match self.table.read(StoreKey::Summary(id)).await? {
+                Some(summary) => summaries.push(summary),
+                None => match self.rebuild(id).await {
+                    Ok(summary) => summaries.push(summary),
+                    Err(e) => warn!("Failed to read the session {id} summary: {e}"),
+                },
+            }
+        }
+
+        Ok(summaries)
+    }
+
+    /// Writes the session listing entry
+    pub async fn write_summary(&self, meta: &Metadata) -> Result<()> {
+        self.table
+            .write(StoreKey::Summary(meta.session_id), meta.summary())
+            .await
+    }
+
+    /// Removes the session listing entry (the index postings are removed by the search)
+    pub async fn remove(&self, id: SessionId) -> Result<()> {
+        self.table.remove(StoreKey::Summary(id)).await?;
+        self.table.flush().await
+    }
+
+    /// Flushes the store to the disk
+    pub async fn flush(&self) -> Result<()> {
+        self.table.flush().await
+    }
+
+    /// Adds the message terms to the search index
+    pub async fn index(&self, id: SessionId, idx: usize, msg: &Message) -> Result<()> {
+        let Some(text) = index::text(msg) else {
+            return Ok(());
+        };
+
+        let _lock = self.lock.lock().await;
+        for term in index::terms(&text) {
+            let key = StoreKey::Term(term);
+            let mut postings: Postings = self.table.read(key.clone()).await?.unwrap_or_default();
+            let indexes = postings.entry(id).or_default();
+            if let Err(pos) = indexes.binary_search(&idx) {
+                indexes.insert(pos, idx);
+                self.table.write(key, postings).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the message terms from the search index
+    pub async fn unindex(&self, id: SessionId, idx: usize, msg: &Message) -> Result<()> {
+        let Some(text) = index::text(msg) else {
+            return Ok(());
+        };
+
+        let _lock = self.lock.lock().await;
+        for term in index::terms(&text) {
+            let key = StoreKey::Term(term);
+            let mut postings: Postings = self.table.read(key.clone()).await?.unwrap_or_default();
+            let Some(indexes) = postings.get_mut(&id) else {
+                continue;
+            };
+            if let Ok(pos) = indexes.binary_search(&idx) {
+                indexes.remove(pos);
+                if indexes.is_empty() {
+                    postings.remove(&id);
+                }
+                self.write_postings(key, postings).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the user messages containing the terms, returns their indexes with the matched terms share
+    pub async fn find(&self, terms: &[String]) -> Result<HashMap<SessionId, Vec<(usize, f32)>>> {
+        let _lock = self.lock.lock().await;
+
+        let mut matched = HashMap::<(SessionId, usize), usize>::new();
+        for term in terms {
+            let key = StoreKey::Term(term.clone());
+            let mut postings: Postings = self.table.read(key.clone()).await?.unwrap_or_default();
+
+            // the postings of the removed sessions are stale:
+            let count = postings.len();
+            postings.retain(|id, _| Session::dir(id).exists());
+            if postings.len() < count {
+                self.write_postings(key, postings.clone()).await?;
+            }
+
+            for (id, indexes) in postings {
+                for idx in indexes {
+                    *matched.entry((id, idx)).or_default() += 1;
+                }
+            }
+        }
+
+        let mut found = HashMap::<SessionId, Vec<(usize, f32)>>::new();
+        for ((id, idx), count) in matched {
+            found
+                .entry(id)
+                .or_default()
+                .push((idx, count as f32 / terms.len() as f32));
+        }
+        Ok(found)
+    }
+
+    /// Writes the term postings (the empty ones are removed)
+    async fn write_postings(&self, key: StoreKey, postings: Postings) -> Result<()> {
+        match postings.is_empty() {
+            true => self.table.remove(key).await,
+            false => self.table.write(key, postings).await,
+        }
+    }
+
+    /// Indexes the session written before the store, returns its listing entry
+    async fn rebuild(&self, id: SessionId) -> Result<SessionSummary> {
+        let (meta, messages) = Session::with_table(id, async |table| {
+            let meta: Metadata = table
+                .read(Key::Metadata)
+                .await?
+                .unwrap_or(Metadata::new(id));
+
+            let mut messages = Vec::with_capacity(meta.message_count as usize);
+            for i in 0..meta.message_count as usize {
+                if let Some(msg) = table.read::<_, Message>(Key::Message(i)).await? {
+                    messages.push((i, msg));
+                }
+            }
+            Ok((meta, messages))
+        })
+        .await?;
+
+        info!("Indexing the session {id} history");
+        for (idx, msg) in &messages {
+            self.index(id, *idx, msg).await?;
+        }
+        self.write_summary(&meta).await?;
+        self.flush().await?;
+
+        Ok(meta.summary())
+    }
+}
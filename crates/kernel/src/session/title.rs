@@ -0,0 +1,65 @@
+use super::Session;
+use crate::{context::extract_text_from_msg, prelude::*};
+
+use anylm::{
+    api::{Message, Messages},
+    completions::{Chunk, Completions},
+};
+
+/// Returns the first text line cut to the title length
+pub fn truncate(text: &str) -> String {
+    let max_len = Settings::get().sessions.title_length.max(1);
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+
+    if line.chars().count() <= max_len {
+        return line.to_owned();
+    }
+    let cut = line.chars().take(max_len).collect::<String>();
+    str!("{}…", cut.trim_end())
+}
+
+/// Generates the session title by the first turn with the model
+pub async fn generate(session: Arc<Mutex<Session>>, turn: Vec<Message>) -> Result<()> {
+    let settings = Settings::get();
+    let mut options = settings
+        .compression
+        .options
+        .clone()
+        .unwrap_or(settings.completions.options.clone());
+    options.max_tokens.replace(32);
+
+    // the dialogue texts only (without the tool calls):
+    let mut messages = Messages::new();
+    for msg in &turn {
+        let Some(text) = extract_text_from_msg(msg) else {
+            continue;
+        };
+        if msg.role.is_user() {
+            messages.add_user(vec![text.into()]);
+        } else if msg.role.is_assistant() {
+            messages.add_assistant(vec![text.into()], vec![]);
+        }
+    }
+    let messages = messages
+        .user(vec![settings.sessions.title_prompt.as_str().into()])
+        .wrap();
+
+    let mut response = Completions::try_from(options)?.send(messages).await?;
+    let mut text = String::new();
+    while let Some(chunk) = response.next().await {
+        if let Chunk::Text(part) = chunk? {
+            text.push_str(&part);
+        }
+    }
+
+    // the truncated first message is kept otherwise:
+    let title = text.trim().trim_matches('"').trim();
+    if title.is_empty() {
+        return Ok(());
+    }
+    session.lock().await.set_title(truncate(title)).await
+}
@@ -0,0 +1,75 @@
+use crate::prelude::*;
+
+use anylm::api::{Message, Messages};
+
+/// The conversation turn being handled (persisted as a whole when the query is completed)
+#[derive(Default)]
+pub struct Turn {
+    /// The latest query context (the control iterations extend the previous one)
+    context: Option<Arc<Mutex<Messages>>>,
+    /// The turn messages indices in the context (the user message & the accepted answers)
+    indices: Vec<usize>,
+}
+
+impl Turn {
+    /// Starts (or continues with the control iteration) the turn in the query context
+    pub async fn begin(&mut self, context: &Arc<Mutex<Messages>>) {
+        // the first context ends with the user message:
+        if self.context.is_none() {
+            let len = context.lock().await.messages.len();
+            self.indices.push(len.saturating_sub(1));
+        }
+
+        self.context = Some(context.clone());
+    }
+
+    /// Shifts the turn messages indices (the context history before the turn was replaced)
+    pub fn shift(&mut self, shift: isize) {
+        for idx in &mut self.indices {
+            *idx = idx.saturating_add_signed(shift);
+        }
+    }
+
+    /// Marks the last assistant answer in the context as a part of the turn
+    pub async fn accept(&mut self) {
+        let Some(context) = &self.context else {
+            return;
+        };
+
+        let lock = context.lock().await;
+        if let Some(idx) = lock
+            .messages
+            .iter()
+            .rposition(|msg| msg.role.is_assistant())
+            && !self.indices.contains(&idx)
+        {
+            self.indices.push(idx);
+        }
+    }
+
+    /// Collects the turn messages (the tool results follow their calls), once
+    pub async fn collect(&mut self) -> Option<Vec<Message>> {
+        let context = self.context.take()?;
+        let indices = std::mem::take(&mut self.indices);
+        let lock = context.lock().await;
+
+        let mut messages = vec![];
+        for idx in indices {
+            let Some(msg) = lock.messages.get(idx) else {
+                continue;
+            };
+            messages.push(msg.clone());
+
+            for call in &msg.tool_calls {
+                if let Some(result) = lock.messages[idx..]
+                    .iter()
+                    .find(|other| other.role.is_tool() && other.tool_call_id == call.id)
+                {
+                    messages.push(result.clone());
+                }
+            }
+        }
+
+        Some(messages)
+    }
+}
@@ -4,8 +4,10 @@ use atoman::{Config, State, StateGuard};
 use macron::str;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 /// The default system prompt
@@ -70,6 +72,12 @@ Return only the summary text (do not include meta-comments or explanations about
 Break it down into numbered sections.
 "#;
 
+/// The default session title prompt
+const TITLE_PROMPT: &str = r#"
+Write a short title (3-6 words) for the dialogue above, in the language of the dialogue.
+Return only the title text without quotes or punctuation at the end.
+"#;
+
 /// The settings instance
 static SETTINGS: State<Config<Settings>> = State::default();
 
@@ -93,11 +101,20 @@ impl ::std::default::Default for ServerOptions {
 
 /// The execution control options for assistant runs
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ExecutionOptions {
     /// The number of recent messages to preserve during context compression
     pub preserve_messages: usize,
     /// The maximum number of retries for failed AI calls
     pub max_retries: usize,
+    /// The maximum number of planning/control cycles per query (0 = unlimited)
+    pub max_cycles: usize,
+    /// The maximum number of tool calling steps per agent task
+    pub max_agent_steps: usize,
+    /// The agent skills whose tools the planner calls directly (by agent name, empty = all skills)
+    pub direct_tools: HashMap<String, Vec<String>>,
+    /// The agent tool calls timeouts
+    pub timeouts: TimeoutOptions,
 }
 
 impl ::std::default::Default for ExecutionOptions {
@@ -105,6 +122,86 @@ impl ::std::default::Default for ExecutionOptions {
         Self {
             preserve_messages: 2,
             max_retries: 5,
+            max_cycles: 5,
+            max_agent_steps: 8,
+            direct_tools: HashMap::new(),
+            timeouts: TimeoutOptions::default(),
+        }
+    }
+}
+
+/// The agent tool calls timeouts options
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutOptions {
+    /// The default tool call timeout in seconds (0 = no timeout)
+    pub tool: u64,
+    /// The per-agent timeouts overrides (by agent name)
+    pub agents: HashMap<String, AgentTimeouts>,
+}
+
+impl ::std::default::Default for TimeoutOptions {
+    fn default() -> Self {
+        Self {
+            tool: 120,
+            agents: HashMap::new(),
+        }
+    }
+}
+
+impl TimeoutOptions {
+    /// Returns the tool call timeout of the agent tool (None = no timeout)
+    pub fn tool_timeout(&self, agent: &str, tool: &str) -> Option<Duration> {
+        let secs = self
+            .agents
+            .get(agent)
+            .and_then(|agent| agent.tools.get(tool).copied().or(agent.tool))
+            .unwrap_or(self.tool);
+
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+}
+
+/// The agent tool calls timeouts overrides
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentTimeouts {
+    /// The agent tool calls timeout in seconds (0 = no timeout)
+    pub tool: Option<u64>,
+    /// The per-tool timeouts in seconds (by tool name)
+    pub tools: HashMap<String, u64>,
+}
+
+/// The agent workers lifecycle options
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AgentsOptions {
+    /// The idle time in seconds after which the agent worker is stopped (0 = never)
+    pub idle_timeout: u64,
+    /// The number of crashes within the window after which the agent is quarantined
+    pub max_crashes: usize,
+    /// The crashes accounting window in seconds
+    pub crash_window: u64,
+    /// The initial restart backoff in milliseconds (doubled after every crash)
+    pub restart_backoff: u64,
+    /// The maximum restart backoff in milliseconds
+    pub max_backoff: u64,
+    /// The maximum agent log file size in bytes (0 = unlimited)
+    pub log_max_size: u64,
+    /// The maximum number of agent log files (including the rotated ones)
+    pub log_max_files: usize,
+}
+
+impl ::std::default::Default for AgentsOptions {
+    fn default() -> Self {
+        Self {
+            idle_timeout: 600,
+            max_crashes: 3,
+            crash_window: 300,
+            restart_backoff: 500,
+            max_backoff: 30_000,
+            log_max_size: 5 * 1024 * 1024,
+            log_max_files: 5,
         }
     }
 }
@@ -126,13 +223,16 @@ impl Default for RuntimeOptions {
 
 /// The main completions pipeline options
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CompletionsOptions {
-    /// The base system prompt template
+    /// The base system prompt template (placed after the static prompts)
     pub system_prompt: String,
     /// The primary assistant role and behavior prompt
     pub assist_prompt: String,
     /// The control prompt for evaluating agent task execution
     pub control_prompt: String,
+    /// The system prompt datetime granularity in seconds (keeps the prompt prefix cacheable)
+    pub time_granularity: u64,
     /// Model and provider parameters for completions
     pub options: Options,
 }
@@ -150,6 +250,7 @@ impl ::std::default::Default for CompletionsOptions {
             system_prompt: str!(SYSTEM_PROMPT.trim()),
             assist_prompt: str!(ASSISTANT_PROMPT.trim()),
             control_prompt: str!(CONTROL_PROMPT.trim()),
+            time_granularity: 60,
             options,
         }
     }
@@ -157,11 +258,35 @@ impl ::std::default::Default for CompletionsOptions {
 
 /// The context compression pipeline options
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CompressionOptions {
     /// The prompt used for summarizing and compressing context
     pub prompt: String,
     /// Model and provider parameters for compression
     pub options: Option<Options>,
+    /// Flag indicating whether the context is compacted automatically on overflow
+    pub auto: bool,
+    /// The share of the model context window that triggers the auto compaction
+    pub threshold: f32,
+    /// The context window size in tokens of the models not listed in `context_lengths`
+    pub context_length: usize,
+    /// The context window sizes in tokens by model name
+    pub context_lengths: HashMap<String, usize>,
+    /// The maximum tool output size in tokens kept in the overflowed context
+    pub max_tool_tokens: usize,
+}
+
+impl CompressionOptions {
+    /// Returns the context size in tokens that triggers the auto compaction for the model
+    pub fn context_limit(&self, model: &str) -> usize {
+        let length = self
+            .context_lengths
+            .get(model)
+            .copied()
+            .unwrap_or(self.context_length);
+
+        (length as f32 * self.threshold.clamp(0.0, 1.0)) as usize
+    }
 }
 
 impl ::std::default::Default for CompressionOptions {
@@ -169,6 +294,11 @@ impl ::std::default::Default for CompressionOptions {
         Self {
             prompt: str!(COMPRESSION_PROMPT.trim()),
             options: None,
+            auto: true,
+            threshold: 0.8,
+            context_length: 32_768,
+            context_lengths: HashMap::new(),
+            max_tool_tokens: 1_024,
         }
     }
 }
@@ -212,13 +342,113 @@ impl ::std::default::Default for ContextOptions {
     }
 }
 
+/// The planner agents pre-selection options
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelectionOptions {
+    /// Flag indicating whether the relevant agent skills pre-selection is enabled
+    pub enable: bool,
+    /// The maximum number of the agent skills offered to the planner
+    pub top_k: usize,
+    /// The minimum query similarity of the selected skill (the full list is offered below it)
+    pub min_similarity: f32,
+}
+
+impl ::std::default::Default for SelectionOptions {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            top_k: 8,
+            min_similarity: 0.3,
+        }
+    }
+}
+
+/// The user sessions options
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionsOptions {
+    /// Flag indicating whether the session titles are generated by the model (the first message is truncated otherwise)
+    pub generate_titles: bool,
+    /// The prompt used for generating the session title
+    pub title_prompt: String,
+    /// The maximum session title length in chars
+    pub title_length: usize,
+    /// The idle time in seconds after which the session is closed in memory (0 = never)
+    pub idle_timeout: u64,
+}
+
+impl ::std::default::Default for SessionsOptions {
+    fn default() -> Self {
+        Self {
+            generate_titles: true,
+            title_prompt: str!(TITLE_PROMPT.trim()),
+            title_length: 60,
+            idle_timeout: 1800,
+        }
+    }
+}
+
+/// The user sessions retention policy (0 = no limit, the operator opts in)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionOptions {
+    /// The maximum number of sessions per user (the least recently active are removed)
+    pub max_sessions: usize,
+    /// The maximum session inactivity age in days
+    pub max_age: u64,
+    /// The maximum sessions disk usage per user in megabytes
+    pub max_disk_usage: u64,
+    /// The sessions garbage collection interval in seconds (0 = disabled)
+    pub gc_interval: u64,
+}
+
+impl ::std::default::Default for RetentionOptions {
+    fn default() -> Self {
+        Self {
+            max_sessions: 0,
+            max_age: 0,
+            max_disk_usage: 0,
+            gc_interval: 3600,
+        }
+    }
+}
+
+/// The conversation history search options
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryOptions {
+    /// Flag indicating whether the messages are embedded for the semantic search
+    pub semantic: bool,
+    /// The similarity coefficient threshold of the semantic search hit
+    pub similarity: f32,
+    /// The maximum search results count
+    pub search_limit: usize,
+    /// The search result snippet length in chars
+    pub snippet_length: usize,
+}
+
+impl ::std::default::Default for HistoryOptions {
+    fn default() -> Self {
+        Self {
+            semantic: true,
+            similarity: 0.5,
+            search_limit: 10,
+            snippet_length: 300,
+        }
+    }
+}
+
 /// The query cache options
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CacheOptions {
     /// Flag indicating whether response caching is enabled
     pub enable: bool,
     /// The similarity coefficient threshold required for a cache hit
     pub coefficient: f32,
+    /// The cached answer lifetime in seconds (0 = never expires)
+    pub ttl: u64,
 }
 
 impl ::std::default::Default for CacheOptions {
@@ -226,6 +456,7 @@ impl ::std::default::Default for CacheOptions {
         Self {
             enable: false,
             coefficient: 0.9,
+            ttl: 86_400,
         }
     }
 }
@@ -237,6 +468,9 @@ pub struct Settings {
     pub server: ServerOptions,
     /// Execution control options for assistant runs
     pub execution: ExecutionOptions,
+    /// Agent workers lifecycle options
+    #[serde(default)]
+    pub agents: AgentsOptions,
     /// JavaScript runtime options
     pub runtime: RuntimeOptions,
     /// Main completions pipeline options
@@ -247,8 +481,20 @@ pub struct Settings {
     pub embeddings: EmbeddingsOptions,
     /// RAG memory and context settings
     pub context: ContextOptions,
+    /// Planner agents pre-selection settings
+    #[serde(default)]
+    pub selection: SelectionOptions,
     /// Response caching settings
     pub cache: CacheOptions,
+    /// User sessions settings
+    #[serde(default)]
+    pub sessions: SessionsOptions,
+    /// User sessions retention policy
+    #[serde(default)]
+    pub retention: RetentionOptions,
+    /// Conversation history search settings
+    #[serde(default)]
+    pub history: HistoryOptions,
 }
 
 impl Settings {
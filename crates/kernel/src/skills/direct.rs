@@ -0,0 +1,41 @@
+use crate::prelude::*;
+use anylm::api::Tool;
+
+/// The namespaced agent tool name separator (e.g. `system-agent__set_volume`)
+pub const SEPARATOR: &str = "__";
+
+/// The agent tool call planned directly (executed without the agent model)
+#[derive(Default, Debug, Clone)]
+pub struct DirectCall {
+    pub tool: String,
+    /// The raw JSON arguments
+    pub args: String,
+}
+
+/// Returns the agent tool schema namespaced for the planner
+pub fn namespaced(agent: &str, tool: &Tool) -> Option<Tool> {
+    let mut schema = serde_json::to_value(tool).ok()?;
+    let name = schema["name"].as_str()?.to_owned();
+    let description = schema["description"]
+        .as_str()
+        .unwrap_or_default()
+        .to_owned();
+
+    schema["name"] = json!(format!("{agent}{SEPARATOR}{name}"));
+    schema["description"] = json!(format!("The `{agent}` agent tool: {description}"));
+
+    serde_json::from_value(schema).ok()
+}
+
+/// Returns true if the tool schema is named so
+pub fn is_named(tool: &Tool, name: &str) -> bool {
+    serde_json::to_value(tool)
+        .ok()
+        .is_some_and(|schema| schema["name"] == name)
+}
+
+/// Splits the namespaced tool name into the agent & tool names
+pub fn split_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once(SEPARATOR)
+        .filter(|(agent, tool)| !agent.is_empty() && !tool.is_empty())
+}
@@ -21,12 +21,56 @@ pub fn tools_list() -> Vec<Tool> {
                 "Optional task ID to inject the execution result directly into its input context.",
             ),
         )
+        .optional_property(
+            "parameter",
+            Schema::string(
+                "Optional placeholder name: the result replaces `{{parameter}}` in the task query \
+                 (the result is appended to the query end if omitted). Requires task_id.",
+            ),
+        )
+        .optional_property(
+            "depend_tasks",
+            Schema::array(
+                "Identifiers of tasks whose results the code transforms: the code runs after they are completed, \
+                 with each result bound as the `task_<ID>` string variable (e.g. `JSON.parse(task_1)`). Requires task_id.",
+            )
+            .items(Schema::integer("Identifier of task that must be completed before.")),
+        )
     ]
 }
 
-#[derive(Deserialize)]
+/// The JS evaluation info
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct EvalAction {
     pub task_id: Option<i64>,
     pub parameter: Option<String>,
     pub code: String,
+    #[serde(default)]
+    pub depend_tasks: HashSet<i64>,
+}
+
+impl EvalAction {
+    /// Returns the variable name of the task result
+    pub fn task_var(task_id: i64) -> String {
+        format!("task_{task_id}")
+    }
+
+    /// Injects the evaluation result into the task query
+    pub fn inject(&self, task_id: i64, task_query: &mut String, result: &str) {
+        if let Some(parameter) = &self.parameter {
+            let placeholder = format!("{{{{{parameter}}}}}");
+
+            if task_query.contains(&placeholder) {
+                *task_query = task_query.replace(&placeholder, result);
+            } else {
+                warn!("Placeholder '{parameter}' not found in task #{task_id}");
+            }
+        } else {
+            if !task_query.ends_with('\n') {
+                task_query.push('\n');
+            }
+
+            task_query.push_str(result);
+        }
+    }
 }
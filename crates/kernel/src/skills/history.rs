@@ -0,0 +1,28 @@
+use crate::prelude::*;
+use anylm::api::{Schema, Tool};
+
+pub fn tools_list() -> Vec<Tool> {
+    vec![
+        Tool::new(
+            "search_history",
+            "Searches the user's past conversations (including the compressed history) by keywords and meaning. \
+            Use this when the user refers to something discussed earlier that isn't in the current context \
+            (e.g., \"what did we decide about X last week\"). The found messages are returned to you before answering.",
+        )
+        .required_property(
+            "query",
+            Schema::string("The search text: the key terms or a short description of the discussed topic."),
+        )
+        .optional_property(
+            "limit",
+            Schema::integer("Optional maximum number of the found messages."),
+        ),
+    ]
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SearchHistoryAction {
+    pub query: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
@@ -1,3 +1,5 @@
+pub mod direct;
 pub mod eval;
 pub mod fact;
+pub mod history;
 pub mod task;
@@ -1,3 +1,4 @@
+use super::{direct::DirectCall, eval::EvalAction};
 use crate::prelude::*;
 
 use anylm::api::{Schema, Tool};
@@ -46,9 +47,48 @@ pub struct TaskAction {
     pub task_query: String,
     #[serde(default)]
     pub depend_tasks: HashSet<i64>,
+    /// The JS transforms over the dependency results (applied to the query before handling)
+    #[serde(skip)]
+    pub transforms: Vec<EvalAction>,
+    /// The agent tool called by the planner directly (None = handled by the agent model)
+    #[serde(skip)]
+    pub direct: Option<DirectCall>,
 }
 
 impl TaskAction {
+    /// Creates the task of the agent tool called by the planner directly
+    pub fn direct(
+        task_id: i64,
+        tool_call_id: String,
+        agent_name: &str,
+        agent_skills: Vec<String>,
+        call: DirectCall,
+    ) -> Self {
+        Self {
+            task_id,
+            tool_call_id,
+            agent_name: agent_name.to_owned(),
+            agent_skills,
+            task_query: str!("Call the `{}` tool: {}", call.tool, call.args),
+            depend_tasks: set![],
+            transforms: vec![],
+            direct: Some(call),
+        }
+    }
+
+    /// Returns the call signature (equal for the identical calls, whatever the task IDs)
+    pub fn signature(&self) -> String {
+        let mut skills = self.agent_skills.clone();
+        skills.sort();
+
+        json!({
+            "agent_name": self.agent_name,
+            "agent_skills": skills,
+            "task_query": self.task_query,
+        })
+        .to_string()
+    }
+
     /// Generates the random task ID
     fn random_id() -> i64 {
         SystemTime::now()
@@ -57,3 +97,94 @@ impl TaskAction {
             .as_nanos() as i64
     }
 }
+
+/// Returns the planned calls signatures (the dependencies are referred by their calls, not IDs)
+pub fn plan_signatures(tasks: &[TaskAction]) -> Vec<String> {
+    let calls: HashMap<i64, String> = tasks
+        .iter()
+        .map(|task| (task.task_id, task.signature()))
+        .collect();
+
+    tasks
+        .iter()
+        .map(|task| {
+            let mut depends = task
+                .depend_tasks
+                .iter()
+                .filter_map(|id| calls.get(id))
+                .collect::<Vec<_>>();
+            depends.sort();
+
+            json!({ "call": calls[&task.task_id], "depends": depends }).to_string()
+        })
+        .collect()
+}
+
+/// Validates the planned tasks graph: the task IDs must be unique and the dependencies acyclic
+pub fn validate_plan(tasks: &[TaskAction]) -> StdResult<(), Error> {
+    // check duplicate IDs:
+    let mut ids = HashSet::new();
+    let mut duplicates = tasks
+        .iter()
+        .filter(|task| !ids.insert(task.task_id))
+        .map(|task| task.task_id)
+        .collect::<Vec<_>>();
+
+    if !duplicates.is_empty() {
+        duplicates.sort();
+        duplicates.dedup();
+        return Err(Error::DuplicateTaskIds { ids: duplicates });
+    }
+
+    // check dependency cycles (depth-first search):
+    let graph: HashMap<i64, &HashSet<i64>> = tasks
+        .iter()
+        .map(|task| (task.task_id, &task.depend_tasks))
+        .collect();
+
+    let mut roots = graph.keys().copied().collect::<Vec<_>>();
+    roots.sort();
+
+    let mut visited = HashSet::new();
+    for root in roots {
+        let mut path = vec![];
+        if let Some(cycle) = find_cycle(root, &graph, &mut visited, &mut path) {
+            return Err(Error::TaskDependencyCycle { cycle });
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the dependency cycle reachable from the task (as a closed IDs path)
+fn find_cycle(
+    id: i64,
+    graph: &HashMap<i64, &HashSet<i64>>,
+    visited: &mut HashSet<i64>,
+    path: &mut Vec<i64>,
+) -> Option<Vec<i64>> {
+    if let Some(pos) = path.iter().position(|&other| other == id) {
+        let mut cycle = path[pos..].to_vec();
+        cycle.push(id);
+        return Some(cycle);
+    }
+
+    // unknown dependencies are stripped before spawning:
+    let depends = graph.get(&id)?;
+    if !visited.insert(id) {
+        return None;
+    }
+
+    let mut depends = depends.iter().copied().collect::<Vec<_>>();
+    depends.sort();
+
+    path.push(id);
+    for dep in depends {
+        if let Some(cycle) = find_cycle(dep, graph, visited, path) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+
+    None
+}
@@ -0,0 +1,383 @@
+//! The agents tests: the tools cache, the tool calls timeouts, the workers supervision & logs.
+
+mod common;
+
+use common::{Kernel, MockLlm, Reply, Request, answer, set, texts};
+use ovsy_share::{AgentInfo, EventKind};
+use serde_json::{Value as JsonValue, json};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Returns the tools names of the agent skill
+fn tool_names(info: &AgentInfo, skill: &str) -> Vec<String> {
+    info.tools[skill]
+        .iter()
+        .map(|tool| {
+            serde_json::to_value(tool).unwrap()["name"]
+                .as_str()
+                .unwrap()
+                .to_owned()
+        })
+        .collect()
+}
+
+/// Returns the fake agent details
+async fn agent_info(kernel: &Kernel) -> AgentInfo {
+    kernel
+        .get("/agents/fake-agent")
+        .await
+        .json::<AgentInfo>()
+        .await
+        .unwrap()
+}
+
+/// Waits for the fake agent details to satisfy the condition
+async fn wait_agent(kernel: &Kernel, condition: impl Fn(&AgentInfo) -> bool) -> AgentInfo {
+    for _ in 0..100 {
+        let info = agent_info(kernel).await;
+        if condition(&info) {
+            return info;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!(
+        "The agent status is not reached: {:?}",
+        agent_info(kernel).await.status
+    );
+}
+
+/// Kills the fake agent worker process
+fn kill_agent(kernel: &Kernel) {
+    let worker = kernel.root.join("bin/ovsy-fake-agent serve");
+    let status = std::process::Command::new("pkill")
+        .args(["-KILL", "-f", &worker.to_string_lossy()])
+        .status()
+        .unwrap();
+    assert!(status.success(), "The agent worker is not running");
+}
+
+/// Returns the files of the directory tree with the name prefix (sorted)
+fn find_files(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let mut found = vec![];
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            found.extend(find_files(&path, prefix));
+        } else if entry.file_name().to_string_lossy().starts_with(prefix) {
+            found.push(path);
+        }
+    }
+    found.sort();
+    found
+}
+
+#[tokio::test]
+async fn agent_tools_are_cached() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say alpha", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "alpha" }))
+    });
+
+    let kernel = Kernel::start(&llm, |_| {}).await;
+
+    // the dormant agent has no cached tools:
+    let info = kernel
+        .get("/agents/fake-agent")
+        .await
+        .json::<AgentInfo>()
+        .await
+        .unwrap();
+    assert_eq!(info.status.name, "fake-agent");
+    assert!(!info.status.resident);
+    assert!(info.tools.is_empty());
+
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Say it").await;
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+
+    // the tools are fetched on the agent start and passed to the agent model:
+    let info = kernel
+        .get("/agents/fake-agent")
+        .await
+        .json::<AgentInfo>()
+        .await
+        .unwrap();
+    assert!(info.status.resident);
+    assert_eq!(info.skills[0].name, "testing");
+    assert_eq!(tool_names(&info, "testing"), ["echo", "sleep", "fail"]);
+
+    let agents = llm.agent_requests();
+    let offered = agents[0].0["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tool| tool["function"]["name"].clone())
+        .collect::<Vec<JsonValue>>();
+    assert_eq!(offered, [json!("echo"), json!("sleep"), json!("fail")]);
+}
+
+#[tokio::test]
+async fn direct_tools_do_not_start_evicted_agents() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| Reply::text("All done."));
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "agents.idle_timeout", 1);
+        set(
+            settings,
+            "execution.direct_tools.fake-agent",
+            vec!["testing"],
+        );
+    })
+    .await;
+    let sid = kernel.session().await;
+
+    // the schemas are fetched on the kernel start by starting the agent once:
+    kernel.query(&sid, "First").await;
+    assert!(llm.planner_requests()[0].has_tool("fake-agent__echo"));
+
+    // the idle agent is stopped, its schemas are kept:
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let info = kernel
+        .get("/agents/fake-agent")
+        .await
+        .json::<AgentInfo>()
+        .await
+        .unwrap();
+    assert!(!info.status.resident);
+    assert_eq!(tool_names(&info, "testing"), ["echo", "sleep", "fail"]);
+
+    // the direct tools are offered from the cache, the agent stays dormant:
+    kernel.query(&sid, "Second").await;
+    assert!(llm.planner_requests()[1].has_tool("fake-agent__echo"));
+
+    let info = kernel
+        .get("/agents/fake-agent")
+        .await
+        .json::<AgentInfo>()
+        .await
+        .unwrap();
+    assert!(!info.status.resident);
+}
+
+#[tokio::test]
+async fn busy_agents_are_not_evicted() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Sleep long", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("sleep", json!({ "ms": 3000 }))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "agents.idle_timeout", 1);
+    })
+    .await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Sleep").await;
+
+    // the tool call outlasts the idle timeout, but the agent isn't stopped during it:
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+    assert!(
+        texts(&events, EventKind::Answer)
+            .concat()
+            .contains("slept 3000ms")
+    );
+
+    let info = kernel
+        .get("/agents/fake-agent")
+        .await
+        .json::<AgentInfo>()
+        .await
+        .unwrap();
+    assert!(info.status.crashes.is_empty(), "{:?}", info.status.crashes);
+}
+
+#[tokio::test]
+async fn tool_call_timeout_is_fed_back() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Sleep long", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("sleep", json!({ "ms": 5000 }))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "execution.timeouts.tool", 0);
+        set(
+            settings,
+            "execution.timeouts.agents.fake-agent.tools.sleep",
+            1,
+        );
+    })
+    .await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Sleep").await;
+
+    // the per-tool timeout stops the call, the task goes on:
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+    assert!(
+        texts(&events, EventKind::Thinking)
+            .iter()
+            .any(|text| text == "Tool `sleep` of agent `fake-agent` timed out after 1s")
+    );
+    assert!(answer(&events).ends_with("All done."));
+
+    // the agent model gets the structured error to retry or change the strategy:
+    let step = llm
+        .agent_requests()
+        .into_iter()
+        .find(Request::is_agent_step)
+        .unwrap();
+    let result = step
+        .messages()
+        .iter()
+        .find(|msg| msg["role"] == "tool")
+        .map(Request::message_text)
+        .unwrap();
+    let result: JsonValue = serde_json::from_str(&result).unwrap();
+    assert_eq!(result["error"], "timeout");
+    assert!(
+        result["message"]
+            .as_str()
+            .unwrap()
+            .contains("timed out after 1s")
+    );
+    assert!(step.last_user_text().contains("Some tool calls timed out"));
+}
+
+#[tokio::test]
+async fn crashed_agent_is_restarted_and_quarantined() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say alpha", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "alpha" }))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "agents.max_crashes", 3);
+        set(settings, "agents.restart_backoff", 100);
+    })
+    .await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "Say it").await;
+    assert!(llm.planner_requests()[0].contains("The fake agent for tests."));
+
+    // the crashed worker is restarted with the doubled backoff:
+    for crashes in 1..=2 {
+        kill_agent(&kernel);
+        let info = wait_agent(&kernel, |info| {
+            info.status.crashes.len() == crashes && info.status.resident
+        })
+        .await;
+        assert!(!info.status.quarantined);
+    }
+
+    let output = kernel.cli(&["logs", "-n", "1000"]).await;
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.contains("Restarting agent `fake-agent` in 100ms"),
+        "{logs}"
+    );
+    assert!(
+        logs.contains("Restarting agent `fake-agent` in 200ms"),
+        "{logs}"
+    );
+
+    // too many crashes within the window quarantine the agent:
+    kill_agent(&kernel);
+    let info = wait_agent(&kernel, |info| info.status.quarantined).await;
+    assert_eq!(info.status.crashes.len(), 3);
+    assert!(
+        info.status
+            .crashes
+            .iter()
+            .all(|crash| crash.reason.starts_with("Worker exited"))
+    );
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(!agent_info(&kernel).await.status.resident);
+
+    // the planner doesn't delegate to the quarantined agent anymore:
+    kernel.query(&sid, "Say it again").await;
+    let planner = llm.planner_requests().pop().unwrap();
+    assert!(!planner.contains("The fake agent for tests."));
+}
+
+#[tokio::test]
+async fn agent_output_is_logged_with_rotation() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Echo lines", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tools(
+            (1..=10)
+                .map(|i| ("echo", json!({ "text": format!("line {i}") })))
+                .collect(),
+        )
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "agents.log_max_size", 150);
+        set(settings, "agents.log_max_files", 3);
+    })
+    .await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Echo lines").await;
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // the worker output is split into the limited number of files:
+    let files = find_files(&kernel.root.join("state"), "fake-agent");
+    let names = files
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        ["fake-agent.1.log", "fake-agent.2.log", "fake-agent.log"]
+    );
+    for path in &files {
+        assert!(std::fs::metadata(path).unwrap().len() <= 150, "{path:?}");
+    }
+
+    // the lines are tagged with the agent name & PID:
+    let output = kernel.cli(&["logs", "fake-agent", "-n", "1"]).await;
+    let logs = String::from_utf8_lossy(&output.stdout);
+    let line = logs.lines().last().unwrap();
+    assert!(line.contains("[fake-agent:"), "{logs}");
+    assert!(line.contains("] echo: line "), "{logs}");
+}
+
+#[tokio::test]
+async fn unknown_agent_is_not_found() {
+    let llm = MockLlm::start().await;
+    let kernel = Kernel::start(&llm, |_| {}).await;
+
+    let res = kernel.get("/agents/missing-agent").await;
+    assert_eq!(res.status(), 404);
+}
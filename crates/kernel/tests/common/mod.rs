@@ -0,0 +1,793 @@
+//! The kernel integration tests harness: a scriptable OpenAI-compatible stub server and
+//! an isolated kernel instance with the fake agent installed.
+
+#![allow(dead_code)]
+
+use anylm::api::Message;
+use ovsy_share::{
+    Event, EventKind, HandleQuery, SessionId, SessionInfo, SessionSummary, UserSessionsQuery,
+};
+use serde_json::{Value as JsonValue, json};
+use std::{
+    net::TcpListener as StdTcpListener,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    process::{Child, Command},
+};
+
+/// The recorded completions request
+#[derive(Debug, Clone)]
+pub struct Request(pub JsonValue);
+
+impl Request {
+    /// Returns true if it's the planner (or control) request
+    pub fn is_planner(&self) -> bool {
+        self.has_tool("handle_agent")
+    }
+
+    /// Returns true if it's the agent task request
+    pub fn is_agent(&self) -> bool {
+        !self.is_planner() && !self.is_compaction() && !self.is_title()
+    }
+
+    /// Returns true if it's the session history compaction request
+    pub fn is_compaction(&self) -> bool {
+        !self.is_planner()
+            && self
+                .last_user_text()
+                .contains("summary of our dialogue history")
+    }
+
+    /// Returns true if it's the agent follow-up request (with the tool results)
+    pub fn is_agent_step(&self) -> bool {
+        self.is_agent() && self.messages().iter().any(|msg| msg["role"] == "tool")
+    }
+
+    /// Returns true if the tool is offered to the model
+    pub fn has_tool(&self, name: &str) -> bool {
+        self.0["tools"]
+            .as_array()
+            .is_some_and(|tools| tools.iter().any(|tool| tool["function"]["name"] == name))
+    }
+
+    /// Returns the request messages
+    pub fn messages(&self) -> Vec<JsonValue> {
+        self.0["messages"].as_array().cloned().unwrap_or_default()
+    }
+
+    /// Returns the text of the message
+    pub fn message_text(message: &JsonValue) -> String {
+        match &message["content"] {
+            JsonValue::String(text) => text.clone(),
+            JsonValue::Array(parts) => parts
+                .iter()
+                .filter_map(|part| part["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => String::new(),
+        }
+    }
+
+    /// Returns the system prompt text
+    pub fn system_text(&self) -> String {
+        self.messages()
+            .iter()
+            .filter(|msg| msg["role"] == "system")
+            .map(Self::message_text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Returns the last user message text
+    pub fn last_user_text(&self) -> String {
+        self.messages()
+            .iter()
+            .rev()
+            .find(|msg| msg["role"] == "user")
+            .map(Self::message_text)
+            .unwrap_or_default()
+    }
+
+    /// Returns true if it's the session title generation request
+    pub fn is_title(&self) -> bool {
+        !self.is_planner() && self.last_user_text().contains("short title")
+    }
+
+    /// Returns the prompt as the LLM server sees it (the tools go before the messages)
+    pub fn prompt(&self) -> String {
+        format!("{}{}", self.0["tools"], self.0["messages"])
+    }
+
+    /// Returns true if the request contains the text anywhere
+    pub fn contains(&self, text: &str) -> bool {
+        self.0.to_string().contains(text)
+    }
+}
+
+/// The scripted model reply
+#[derive(Debug, Clone)]
+pub enum Reply {
+    /// Plain text answer (streamed by words)
+    Text(String),
+    /// Tool calls with the raw arguments strings
+    Tools(Vec<(String, String)>),
+    /// Empty answer without tool calls
+    Empty,
+    /// Unparsable stream chunk
+    Malformed,
+    /// The reply followed by an unparsable stream chunk
+    Broken(Box<Reply>),
+}
+
+impl Reply {
+    /// Creates a text reply
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// Creates a single tool call reply
+    pub fn tool(name: &str, args: JsonValue) -> Self {
+        Self::Tools(vec![(name.to_owned(), args.to_string())])
+    }
+
+    /// Creates a multiple tool calls reply
+    pub fn tools(calls: Vec<(&str, JsonValue)>) -> Self {
+        Self::Tools(
+            calls
+                .into_iter()
+                .map(|(name, args)| (name.to_owned(), args.to_string()))
+                .collect(),
+        )
+    }
+
+    /// Breaks the stream after the reply
+    pub fn broken(self) -> Self {
+        Self::Broken(Box::new(self))
+    }
+
+    /// Creates a `handle_agent` task tool call arguments
+    pub fn task(id: i64, agent: &str, query: &str, depends: &[i64]) -> (&'static str, JsonValue) {
+        (
+            "handle_agent",
+            json!({
+                "agent_name": agent,
+                "agent_skills": ["testing"],
+                "task_id": id,
+                "task_query": query,
+                "depend_tasks": depends,
+            }),
+        )
+    }
+
+    /// Returns the SSE stream payloads
+    fn payloads(&self, calls_counter: &AtomicUsize) -> Vec<String> {
+        let chunk = |delta: JsonValue, finish: Option<&str>| {
+            json!({ "choices": [{ "delta": delta, "finish_reason": finish }] }).to_string()
+        };
+
+        let mut payloads = vec![];
+        match self {
+            Self::Text(text) => {
+                for word in text.split_inclusive(' ') {
+                    payloads.push(chunk(json!({ "content": word }), None));
+                }
+                payloads.push(chunk(json!({}), Some("stop")));
+            }
+            Self::Tools(calls) => {
+                for (index, (name, args)) in calls.iter().enumerate() {
+                    let id = format!("call_{}", calls_counter.fetch_add(1, Ordering::SeqCst));
+                    payloads.push(chunk(
+                        json!({ "tool_calls": [{
+                            "index": index,
+                            "id": id,
+                            "type": "function",
+                            "function": { "name": name, "arguments": args },
+                        }] }),
+                        None,
+                    ));
+                }
+                payloads.push(chunk(json!({}), Some("tool_calls")));
+            }
+            Self::Empty => {
+                payloads.push(chunk(json!({}), Some("stop")));
+            }
+            Self::Malformed => {
+                payloads.push(String::from("{\"choices\": oops}"));
+            }
+            Self::Broken(reply) => {
+                payloads.extend(reply.payloads(calls_counter));
+                payloads.extend(Self::Malformed.payloads(calls_counter));
+            }
+        }
+        payloads
+    }
+}
+
+type Matcher = Box<dyn Fn(&Request) -> bool + Send + Sync>;
+type Responder = Box<dyn Fn(&Request) -> Reply + Send + Sync>;
+
+/// The scripted reply rule
+struct Rule {
+    matcher: Matcher,
+    responder: Responder,
+    /// The remaining uses (None = unlimited)
+    times: Option<usize>,
+}
+
+/// The OpenAI-compatible stub server
+pub struct MockLlm {
+    pub url: String,
+    rules: Mutex<Vec<Rule>>,
+    requests: Mutex<Vec<Request>>,
+    calls_counter: AtomicUsize,
+}
+
+impl MockLlm {
+    /// Starts the stub server
+    pub async fn start() -> Arc<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let this = Arc::new(Self {
+            url,
+            rules: Mutex::new(vec![]),
+            requests: Mutex::new(vec![]),
+            calls_counter: AtomicUsize::new(1),
+        });
+
+        let server = this.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let server = server.clone();
+                tokio::spawn(async move { server.handle(stream).await });
+            }
+        });
+
+        this
+    }
+
+    /// Adds the one-time reply rule
+    pub fn once<M>(&self, matcher: M, reply: Reply) -> &Self
+    where
+        M: Fn(&Request) -> bool + Send + Sync + 'static,
+    {
+        self.rule(matcher, move |_| reply.clone(), Some(1))
+    }
+
+    /// Adds the permanent reply rule
+    pub fn always<M, R>(&self, matcher: M, responder: R) -> &Self
+    where
+        M: Fn(&Request) -> bool + Send + Sync + 'static,
+        R: Fn(&Request) -> Reply + Send + Sync + 'static,
+    {
+        self.rule(matcher, responder, None)
+    }
+
+    /// Adds the reply rule
+    fn rule<M, R>(&self, matcher: M, responder: R, times: Option<usize>) -> &Self
+    where
+        M: Fn(&Request) -> bool + Send + Sync + 'static,
+        R: Fn(&Request) -> Reply + Send + Sync + 'static,
+    {
+        self.rules.lock().unwrap().push(Rule {
+            matcher: Box::new(matcher),
+            responder: Box::new(responder),
+            times,
+        });
+        self
+    }
+
+    /// Returns the recorded completions requests
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Returns the recorded planner (and control) requests
+    pub fn planner_requests(&self) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(Request::is_planner)
+            .collect()
+    }
+
+    /// Returns the recorded agent tasks requests
+    pub fn agent_requests(&self) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(Request::is_agent)
+            .collect()
+    }
+
+    /// Returns the recorded agent tasks requests (without the follow-up steps)
+    pub fn agent_tasks(&self) -> Vec<Request> {
+        self.agent_requests()
+            .into_iter()
+            .filter(|req| !req.is_agent_step())
+            .collect()
+    }
+
+    /// Returns the requests with their prompt sizes outside the longest prefix shared with a previous request
+    ///
+    /// This checks the prompt prefix stability only, the server prompt processing time isn't measured.
+    pub fn prompt_costs(&self) -> Vec<(Request, usize)> {
+        let requests = self.requests();
+        let prompts = requests.iter().map(Request::prompt).collect::<Vec<_>>();
+
+        requests
+            .into_iter()
+            .enumerate()
+            .map(|(i, request)| {
+                let cached = prompts[..i]
+                    .iter()
+                    .map(|prev| common_prefix(prev, &prompts[i]))
+                    .max()
+                    .unwrap_or(0);
+                (request, prompts[i].len() - cached)
+            })
+            .collect()
+    }
+
+    /// Picks the reply for the request (the first matching rule wins)
+    fn reply(&self, request: &Request) -> Reply {
+        let mut rules = self.rules.lock().unwrap();
+
+        let Some(pos) = rules
+            .iter()
+            .position(|rule| rule.times.is_none_or(|times| times > 0) && (rule.matcher)(request))
+        else {
+            return Reply::Empty;
+        };
+
+        let rule = &mut rules[pos];
+        if let Some(times) = rule.times.as_mut() {
+            *times -= 1;
+        }
+        (rule.responder)(request)
+    }
+
+    /// Handles the HTTP connection
+    async fn handle(&self, mut stream: TcpStream) {
+        let Some((path, body)) = read_request(&mut stream).await else {
+            return;
+        };
+
+        let response = if path.ends_with("/embeddings") {
+            let input = body["input"].to_string();
+            let data = json!({
+                "object": "list",
+                "data": [{ "object": "embedding", "index": 0, "embedding": embed(&input) }],
+                "model": "mock-embeddings",
+                "usage": { "total_tokens": 1 },
+            })
+            .to_string();
+
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{data}",
+                data.len()
+            )
+        } else {
+            let request = Request(body);
+            self.requests.lock().unwrap().push(request.clone());
+
+            let mut sse = String::new();
+            for payload in self.reply(&request).payloads(&self.calls_counter) {
+                sse.push_str(&format!("data: {payload}\n\n"));
+            }
+            sse.push_str("data: [DONE]\n\n");
+
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{sse}",
+                sse.len()
+            )
+        };
+
+        stream.write_all(response.as_bytes()).await.ok();
+        stream.shutdown().await.ok();
+    }
+}
+
+/// Reads the HTTP request path & JSON body
+async fn read_request(stream: &mut TcpStream) -> Option<(String, JsonValue)> {
+    let mut buf = vec![];
+    let mut chunk = [0; 8192];
+
+    // read the headers:
+    let head_end = loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let path = head.split_whitespace().nth(1)?.to_owned();
+    let length = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+
+    // read the body:
+    while buf.len() < head_end + length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let body = serde_json::from_slice(&buf[head_end..]).unwrap_or(JsonValue::Null);
+    Some((path, body))
+}
+
+/// Returns the length of the common prefix of the texts
+fn common_prefix(a: &str, b: &str) -> usize {
+    a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count()
+}
+
+/// Generates the deterministic fake embedding of the text
+fn embed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0; 8];
+    for (i, byte) in text.bytes().enumerate() {
+        vector[i % 8] += byte as f32 / 255.0;
+    }
+
+    let norm = vector
+        .iter()
+        .map(|x| x * x)
+        .sum::<f32>()
+        .sqrt()
+        .max(f32::EPSILON);
+    vector.into_iter().map(|x| x / norm).collect()
+}
+
+/// The isolated kernel instance
+pub struct Kernel {
+    pub url: String,
+    pub root: PathBuf,
+    child: Child,
+}
+
+impl Kernel {
+    /// Starts the kernel with the fake agent, patching the default settings
+    pub async fn start<F>(llm: &MockLlm, patch: F) -> Self
+    where
+        F: FnOnce(&mut toml::Table),
+    {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "ovsy-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::remove_dir_all(&root).ok();
+
+        // install the kernel & the fake agent binaries:
+        let bin_dir = root.join("bin");
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        install(Path::new(env!("CARGO_BIN_EXE_ovsy")), &bin_dir.join("ovsy"));
+        install(&fake_agent_path(), &bin_dir.join("ovsy-fake-agent"));
+        let kernel_bin = bin_dir.join("ovsy");
+
+        // generate the default settings:
+        Command::new(&kernel_bin)
+            .arg("status")
+            .envs(Self::envs(&root))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .unwrap();
+
+        let settings_path = root.join("config/ovsy/settings.toml");
+        let mut settings: toml::Table =
+            toml::from_str(&std::fs::read_to_string(&settings_path).unwrap()).unwrap();
+
+        let port = StdTcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        set(&mut settings, "server.port", port as i64);
+        set(
+            &mut settings,
+            "completions.options.base_url",
+            llm.url.as_str(),
+        );
+        set(&mut settings, "completions.options.model", "mock");
+        set(
+            &mut settings,
+            "embeddings.options.base_url",
+            llm.url.as_str(),
+        );
+        set(&mut settings, "embeddings.options.model", "mock-embeddings");
+        patch(&mut settings);
+
+        std::fs::write(&settings_path, toml::to_string_pretty(&settings).unwrap()).unwrap();
+
+        // run the server:
+        let child = Self::serve(&root);
+        let this = Self {
+            url: format!("http://127.0.0.1:{port}"),
+            root,
+            child,
+        };
+        this.wait_ready().await;
+        this
+    }
+
+    /// Spawns the kernel server process
+    fn serve(root: &Path) -> Child {
+        Command::new(root.join("bin/ovsy"))
+            .arg("serve")
+            .envs(Self::envs(root))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap()
+    }
+
+    /// Stops the kernel server, prepares its data & starts it again
+    pub async fn restart<F>(&mut self, prepare: F)
+    where
+        F: FnOnce(&Path),
+    {
+        self.child.kill().await.unwrap();
+        prepare(&self.root);
+
+        self.child = Self::serve(&self.root);
+        self.wait_ready().await;
+    }
+
+    /// Returns the isolated environment variables
+    fn envs(root: &Path) -> Vec<(&'static str, PathBuf)> {
+        vec![
+            ("HOME", root.join("home")),
+            ("XDG_CONFIG_HOME", root.join("config")),
+            ("XDG_STATE_HOME", root.join("state")),
+            ("XDG_DATA_HOME", root.join("data")),
+            ("XDG_CACHE_HOME", root.join("cache")),
+            ("TMPDIR", root.join("tmp")),
+        ]
+    }
+
+    /// Waits for the server to respond
+    async fn wait_ready(&self) {
+        std::fs::create_dir_all(self.root.join("tmp")).ok();
+        let client = reqwest::Client::new();
+
+        for _ in 0..100 {
+            if let Ok(res) = client.get(format!("{}/ping", self.url)).send().await
+                && res.status().is_success()
+            {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("The kernel server didn't start at {}", self.url);
+    }
+
+    /// Sends the GET request to the kernel API
+    pub async fn get(&self, path: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}{path}", self.url))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /// Sends the DELETE request to the kernel API
+    pub async fn delete(&self, path: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .delete(format!("{}{path}", self.url))
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /// Sends the POST request with the JSON body to the kernel API
+    pub async fn post(&self, path: &str, body: &JsonValue) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}{path}", self.url))
+            .json(body)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    /// Installs the fake agent copy under the name & rescans the agents
+    pub async fn install_agent(&self, name: &str) {
+        install(
+            &fake_agent_path(),
+            &self.root.join(format!("bin/ovsy-{name}")),
+        );
+        assert!(self.get("/refresh").await.status().is_success());
+    }
+
+    /// Runs the CLI command with the kernel settings
+    pub async fn cli(&self, args: &[&str]) -> std::process::Output {
+        Command::new(self.root.join("bin/ovsy"))
+            .args(args)
+            .envs(Self::envs(&self.root))
+            .output()
+            .await
+            .unwrap()
+    }
+
+    /// Initializes a new user session
+    pub async fn session(&self) -> SessionId {
+        let sid = SessionId::new(1);
+        let info = SessionInfo {
+            current_path: None,
+            timezone: 0,
+        };
+
+        let res = reqwest::Client::new()
+            .post(format!("{}/sessions/{sid}/init", self.url))
+            .json(&info)
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success(), "Failed to init the session");
+
+        sid
+    }
+
+    /// Finishes the session (the client exits)
+    pub async fn finish(&self, sid: &SessionId) {
+        let res = reqwest::Client::new()
+            .post(format!("{}/sessions/{sid}/finish", self.url))
+            .send()
+            .await
+            .unwrap();
+        assert!(res.status().is_success(), "Failed to finish the session");
+    }
+
+    /// Returns the user sessions list page
+    pub async fn sessions(&self, query: &UserSessionsQuery) -> Vec<SessionSummary> {
+        reqwest::Client::new()
+            .post(format!("{}/users/1/sessions", self.url))
+            .json(query)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    /// Returns the session history (as the reloading client receives it)
+    pub async fn history(&self, sid: &SessionId) -> Vec<JsonValue> {
+        let info = SessionInfo {
+            current_path: None,
+            timezone: 0,
+        };
+
+        reqwest::Client::new()
+            .post(format!("{}/sessions/{sid}/init", self.url))
+            .json(&info)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    /// Sends the user query & collects all the stream events
+    pub async fn query(&self, sid: &SessionId, text: &str) -> Vec<Event> {
+        let query = HandleQuery::new(Message::user(vec![text.into()]));
+        self.stream(&format!("/sessions/{sid}/query"), &json!(query))
+            .await
+    }
+
+    /// Sends the POST request & collects all the stream events
+    pub async fn stream(&self, path: &str, body: &JsonValue) -> Vec<Event> {
+        let request = reqwest::Client::new()
+            .post(format!("{}{path}", self.url))
+            .json(body)
+            .send();
+
+        let body = tokio::time::timeout(Duration::from_secs(30), async {
+            request.await.unwrap().text().await.unwrap()
+        })
+        .await
+        .expect("The query stream didn't finish in time");
+
+        body.split("\n\n")
+            .filter_map(|item| item.trim().strip_prefix("data:"))
+            .filter_map(|data| serde_json::from_str(data.trim()).ok())
+            .collect()
+    }
+}
+
+impl Drop for Kernel {
+    fn drop(&mut self) {
+        self.child.start_kill().ok();
+        std::fs::remove_dir_all(&self.root).ok();
+    }
+}
+
+/// Sets the settings value by the dotted path
+pub fn set(table: &mut toml::Table, path: &str, value: impl Into<toml::Value>) {
+    let (parents, key) = path.rsplit_once('.').unwrap_or(("", path));
+
+    let mut table = table;
+    for part in parents.split('.').filter(|part| !part.is_empty()) {
+        table = table
+            .entry(part)
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .unwrap();
+    }
+    table.insert(key.to_owned(), value.into());
+}
+
+/// Installs the binary (hard linked, if possible)
+fn install(from: &Path, to: &Path) {
+    if std::fs::hard_link(from, to).is_err() {
+        std::fs::copy(from, to).unwrap();
+    }
+}
+
+/// Returns the fake agent binary path (built as the kernel example)
+fn fake_agent_path() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let path = exe
+        .parent()
+        .and_then(Path::parent)
+        .unwrap()
+        .join("examples/fake_agent");
+
+    assert!(
+        path.exists(),
+        "The fake agent is not built at {path:?}, run `cargo build -p ovsy --examples`"
+    );
+    path
+}
+
+/// Returns the text of the events of the kind
+pub fn texts(events: &[Event], kind: EventKind) -> Vec<String> {
+    events
+        .iter()
+        .filter(|event| event.kind == kind)
+        .map(|event| event.text.clone())
+        .collect()
+}
+
+/// Returns the messages roles
+pub fn roles(messages: &[JsonValue]) -> Vec<String> {
+    messages
+        .iter()
+        .map(|msg| msg["role"].as_str().unwrap_or_default().to_owned())
+        .collect()
+}
+
+/// Returns the joined answer text (without tasks events)
+pub fn answer(events: &[Event]) -> String {
+    events
+        .iter()
+        .filter(|event| event.kind == EventKind::Answer && event.task_info.is_none())
+        .map(|event| event.text.as_str())
+        .collect()
+}
@@ -0,0 +1,1018 @@
+//! The orchestration loop tests: drive `/sessions/{sid}/query` end to end with the mock LLM
+//! provider and the fake agent.
+
+mod common;
+
+use common::{Kernel, MockLlm, Reply, Request, answer, roles, set, texts};
+use ovsy_share::EventKind;
+use serde_json::json;
+
+/// Starts the kernel with the maximum retries number
+async fn kernel(llm: &MockLlm, max_retries: i64) -> Kernel {
+    Kernel::start(llm, |settings| {
+        set(settings, "execution.max_retries", max_retries)
+    })
+    .await
+}
+
+#[tokio::test]
+async fn plain_text_answer() {
+    let llm = MockLlm::start().await;
+    llm.once(Request::is_planner, Reply::text("Hello there!"));
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Hi").await;
+
+    assert_eq!(answer(&events), "Hello there!");
+    assert_eq!(events.last().unwrap().kind, EventKind::Finish);
+    assert_eq!(llm.planner_requests().len(), 1);
+    assert!(llm.agent_requests().is_empty());
+}
+
+#[tokio::test]
+async fn empty_response_is_retried() {
+    let llm = MockLlm::start().await;
+    llm.once(Request::is_planner, Reply::Empty)
+        .once(Request::is_planner, Reply::text("Second try."));
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Hi").await;
+
+    assert_eq!(answer(&events), "Second try.");
+    assert!(texts(&events, EventKind::Error).is_empty());
+
+    // the retry feedback isn't a part of the persisted turn:
+    let history = kernel.history(&sid).await;
+    assert_eq!(roles(&history), ["user", "assistant"]);
+    assert_eq!(Request::message_text(&history[1]), "Second try.");
+
+    let requests = llm.planner_requests();
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[1]
+            .last_user_text()
+            .starts_with("You returned an empty response")
+    );
+}
+
+#[tokio::test]
+async fn max_retries_exhaustion() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| Reply::Empty);
+
+    let kernel = kernel(&llm, 2).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Hi").await;
+
+    let errors = texts(&events, EventKind::Error);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("Model failed to plan tasks: returned empty response"));
+    assert_eq!(llm.planner_requests().len(), 2);
+}
+
+#[tokio::test]
+async fn malformed_arguments_are_retried() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::Tools(vec![("handle_agent".into(), "{\"task_id\": ".into())]),
+    )
+    .once(Request::is_planner, Reply::text("Recovered."));
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Hi").await;
+
+    assert_eq!(answer(&events), "Recovered.");
+
+    let requests = llm.planner_requests();
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[1]
+            .last_user_text()
+            .contains("Failed to parse handle_agent")
+    );
+}
+
+#[tokio::test]
+async fn stream_error_is_retried() {
+    let llm = MockLlm::start().await;
+    llm.once(Request::is_planner, Reply::Malformed)
+        .once(Request::is_planner, Reply::text("Recovered."));
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Hi").await;
+
+    assert_eq!(answer(&events), "Recovered.");
+
+    let requests = llm.planner_requests();
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[1]
+            .last_user_text()
+            .starts_with("An error occurred during stream generation")
+    );
+}
+
+#[tokio::test]
+async fn dependency_graph_ordering() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![
+            Reply::task(1, "fake-agent", "Say alpha", &[]),
+            Reply::task(2, "fake-agent", "Say beta", &[1]),
+        ]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |req| {
+        let text = if req.contains("Say alpha") {
+            "alpha-result"
+        } else {
+            "beta-result"
+        };
+        Reply::tool("echo", json!({ "text": text }))
+    });
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Run the chain").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+
+    // the dependent task starts only after its dependency finished:
+    let position = |task_id, kind: EventKind| {
+        events
+            .iter()
+            .position(|event| {
+                event.kind == kind
+                    && event
+                        .task_info
+                        .as_ref()
+                        .is_some_and(|i| i.task_id == task_id)
+            })
+            .unwrap()
+    };
+    assert!(position(1, EventKind::Finish) < position(2, EventKind::Thinking));
+
+    // the dependency result is passed to the dependent task:
+    let agents = llm.agent_tasks();
+    assert_eq!(agents.len(), 2);
+    assert!(agents[0].contains("Say alpha"));
+    assert!(agents[1].contains("Say beta"));
+    assert!(agents[1].system_text().contains("alpha-result"));
+
+    // the control query is launched once, after all the tasks:
+    let planners = llm.planner_requests();
+    assert_eq!(planners.len(), 2);
+    assert!(answer(&events).ends_with("All done."));
+
+    // the whole turn is persisted (without the control prompt):
+    let history = kernel.history(&sid).await;
+    assert_eq!(
+        roles(&history),
+        ["user", "assistant", "tool", "tool", "assistant"]
+    );
+    assert_eq!(history[1]["tool_calls"].as_array().unwrap().len(), 2);
+
+    let mut results = history[2..4]
+        .iter()
+        .map(Request::message_text)
+        .collect::<Vec<_>>();
+    results.sort();
+    assert_eq!(results, ["alpha-result", "beta-result"]);
+    assert_eq!(Request::message_text(&history[4]), "All done.");
+}
+
+#[tokio::test]
+async fn query_is_cancelled_mid_tool() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| {
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Sleep long", &[])])
+    })
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("sleep", json!({ "ms": 20_000 }))
+    });
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+
+    let (events, (second, cancel)) = tokio::join!(kernel.query(&sid, "Sleep"), async {
+        // wait for the tool call to start:
+        while llm.agent_requests().is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        // the second query of the session is rejected, the first one stays cancellable:
+        let second = kernel.query(&sid, "Another").await;
+        let cancel = kernel
+            .post(&format!("/sessions/{sid}/cancel"), &json!({}))
+            .await;
+        (second, cancel)
+    });
+
+    assert_eq!(texts(&second, EventKind::Error).len(), 1, "{second:?}");
+    assert!(texts(&second, EventKind::Error)[0].contains("query is in progress"));
+    assert!(cancel.status().is_success());
+    assert_eq!(cancel.text().await.unwrap(), "The query was cancelled");
+
+    assert!(
+        events
+            .iter()
+            .any(|event| event.kind == EventKind::Cancelled),
+        "{events:?}"
+    );
+    assert!(!answer(&events).contains("slept"));
+
+    // the tool call is cancelled on the agent side:
+    let output = kernel.cli(&["logs", "-n", "1000"]).await;
+    let logs = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        logs.lines()
+            .any(|line| line.contains("Tool call `") && line.ends_with("` cancelled")),
+        "{logs}"
+    );
+}
+
+#[tokio::test]
+async fn failed_task_cancels_branch() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![
+            Reply::task(1, "missing-agent", "Do something", &[]),
+            Reply::task(2, "fake-agent", "Say beta", &[1]),
+        ]),
+    )
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "unexpected" }))
+    });
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Run the chain").await;
+
+    let errors = texts(&events, EventKind::Error);
+    assert!(
+        errors
+            .iter()
+            .any(|text| text.contains("Agent `missing-agent` is not available"))
+    );
+    assert!(
+        errors
+            .iter()
+            .any(|text| text == "Cancelled: dependency task 1 failed")
+    );
+
+    // the dependent task is never handled:
+    assert!(llm.agent_requests().is_empty());
+    assert_eq!(llm.planner_requests().len(), 1);
+}
+
+#[tokio::test]
+async fn task_without_skill_tools_fails() {
+    let llm = MockLlm::start().await;
+    let (name, mut args) = Reply::task(1, "fake-agent", "Say alpha", &[]);
+    args["agent_skills"] = json!(["unknown"]);
+    llm.once(Request::is_planner, Reply::tools(vec![(name, args)]))
+        .always(Request::is_agent, |_| {
+            Reply::tool("echo", json!({ "text": "unexpected" }))
+        });
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Say alpha").await;
+
+    // the agent model isn't called without the tools:
+    assert!(
+        texts(&events, EventKind::Error)
+            .iter()
+            .any(|text| text.contains("Agent `fake-agent` has no tools of the skills [unknown]"))
+    );
+    assert!(llm.agent_requests().is_empty());
+}
+
+#[tokio::test]
+async fn dependency_cycle_is_replanned() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![
+            Reply::task(1, "fake-agent", "Say alpha", &[2]),
+            Reply::task(2, "fake-agent", "Say beta", &[1]),
+        ]),
+    )
+    .once(Request::is_planner, Reply::text("Replanned."));
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Run the chain").await;
+
+    assert_eq!(answer(&events), "Replanned.");
+    assert!(llm.agent_requests().is_empty());
+
+    let requests = llm.planner_requests();
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[1]
+            .last_user_text()
+            .contains("\"dependency_cycle\"")
+    );
+    assert!(requests[1].last_user_text().contains("[1,2,1]"));
+}
+
+#[tokio::test]
+async fn duplicate_task_ids_are_replanned() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![
+            Reply::task(1, "fake-agent", "Say alpha", &[]),
+            Reply::task(1, "fake-agent", "Say beta", &[]),
+        ]),
+    )
+    .once(Request::is_planner, Reply::text("Replanned."));
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Run both").await;
+
+    assert_eq!(answer(&events), "Replanned.");
+    assert!(llm.agent_requests().is_empty());
+
+    let requests = llm.planner_requests();
+    assert_eq!(requests.len(), 2);
+    assert!(
+        requests[1]
+            .last_user_text()
+            .contains("\"duplicate_task_ids\"")
+    );
+}
+
+#[tokio::test]
+async fn invalid_plan_exhausts_retries() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| {
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say alpha", &[1])])
+    });
+
+    let kernel = kernel(&llm, 2).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Loop forever").await;
+
+    let errors = texts(&events, EventKind::Error);
+    assert_eq!(errors, vec!["Task dependencies form a cycle: [1, 1]"]);
+    assert!(llm.agent_requests().is_empty());
+}
+
+#[tokio::test]
+async fn eval_transforms_task_results() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![
+            Reply::task(1, "fake-agent", "Say the number", &[]),
+            Reply::task(2, "fake-agent", "Report {{value}}", &[]),
+            (
+                "javascript_eval",
+                json!({
+                    "code": "Number(task_1) * 2",
+                    "task_id": 2,
+                    "parameter": "value",
+                    "depend_tasks": [1],
+                }),
+            ),
+        ]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |req| {
+        let text = if req.contains("Say the number") {
+            "21"
+        } else {
+            "reported"
+        };
+        Reply::tool("echo", json!({ "text": text }))
+    });
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Double it").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+
+    // the transformed task waits for the eval dependencies:
+    let agents = llm.agent_tasks();
+    assert_eq!(agents.len(), 2);
+    assert!(agents[0].contains("Say the number"));
+    assert!(agents[1].last_user_text().ends_with("Report 42"));
+}
+
+#[tokio::test]
+async fn turns_are_persisted() {
+    let llm = MockLlm::start().await;
+    llm.once(Request::is_planner, Reply::text("First answer."))
+        .once(Request::is_planner, Reply::text("Second answer."));
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "First question").await;
+    kernel.query(&sid, "Second question").await;
+
+    let history = kernel.history(&sid).await;
+    assert_eq!(roles(&history), ["user", "assistant", "user", "assistant"]);
+    assert_eq!(Request::message_text(&history[0]), "First question");
+    assert_eq!(Request::message_text(&history[3]), "Second answer.");
+
+    // the next turn is planned with the previous one in context:
+    let requests = llm.planner_requests();
+    assert!(requests[1].contains("First answer."));
+}
+
+#[tokio::test]
+async fn control_cycles_are_bounded() {
+    let llm = MockLlm::start().await;
+
+    // every cycle plans a new task:
+    let counter = std::sync::atomic::AtomicI64::new(1);
+    llm.always(Request::is_planner, move |_| {
+        let id = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        Reply::tools(vec![Reply::task(
+            id,
+            "fake-agent",
+            &format!("Say {id}"),
+            &[],
+        )])
+    })
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "again" }))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| set(settings, "execution.max_cycles", 2)).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Never stop").await;
+
+    let cycles = texts(&events, EventKind::Cycle);
+    assert_eq!(
+        cycles,
+        [
+            json!({ "cycle": 1, "max_cycles": 2 }).to_string(),
+            json!({ "cycle": 2, "max_cycles": 2 }).to_string(),
+        ]
+    );
+    assert_eq!(llm.planner_requests().len(), 2);
+    assert!(answer(&events).ends_with("I could not complete the request after 2 cycles."));
+    assert_eq!(events.last().unwrap().kind, EventKind::Finish);
+
+    // the terminal answer is persisted:
+    let history = kernel.history(&sid).await;
+    assert_eq!(
+        Request::message_text(history.last().unwrap()),
+        "I could not complete the request after 2 cycles."
+    );
+}
+
+#[tokio::test]
+async fn repeated_plan_is_detected() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| {
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say alpha", &[])])
+    })
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "alpha" }))
+    });
+
+    let kernel = kernel(&llm, 2).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Say it").await;
+
+    // the control query repeats the plan, gets feedback & repeats it again:
+    let planners = llm.planner_requests();
+    assert_eq!(planners.len(), 3);
+    assert!(
+        planners[2]
+            .last_user_text()
+            .starts_with("You planned the agent tasks that were already executed")
+    );
+    assert_eq!(llm.agent_tasks().len(), 1);
+    assert!(answer(&events).ends_with("the same tasks were planned repeatedly."));
+}
+
+#[tokio::test]
+async fn renumbered_repeated_plan_is_detected() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![
+            Reply::task(1, "fake-agent", "Say alpha", &[]),
+            Reply::task(2, "fake-agent", "Say beta", &[1]),
+        ]),
+    )
+    .always(Request::is_planner, |_| {
+        Reply::tools(vec![
+            Reply::task(7, "fake-agent", "Say alpha", &[]),
+            Reply::task(8, "fake-agent", "Say beta", &[7]),
+        ])
+    })
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "alpha" }))
+    });
+
+    let kernel = kernel(&llm, 2).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Say it").await;
+
+    // the same calls under the new task IDs are not executed again:
+    assert_eq!(llm.agent_tasks().len(), 2);
+    assert!(answer(&events).ends_with("the same tasks were planned repeatedly."));
+}
+
+#[tokio::test]
+async fn agent_tool_loop() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Find and echo", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent, |req| {
+        // the agent sees the previous tool result and calls the follow-up tool:
+        if req.contains("first-result") && req.contains("second-result") {
+            Reply::text("Echoed twice.")
+        } else if req.contains("first-result") {
+            Reply::tool("echo", json!({ "text": "second-result" }))
+        } else {
+            Reply::tool("echo", json!({ "text": "first-result" }))
+        }
+    });
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Echo it twice").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+
+    let agents = llm.agent_requests();
+    assert_eq!(agents.len(), 3);
+    assert_eq!(llm.agent_tasks().len(), 1);
+
+    // the tool results are fed back as the tool messages:
+    let steps = roles(&agents[2].messages());
+    assert_eq!(
+        steps,
+        ["system", "user", "assistant", "tool", "assistant", "tool"]
+    );
+
+    // the steps are streamed as task-scoped events:
+    let thinking = events
+        .iter()
+        .filter(|event| event.kind == EventKind::Thinking && event.task_info.is_some())
+        .map(|event| event.text.clone())
+        .collect::<Vec<_>>();
+    assert!(
+        thinking
+            .iter()
+            .any(|text| text == "Agent `fake-agent` step 2/8...")
+    );
+    assert!(
+        thinking
+            .iter()
+            .any(|text| text == "Agent `fake-agent` step 3/8...")
+    );
+
+    // all the steps results are passed to the control query:
+    let history = kernel.history(&sid).await;
+    assert_eq!(roles(&history), ["user", "assistant", "tool", "assistant"]);
+    let result = Request::message_text(&history[2]);
+    assert!(result.contains("first-result"));
+    assert!(result.contains("second-result"));
+    assert!(result.contains("Echoed twice."));
+}
+
+#[tokio::test]
+async fn agent_steps_are_bounded() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Echo forever", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "again" }))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "execution.max_agent_steps", 2)
+    })
+    .await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Never stop").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+    assert_eq!(llm.agent_requests().len(), 2);
+    assert!(answer(&events).ends_with("All done."));
+}
+
+#[tokio::test]
+async fn early_tasks_of_broken_stream_are_discarded() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say alpha", &[])]).broken(),
+    )
+    .once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say beta", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "beta-result" }))
+    });
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Say something").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+    assert!(answer(&events).ends_with("All done."));
+
+    // the task started by the broken stream never reaches the agent model:
+    let agents = llm.agent_tasks();
+    assert_eq!(agents.len(), 1);
+    assert!(agents[0].contains("Say beta"));
+    assert!(
+        !texts(&events, EventKind::Thinking)
+            .iter()
+            .any(|text| text.contains("Say alpha"))
+    );
+
+    let history = kernel.history(&sid).await;
+    assert_eq!(roles(&history), ["user", "assistant", "tool", "assistant"]);
+    assert_eq!(Request::message_text(&history[2]), "beta-result");
+}
+
+#[tokio::test]
+async fn early_task_is_restarted_by_plan_changes() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![
+            Reply::task(1, "fake-agent", "Say the number", &[]),
+            ("javascript_eval", json!({ "code": "6 * 7", "task_id": 1 })),
+        ]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "42" }))
+    });
+
+    let kernel = kernel(&llm, 3).await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Say 42").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+
+    // the task runs once, with the injected eval result:
+    let agents = llm.agent_tasks();
+    assert_eq!(agents.len(), 1);
+    assert!(agents[0].last_user_text().ends_with("Say the number\n42"));
+}
+
+#[tokio::test]
+async fn direct_tool_call_skips_agent_model() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tool("fake-agent__echo", json!({ "text": "direct-result" })),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."));
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(
+            settings,
+            "execution.direct_tools.fake-agent",
+            vec!["testing"],
+        )
+    })
+    .await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Echo directly").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+    assert!(answer(&events).ends_with("All done."));
+
+    // the namespaced agent tools are offered to the planner, and no agent model is called:
+    let planners = llm.planner_requests();
+    assert!(planners[0].has_tool("fake-agent__echo"));
+    assert!(planners[0].has_tool("handle_agent"));
+    assert!(llm.agent_requests().is_empty());
+
+    let history = kernel.history(&sid).await;
+    assert_eq!(roles(&history), ["user", "assistant", "tool", "assistant"]);
+    assert_eq!(Request::message_text(&history[2]), "direct-result");
+}
+
+#[tokio::test]
+async fn direct_tool_outside_skills_is_retried() {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tool("fake-agent__note", json!({ "text": "sneaky" })),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."));
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(
+            settings,
+            "execution.direct_tools.fake-agent",
+            vec!["testing"],
+        )
+    })
+    .await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Note directly").await;
+
+    assert!(answer(&events).ends_with("All done."));
+
+    // the tool of another skill is not executed, the planner is asked to retry:
+    let planners = llm.planner_requests();
+    assert!(!planners[0].has_tool("fake-agent__note"));
+    assert_eq!(planners.len(), 2);
+    assert!(planners[1].contains("`fake-agent__note` is not available"));
+
+    let history = kernel.history(&sid).await;
+    assert_eq!(roles(&history), ["user", "assistant"]);
+}
+
+#[tokio::test]
+async fn agent_skills_are_preselected() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |req| {
+        // the planner tries to delegate to the agent that is not offered:
+        let rejected = req.contains("is not available for this request");
+        let skipped = match req.contains("The fake agent for tests.") {
+            true => "other-agent",
+            false => "fake-agent",
+        };
+        match rejected {
+            true => Reply::text("Done."),
+            false => Reply::tools(vec![Reply::task(1, skipped, "Take a note", &[])]),
+        }
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "selection.top_k", 1);
+        set(settings, "selection.min_similarity", 0.0);
+    })
+    .await;
+    kernel.install_agent("other-agent").await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Take a note").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+    assert_eq!(answer(&events), "Done.");
+
+    // only the most relevant skill of one agent is listed, after the history:
+    let planner = &llm.planner_requests()[0];
+    let messages = planner.messages();
+    let prefix = Request::message_text(&messages[0]);
+    assert!(
+        prefix.contains("listed in the last system message"),
+        "{prefix}"
+    );
+    assert!(!prefix.contains("Available Agents"), "{prefix}");
+
+    let late = Request::message_text(&messages[messages.len() - 2]);
+    let agents = ["The fake agent for tests.", "The other agent for tests."]
+        .iter()
+        .filter(|agent| planner.contains(agent))
+        .count();
+    assert_eq!(agents, 1, "{late}");
+    let skills = ["`testing`", "`notes`"]
+        .iter()
+        .filter(|skill| late.contains(*skill))
+        .count();
+    assert_eq!(skills, 1, "{late}");
+    assert!(late.contains("must be one of: `"), "{late}");
+
+    // the delegation to the agent that is not offered is replanned:
+    assert_eq!(llm.planner_requests().len(), 2);
+    assert!(llm.agent_requests().is_empty());
+}
+
+#[tokio::test]
+async fn low_confidence_selection_falls_back() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| Reply::text("Done."));
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "selection.top_k", 1);
+        set(settings, "selection.min_similarity", 1.5);
+    })
+    .await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "Take a note").await;
+
+    // the full agents list is offered late without the names restriction:
+    let messages = llm.planner_requests()[0].messages();
+    let late = Request::message_text(&messages[messages.len() - 2]);
+    assert!(
+        late.contains("`testing`") && late.contains("`notes`"),
+        "{late}"
+    );
+    assert!(!late.contains("must be one of"));
+}
+
+#[tokio::test]
+async fn selection_keeps_prompt_prefix_cached() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| Reply::text("Done."));
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "selection.top_k", 1);
+        set(settings, "selection.min_similarity", 0.0);
+    })
+    .await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "Take a note").await;
+    kernel.query(&sid, "Echo the text").await;
+
+    // whatever skills are selected, the tools & the assistant prompt stay the same:
+    let planners = llm.planner_requests();
+    assert!(planners[0].system_text().contains("must be one of"));
+    assert_eq!(planners[1].0["tools"], planners[0].0["tools"]);
+    assert_eq!(planners[1].messages()[0], planners[0].messages()[0]);
+
+    // only the new turn is outside the shared prompt prefix:
+    let prompt = planners[1].prompt();
+    let costs = llm
+        .prompt_costs()
+        .into_iter()
+        .filter(|(req, _)| req.is_planner())
+        .map(|(_, cost)| cost)
+        .collect::<Vec<_>>();
+    assert!(costs[1] <= prompt.len() - prompt.find("Take a note").unwrap());
+}
+
+/// Starts the kernel with the system prompt time granularity
+async fn cache_kernel(llm: &MockLlm, time_granularity: i64) -> Kernel {
+    Kernel::start(llm, |settings| {
+        set(settings, "completions.time_granularity", time_granularity);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn prompt_prefix_is_cached_across_turns() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| Reply::text("Done."));
+
+    let kernel = cache_kernel(&llm, 1).await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "First question").await;
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    kernel.query(&sid, "Second question").await;
+
+    // the datetime differs, but the tools & the assistant prompt stay in the prefix:
+    let planners = llm.planner_requests();
+    assert_ne!(planners[0].system_text(), planners[1].system_text());
+    assert_eq!(planners[1].messages()[0], planners[0].messages()[0]);
+
+    // only the new turn is outside the shared prompt prefix:
+    let prompt = planners[1].prompt();
+    let costs = llm
+        .prompt_costs()
+        .into_iter()
+        .filter(|(req, _)| req.is_planner())
+        .map(|(_, cost)| cost)
+        .collect::<Vec<_>>();
+    let cost = costs[1];
+    assert!(cost <= prompt.len() - prompt.find("First question").unwrap());
+    assert!(cost * 5 < prompt.len(), "{cost} of {}", prompt.len());
+}
+
+/// Returns the agent tasks prompt sizes outside the shared prefix of two turns
+async fn agent_task_costs(time_granularity: i64) -> Vec<usize> {
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say alpha", &[])]),
+    )
+    .once(Request::is_planner, Reply::text("Done."))
+    .once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say beta", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("Done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("echo", json!({ "text": "ok" }))
+    });
+
+    let kernel = cache_kernel(&llm, time_granularity).await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "Run alpha").await;
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    kernel.query(&sid, "Run beta").await;
+
+    llm.prompt_costs()
+        .into_iter()
+        .filter(|(req, _)| req.is_agent() && !req.is_agent_step())
+        .map(|(_, cost)| cost)
+        .collect()
+}
+
+#[tokio::test]
+async fn time_granularity_keeps_agent_prompts_cached() {
+    let coarse = agent_task_costs(86_400).await;
+    let precise = agent_task_costs(1).await;
+
+    // the second task differs by the task query only, unless the datetime changed:
+    assert!(coarse[1] < 100, "{coarse:?}");
+    assert!(coarse[1] * 2 < precise[1], "{coarse:?} vs {precise:?}");
+}
+
+/// Starts the kernel with the answers cache of the lifetime
+async fn answers_cache_kernel(llm: &MockLlm, ttl: i64) -> Kernel {
+    let answers = std::sync::atomic::AtomicUsize::new(0);
+    llm.always(Request::is_planner, move |_| {
+        let n = answers.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        Reply::text(format!("Answer {n}."))
+    });
+
+    Kernel::start(llm, |settings| {
+        set(settings, "cache.enable", true);
+        set(settings, "cache.ttl", ttl);
+        set(settings, "sessions.generate_titles", false);
+    })
+    .await
+}
+
+#[tokio::test]
+async fn opening_answers_are_cached() {
+    let llm = MockLlm::start().await;
+    let kernel = answers_cache_kernel(&llm, 0).await;
+
+    let first = kernel.session().await;
+    assert_eq!(
+        answer(&kernel.query(&first, "What is Ovsy?").await),
+        "Answer 1."
+    );
+
+    // the same opening query of another session is replayed:
+    let second = kernel.session().await;
+    assert_eq!(
+        answer(&kernel.query(&second, "What is Ovsy?").await),
+        "Answer 1."
+    );
+    assert_eq!(llm.planner_requests().len(), 1);
+    assert_eq!(roles(&kernel.history(&second).await), ["user", "assistant"]);
+
+    // the query after the history depends on it, so it isn't replayed:
+    assert_eq!(
+        answer(&kernel.query(&second, "What is Ovsy?").await),
+        "Answer 2."
+    );
+    assert_eq!(
+        answer(&kernel.query(&first, "Tell more").await),
+        "Answer 3."
+    );
+
+    let third = kernel.session().await;
+    assert_eq!(
+        answer(&kernel.query(&third, "Tell more").await),
+        "Answer 4."
+    );
+    assert_eq!(llm.planner_requests().len(), 4);
+}
+
+#[tokio::test]
+async fn expired_answers_are_not_replayed() {
+    let llm = MockLlm::start().await;
+    let kernel = answers_cache_kernel(&llm, 1).await;
+
+    let first = kernel.session().await;
+    assert_eq!(
+        answer(&kernel.query(&first, "What is Ovsy?").await),
+        "Answer 1."
+    );
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+
+    let second = kernel.session().await;
+    assert_eq!(
+        answer(&kernel.query(&second, "What is Ovsy?").await),
+        "Answer 2."
+    );
+    assert_eq!(llm.planner_requests().len(), 2);
+}
@@ -0,0 +1,977 @@
+//! The sessions tests: the session history management over the kernel API.
+
+mod common;
+
+use common::{Kernel, MockLlm, Reply, Request, answer, roles, set, texts};
+use ovsy_share::{EventKind, HistoryHit, SessionSummary, SessionsSort, UserSessionsQuery};
+use serde_json::{Value as JsonValue, json};
+
+/// Returns the parsed compaction events
+fn compactions(events: &[ovsy_share::Event]) -> Vec<JsonValue> {
+    texts(events, EventKind::Compacted)
+        .iter()
+        .map(|text| serde_json::from_str(text).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn long_tool_outputs_are_trimmed() {
+    let long_text = "lorem ipsum ".repeat(2_000);
+
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say it all", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, move |_| {
+        Reply::tool("echo", json!({ "text": long_text }))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "compression.context_length", 3_000);
+        set(settings, "compression.threshold", 1.0);
+        set(settings, "compression.max_tool_tokens", 50);
+    })
+    .await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Say it all").await;
+
+    assert!(texts(&events, EventKind::Error).is_empty(), "{events:?}");
+    assert!(answer(&events).ends_with("All done."));
+
+    // the control query gets the trimmed output:
+    let compacted = compactions(&events);
+    assert_eq!(compacted.len(), 1, "{compacted:?}");
+    assert_eq!(compacted[0]["mode"], "trim");
+    assert!(compacted[0]["tokens_after"].as_u64() < compacted[0]["tokens_before"].as_u64());
+
+    let control = llm.planner_requests().pop().unwrap();
+    assert!(control.contains("tokens of the output trimmed"));
+    assert!(llm.requests().iter().all(|req| !req.is_compaction()));
+}
+
+#[tokio::test]
+async fn trimmed_outputs_are_not_trimmed_again() {
+    let long_text = "lorem ipsum ".repeat(2_000);
+
+    let llm = MockLlm::start().await;
+    llm.once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Say it all", &[])]),
+    )
+    .once(
+        Request::is_planner,
+        Reply::tools(vec![Reply::task(2, "fake-agent", "Say short", &[])]),
+    )
+    .always(Request::is_planner, |_| Reply::text("All done."))
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, move |req| {
+        match req.contains("Say short") {
+            true => Reply::tool("echo", json!({ "text": "short" })),
+            false => Reply::tool("echo", json!({ "text": long_text })),
+        }
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "completions.assist_prompt", "You are Ovsy.");
+        set(settings, "completions.system_prompt", "Be brief.");
+        set(settings, "compression.context_length", 60);
+        set(settings, "compression.threshold", 1.0);
+        set(settings, "compression.max_tool_tokens", 50);
+    })
+    .await;
+    let sid = kernel.session().await;
+    let events = kernel.query(&sid, "Say it all").await;
+
+    // the context still overflows on the next cycle, but the output is trimmed once:
+    assert!(answer(&events).ends_with("All done."), "{events:?}");
+    let compacted = compactions(&events);
+    assert_eq!(compacted.len(), 1, "{compacted:?}");
+    assert_eq!(compacted[0]["mode"], "trim");
+    assert_eq!(llm.planner_requests().len(), 3);
+}
+
+#[tokio::test]
+async fn overflowed_history_is_compacted() {
+    let long_answer = "lorem ipsum ".repeat(300);
+
+    let llm = MockLlm::start().await;
+    llm.once(Request::is_planner, Reply::text(long_answer.as_str()))
+        .always(Request::is_compaction, |_| {
+            Reply::text("Summary of the talk.")
+        })
+        .always(Request::is_planner, |_| Reply::text("Short answer."));
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "completions.assist_prompt", "You are Ovsy.");
+        set(
+            settings,
+            "completions.system_prompt",
+            "Now: {DATETIME_GLOBAL}",
+        );
+        set(settings, "compression.context_length", 300);
+        set(settings, "compression.threshold", 1.0);
+        set(settings, "execution.preserve_messages", 0);
+    })
+    .await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "First question").await;
+    let events = kernel.query(&sid, "Second question").await;
+
+    // the history is summarized before the completion, keeping the turn being handled:
+    assert_eq!(answer(&events), "Short answer.");
+    let compacted = compactions(&events);
+    assert_eq!(compacted.len(), 1, "{compacted:?}");
+    assert_eq!(compacted[0]["mode"], "summary");
+
+    let planner = llm.planner_requests().pop().unwrap();
+    assert!(planner.contains("Summary of the talk."));
+    assert!(!planner.contains("lorem ipsum"));
+    assert_eq!(planner.last_user_text(), "Second question");
+
+    let history = kernel.history(&sid).await;
+    assert_eq!(roles(&history), ["assistant", "user", "assistant"]);
+    assert_eq!(Request::message_text(&history[0]), "Summary of the talk.");
+    assert_eq!(Request::message_text(&history[1]), "Second question");
+
+    // the next turn is planned with the summary:
+    kernel.query(&sid, "Third question").await;
+    let planner = llm.planner_requests().pop().unwrap();
+    assert!(planner.contains("Summary of the talk."));
+    assert!(!planner.contains("lorem ipsum"));
+}
+
+#[tokio::test]
+async fn looked_up_history_keeps_the_window() {
+    let long_note = format!("The zephyr notes: {}", "lorem ipsum ".repeat(300));
+
+    let llm = MockLlm::start().await;
+    llm.once(
+        |req| req.is_planner() && req.last_user_text() == "Find the zephyr notes",
+        Reply::tool("search_history", json!({ "query": "zephyr notes" })),
+    )
+    .always(Request::is_planner, |req| {
+        match req.messages().iter().any(|msg| msg["role"] == "tool") {
+            true => Reply::text("Found them."),
+            false => Reply::text("Noted."),
+        }
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "completions.assist_prompt", "You are Ovsy.");
+        set(settings, "completions.system_prompt", "Be brief.");
+        set(settings, "sessions.generate_titles", false);
+        set(settings, "compression.context_length", 1_000);
+        set(settings, "compression.threshold", 1.0);
+        set(settings, "compression.max_tool_tokens", 50);
+        set(settings, "history.snippet_length", 10_000);
+    })
+    .await;
+
+    // every session fits the window alone:
+    for _ in 0..2 {
+        let old = kernel.session().await;
+        let events = kernel.query(&old, &long_note).await;
+        assert!(compactions(&events).is_empty(), "{events:?}");
+        kernel.finish(&old).await;
+    }
+
+    // the found messages overflow the window before the next planner completion:
+    let new = kernel.session().await;
+    let events = kernel.query(&new, "Find the zephyr notes").await;
+    assert_eq!(answer(&events), "Found them.");
+
+    let compacted = compactions(&events);
+    assert_eq!(compacted.len(), 1, "{compacted:?}");
+    assert_eq!(compacted[0]["mode"], "trim");
+
+    let planner = llm.planner_requests().pop().unwrap();
+    assert!(planner.contains("tokens of the output trimmed"));
+}
+
+/// Waits for the sessions list to satisfy the condition
+async fn wait_sessions<F>(
+    kernel: &Kernel,
+    query: &UserSessionsQuery,
+    check: F,
+) -> Vec<SessionSummary>
+where
+    F: Fn(&[SessionSummary]) -> bool,
+{
+    for _ in 0..50 {
+        let sessions = kernel.sessions(query).await;
+        if check(&sessions) {
+            return sessions;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("{:?}", kernel.sessions(query).await);
+}
+
+#[tokio::test]
+async fn sessions_are_listed_by_activity() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_title, |req| {
+        if req.contains("Paris") {
+            Reply::text("\"Paris weather\"")
+        } else {
+            Reply::Empty
+        }
+    })
+    .always(Request::is_planner, |_| Reply::text("Sure."));
+
+    let kernel = Kernel::start(&llm, |_| {}).await;
+    let first = kernel.session().await;
+    kernel.query(&first, "What is the weather in Paris?").await;
+    kernel.finish(&first).await;
+
+    let second = kernel.session().await;
+    kernel
+        .query(&second, "Tell me a long story about\nthe sea")
+        .await;
+    kernel.finish(&second).await;
+
+    // the first session is resumed:
+    kernel.history(&first).await;
+    kernel.query(&first, "And tomorrow?").await;
+
+    // the titles are generated by the model or cut from the first message:
+    let query = UserSessionsQuery::new(0);
+    let sessions = wait_sessions(&kernel, &query, |sessions| {
+        sessions
+            .iter()
+            .any(|s| s.title.as_deref() == Some("Paris weather"))
+    })
+    .await;
+
+    let ids = sessions.iter().map(|s| s.id).collect::<Vec<_>>();
+    assert_eq!(ids, [first, second]);
+    assert_eq!(
+        sessions[1].title.as_deref(),
+        Some("Tell me a long story about")
+    );
+    assert_eq!(sessions[0].message_count, 4);
+    assert_eq!(sessions[1].message_count, 2);
+    assert!(sessions[0].token_count > sessions[1].token_count);
+    assert!(sessions[0].last_activity > sessions[1].last_activity);
+
+    // sorting by creation & pagination:
+    let created = kernel
+        .sessions(&UserSessionsQuery::new(0).sort(SessionsSort::Created))
+        .await;
+    assert_eq!(
+        created.iter().map(|s| s.id).collect::<Vec<_>>(),
+        [second, first]
+    );
+
+    let page = kernel.sessions(&UserSessionsQuery::new(1).offset(1)).await;
+    assert_eq!(page.iter().map(|s| s.id).collect::<Vec<_>>(), [second]);
+}
+
+#[tokio::test]
+async fn sessions_are_exported_and_imported() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_compaction, |_| {
+        Reply::text("Summary of the talk.")
+    })
+    .always(Request::is_planner, |req| {
+        Reply::text(format!("Answer to {}", req.last_user_text()))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+    })
+    .await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "First question").await;
+    kernel
+        .post(
+            &format!("/sessions/{sid}/compact"),
+            &json!({ "preserve": 0 }),
+        )
+        .await
+        .text()
+        .await
+        .unwrap();
+    kernel.query(&sid, "Second question").await;
+
+    // the archive keeps the compressed originals:
+    let export: JsonValue = kernel
+        .get(&format!("/sessions/{sid}/export?format=json"))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(export["title"], "First question");
+    assert_eq!(export["compressed_until"], 2);
+    assert_eq!(export["turns"], json!([0, 2, 3]));
+    let messages = export["messages"].as_array().unwrap();
+    assert_eq!(
+        roles(messages),
+        ["user", "assistant", "assistant", "user", "assistant"]
+    );
+    assert_eq!(Request::message_text(&messages[2]), "Summary of the talk.");
+
+    let markdown = kernel
+        .get(&format!("/sessions/{sid}/export?format=markdown"))
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(markdown.starts_with("# First question\n"));
+    assert!(markdown.contains("compressed into the summary below"));
+    assert!(markdown.contains("Answer to Second question"));
+
+    // the imported copy has the same history (with the tool calls):
+    let mut archive = export.clone();
+    let messages = archive["messages"].as_array_mut().unwrap();
+    messages.push(json!({
+        "role": "assistant",
+        "content": [],
+        "tool_calls": [{
+            "id": "call_1",
+            "type": "function",
+            "function": { "name": "echo", "arguments": "{\"text\":\"hi\"}" },
+        }],
+    }));
+    messages.push(json!({
+        "role": "tool",
+        "content": [{ "type": "text", "text": "hi" }],
+        "tool_call_id": "call_1",
+    }));
+
+    let imported: SessionSummary = kernel
+        .post("/users/1/sessions/import", &archive)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_ne!(imported.id, sid);
+    assert_eq!(imported.title.as_deref(), Some("First question"));
+    assert_eq!(imported.message_count, 5);
+
+    let copy: JsonValue = kernel
+        .get(&format!("/sessions/{}/export", imported.id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(copy["compressed_until"], 2);
+    assert_eq!(copy["turns"], export["turns"]);
+    assert_eq!(
+        copy["messages"][5]["tool_calls"][0]["function"]["name"],
+        "echo"
+    );
+    assert_eq!(copy["messages"][6]["tool_call_id"], "call_1");
+
+    let history = kernel.history(&imported.id).await;
+    assert_eq!(
+        roles(&history),
+        ["assistant", "user", "assistant", "assistant", "tool"]
+    );
+
+    // the broken archive is rejected:
+    let mut broken = export.clone();
+    broken["compressed_until"] = json!(10);
+    let res = kernel.post("/users/1/sessions/import", &broken).await;
+    assert_eq!(res.status(), 400);
+}
+
+#[tokio::test]
+async fn sessions_are_exported_and_imported_by_cli() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| Reply::text("Hello there."));
+
+    let kernel = Kernel::start(&llm, |_| {}).await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "Hi!").await;
+
+    let sid = sid.to_string();
+    let markdown = kernel
+        .cli(&["session", "export", &sid, "--format", "markdown"])
+        .await;
+    assert!(markdown.status.success());
+    assert!(String::from_utf8_lossy(&markdown.stdout).contains("Hello there."));
+
+    let file = kernel.root.join("session.json");
+    let file = file.to_str().unwrap();
+    let export = kernel.cli(&["session", "export", &sid, "-o", file]).await;
+    assert!(export.status.success());
+
+    let import = kernel.cli(&["session", "import", file]).await;
+    assert!(import.status.success());
+    assert!(String::from_utf8_lossy(&import.stdout).contains("Session imported."));
+
+    // the unknown session isn't exported:
+    let unknown = ovsy_share::SessionId::new(1).to_string();
+    let missing = kernel.cli(&["session", "export", &unknown]).await;
+    assert!(!missing.status.success());
+}
+
+#[tokio::test]
+async fn running_query_is_not_regenerated() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |req| {
+        match req.last_user_text().as_str() {
+            "First question" => Reply::text("First answer"),
+            "Sleep" => Reply::tools(vec![Reply::task(1, "fake-agent", "Sleep a bit", &[])]),
+            _ => Reply::text("Slept."),
+        }
+    })
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("sleep", json!({ "ms": 1500 }))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+    })
+    .await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "First question").await;
+
+    let (events, regenerated) = tokio::join!(kernel.query(&sid, "Sleep"), async {
+        // wait for the tool call to start:
+        while llm.agent_requests().is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        kernel
+            .stream(&format!("/sessions/{sid}/regenerate"), &json!({}))
+            .await
+    });
+
+    // the running query keeps its turn:
+    assert!(answer(&events).ends_with("Slept."), "{events:?}");
+    let errors = texts(&regenerated, EventKind::Error);
+    assert_eq!(errors.len(), 1, "{regenerated:?}");
+    assert!(errors[0].contains("query is in progress"));
+
+    let history = kernel.history(&sid).await;
+    assert_eq!(Request::message_text(&history[0]), "First question");
+    assert_eq!(Request::message_text(&history[2]), "Sleep");
+}
+
+#[tokio::test]
+async fn answers_are_regenerated_and_sessions_forked() {
+    let counter = std::sync::atomic::AtomicUsize::new(0);
+
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, move |req| {
+        let n = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        Reply::text(format!("Answer {n} to {}", req.last_user_text()))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+    })
+    .await;
+    let sid = kernel.session().await;
+    kernel.query(&sid, "First question").await;
+    kernel.query(&sid, "Second question").await;
+
+    // the last answer is replaced:
+    let events = kernel
+        .stream(&format!("/sessions/{sid}/regenerate"), &json!({}))
+        .await;
+    assert_eq!(answer(&events), "Answer 3 to Second question");
+
+    let history = kernel.history(&sid).await;
+    assert_eq!(roles(&history), ["user", "assistant", "user", "assistant"]);
+    assert_eq!(
+        Request::message_text(&history[3]),
+        "Answer 3 to Second question"
+    );
+
+    // the edited prompt is answered instead:
+    let edited = json!({ "message": { "role": "user", "content": [{ "type": "text", "text": "Edited question" }] } });
+    let events = kernel
+        .stream(&format!("/sessions/{sid}/regenerate"), &edited)
+        .await;
+    assert_eq!(answer(&events), "Answer 4 to Edited question");
+
+    let history = kernel.history(&sid).await;
+    assert_eq!(history.len(), 4);
+    assert_eq!(Request::message_text(&history[2]), "Edited question");
+
+    // the fork copies the history before the index:
+    let forked: SessionSummary = kernel
+        .post(&format!("/sessions/{sid}/fork?at=2"), &json!(null))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_ne!(forked.id, sid);
+    assert_eq!(forked.message_count, 2);
+
+    let fork_history = kernel.history(&forked.id).await;
+    assert_eq!(roles(&fork_history), ["user", "assistant"]);
+    assert_eq!(
+        Request::message_text(&fork_history[1]),
+        "Answer 1 to First question"
+    );
+    assert_eq!(kernel.history(&sid).await.len(), 4);
+
+    let res = kernel
+        .post(&format!("/sessions/{sid}/fork?at=10"), &json!(null))
+        .await;
+    assert_eq!(res.status(), 400);
+
+    // the fork doesn't split a turn:
+    let res = kernel
+        .post(&format!("/sessions/{sid}/fork?at=1"), &json!(null))
+        .await;
+    assert_eq!(res.status(), 400);
+    assert!(
+        res.text()
+            .await
+            .unwrap()
+            .contains("inside a conversation turn")
+    );
+
+    // the empty session has nothing to regenerate:
+    let empty = kernel.session().await;
+    let events = kernel
+        .stream(&format!("/sessions/{empty}/regenerate"), &json!({}))
+        .await;
+    assert!(texts(&events, EventKind::Error)[0].contains("no turn to regenerate"));
+}
+
+/// Returns the session ids of the list
+fn ids(sessions: &[SessionSummary]) -> Vec<ovsy_share::SessionId> {
+    sessions.iter().map(|s| s.id).collect()
+}
+
+#[tokio::test]
+async fn sessions_are_deleted_and_pruned() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| Reply::text("Sure."));
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+        set(settings, "retention.max_sessions", 1);
+        set(settings, "retention.max_age", 0);
+        set(settings, "retention.max_disk_usage", 0);
+        set(settings, "retention.gc_interval", 0);
+    })
+    .await;
+
+    let mut sids = vec![];
+    for text in ["First", "Second", "Third"] {
+        let sid = kernel.session().await;
+        kernel.query(&sid, text).await;
+        kernel.finish(&sid).await;
+        sids.push(sid);
+    }
+    let [first, second, third] = sids[..] else {
+        unreachable!()
+    };
+
+    let all = UserSessionsQuery::new(0);
+    assert_eq!(ids(&kernel.sessions(&all).await), [third, second, first]);
+
+    let prune_plan = async || -> Vec<JsonValue> {
+        kernel
+            .post("/sessions/prune?dry_run=true", &json!(null))
+            .await
+            .json()
+            .await
+            .unwrap()
+    };
+    let planned = prune_plan().await;
+    assert_eq!(planned.len(), 2, "{planned:?}");
+    assert!(planned.iter().all(|s| s["reason"] == "count"));
+    assert_eq!(planned[0]["id"], json!(second));
+    assert_eq!(planned[1]["id"], json!(first));
+
+    let dry_run = kernel.cli(&["session", "prune", "--dry-run"]).await;
+    assert!(dry_run.status.success());
+    let stdout = String::from_utf8_lossy(&dry_run.stdout);
+    assert!(stdout.contains("2 sessions"), "{stdout}");
+    assert!(stdout.contains("would be removed"), "{stdout}");
+    assert_eq!(kernel.sessions(&all).await.len(), 3);
+
+    // the active session is never pruned:
+    kernel.history(&first).await;
+    let planned = prune_plan().await;
+    assert_eq!(planned.len(), 1, "{planned:?}");
+    assert_eq!(planned[0]["id"], json!(second));
+
+    // the session is deleted with its database:
+    let res = kernel.delete(&format!("/sessions/{first}")).await;
+    assert!(res.status().is_success());
+    assert_eq!(ids(&kernel.sessions(&all).await), [third, second]);
+    let res = kernel.delete(&format!("/sessions/{first}")).await;
+    assert_eq!(res.status(), 404);
+
+    let pruned: JsonValue = kernel
+        .post("/sessions/prune", &json!(null))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(pruned[0]["id"], json!(second));
+    assert_eq!(ids(&kernel.sessions(&all).await), [third]);
+}
+
+#[tokio::test]
+async fn session_deleted_mid_query_stays_deleted() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |_| {
+        Reply::tools(vec![Reply::task(1, "fake-agent", "Sleep long", &[])])
+    })
+    .always(Request::is_agent_step, |_| Reply::Empty)
+    .always(Request::is_agent, |_| {
+        Reply::tool("sleep", json!({ "ms": 20_000 }))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+    })
+    .await;
+    let sid = kernel.session().await;
+
+    let (events, deleted) = tokio::join!(kernel.query(&sid, "Sleep"), async {
+        while llm.agent_requests().is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        kernel.delete(&format!("/sessions/{sid}")).await
+    });
+
+    // the query is cancelled, its turn isn't saved into the re-created database:
+    assert!(deleted.status().is_success());
+    assert!(
+        events
+            .iter()
+            .any(|event| event.kind == EventKind::Cancelled),
+        "{events:?}"
+    );
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    let all = UserSessionsQuery::new(0);
+    assert!(!ids(&kernel.sessions(&all).await).contains(&sid));
+    let res = kernel.delete(&format!("/sessions/{sid}")).await;
+    assert_eq!(res.status(), 404);
+}
+
+#[tokio::test]
+async fn sessions_are_collected_in_background() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |req| {
+        Reply::text(format!("Answer to {}", req.last_user_text()))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+        set(settings, "retention.max_sessions", 1);
+        set(settings, "retention.gc_interval", 1);
+    })
+    .await;
+
+    let old = kernel.session().await;
+    kernel.query(&old, "Old question").await;
+    kernel.finish(&old).await;
+
+    let new = kernel.session().await;
+    kernel.query(&new, "New question").await;
+    kernel.query(&new, "Next question").await;
+    kernel.finish(&new).await;
+
+    // the oldest session is removed, the rest database is rewritten:
+    wait_sessions(&kernel, &UserSessionsQuery::new(0), |sessions| {
+        ids(sessions) == [new]
+    })
+    .await;
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+
+    let history = kernel.history(&new).await;
+    assert_eq!(roles(&history), ["user", "assistant", "user", "assistant"]);
+    assert_eq!(
+        Request::message_text(&history[3]),
+        "Answer to Next question"
+    );
+
+    let sessions_dir = kernel.root.join("data");
+    let leftovers = walk(&sessions_dir)
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "compact"))
+        .collect::<Vec<_>>();
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+}
+
+#[tokio::test]
+async fn interrupted_compactions_are_recovered() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |req| {
+        Reply::text(format!("Answer to {}", req.last_user_text()))
+    });
+
+    let mut kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+        set(settings, "retention.gc_interval", 0);
+    })
+    .await;
+
+    let moved = kernel.session().await;
+    kernel.query(&moved, "Moved question").await;
+    kernel.finish(&moved).await;
+    let swapped = kernel.session().await;
+    kernel.query(&swapped, "Swapped question").await;
+    kernel.finish(&swapped).await;
+
+    // the crashes before the fresh database was moved in & before the old one was removed:
+    kernel
+        .restart(|root| {
+            for dir in walk(&root.join("data")) {
+                if dir.ends_with(moved.to_string()) {
+                    std::fs::rename(&dir, dir.with_extension("old")).unwrap();
+                    std::fs::create_dir_all(dir.with_extension("compact")).unwrap();
+                } else if dir.ends_with(swapped.to_string()) {
+                    std::fs::create_dir_all(dir.with_extension("old")).unwrap();
+                }
+            }
+        })
+        .await;
+
+    for (sid, text) in [(moved, "Moved question"), (swapped, "Swapped question")] {
+        let history = kernel.history(&sid).await;
+        assert_eq!(roles(&history), ["user", "assistant"]);
+        assert_eq!(Request::message_text(&history[0]), text);
+    }
+
+    let leftovers = walk(&kernel.root.join("data"))
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == "compact" || ext == "old")
+        })
+        .collect::<Vec<_>>();
+    assert!(leftovers.is_empty(), "{leftovers:?}");
+}
+
+/// Returns all the nested directories
+fn walk(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut dirs = vec![];
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        if entry.path().is_dir() {
+            dirs.push(entry.path());
+            dirs.extend(walk(&entry.path()));
+        }
+    }
+    dirs
+}
+
+/// Returns the number of the sessions open in memory
+async fn open_sessions(kernel: &Kernel) -> u64 {
+    let status: JsonValue = kernel.get("/status").await.json().await.unwrap();
+    status["open_sessions"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn idle_sessions_are_evicted_and_reopened() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |req| {
+        Reply::text(format!("Answer to {}", req.last_user_text()))
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(
+            settings,
+            "completions.assist_prompt",
+            "Path: {CURRENT_PATH}",
+        );
+        set(settings, "sessions.generate_titles", false);
+        set(settings, "sessions.idle_timeout", 1);
+    })
+    .await;
+
+    let sid = ovsy_share::SessionId::new(1);
+    let info = json!({ "current_path": "/home/user/project", "timezone": 0 });
+    let res = kernel.post(&format!("/sessions/{sid}/init"), &info).await;
+    assert!(res.status().is_success());
+    kernel.query(&sid, "First question").await;
+    assert_eq!(open_sessions(&kernel).await, 1);
+
+    // the idle session is closed without the client finishing it:
+    let started = std::time::Instant::now();
+    while open_sessions(&kernel).await > 0 {
+        assert!(
+            started.elapsed().as_secs() < 15,
+            "The session wasn't evicted"
+        );
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+
+    // the next query re-opens it with the same client info & history:
+    let events = kernel.query(&sid, "Second question").await;
+    assert_eq!(answer(&events), "Answer to Second question");
+    assert_eq!(open_sessions(&kernel).await, 1);
+
+    let last = llm.planner_requests().pop().unwrap();
+    assert!(last.system_text().contains("/home/user/project"));
+    let history = roles(&last.messages())
+        .into_iter()
+        .filter(|role| role != "system")
+        .collect::<Vec<_>>();
+    assert_eq!(history, ["user", "assistant", "user"]);
+}
+
+/// Searches the user conversations history
+async fn search(kernel: &Kernel, query: &str) -> Vec<HistoryHit> {
+    let url =
+        reqwest::Url::parse_with_params(&format!("{}/users/1/search", kernel.url), [("q", query)])
+            .unwrap();
+
+    let res = reqwest::get(url).await.unwrap();
+    assert!(res.status().is_success());
+    res.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn history_is_searched_across_sessions() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_compaction, |_| Reply::text("Summary."))
+        .always(Request::is_planner, |req| {
+            Reply::text(format!("Answer to {}", req.last_user_text()))
+        });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+    })
+    .await;
+
+    let old = kernel.session().await;
+    kernel
+        .query(&old, "Where is the Zephyr deploy config?")
+        .await;
+    kernel.finish(&old).await;
+
+    let new = kernel.session().await;
+    kernel.query(&new, "How to cook a banana?").await;
+
+    // the compressed messages are searched too:
+    kernel
+        .stream(
+            &format!("/sessions/{new}/compact"),
+            &json!({ "preserve": 0 }),
+        )
+        .await;
+
+    let hits = search(&kernel, "zephyr config").await;
+    assert!(hits.len() >= 2, "{hits:?}");
+    for hit in &hits[..2] {
+        assert_eq!(hit.session_id, old);
+        assert!(hit.text.contains("Zephyr deploy config"), "{hit:?}");
+    }
+    assert_eq!(
+        hits[..2]
+            .iter()
+            .map(|hit| hit.role.as_str())
+            .collect::<Vec<_>>(),
+        ["assistant", "user"]
+    );
+
+    let hits = search(&kernel, "banana").await;
+    assert_eq!(hits[0].session_id, new);
+    assert!(hits[0].compressed, "{:?}", hits[0]);
+
+    // the removed session messages aren't found:
+    assert!(
+        kernel
+            .delete(&format!("/sessions/{old}"))
+            .await
+            .status()
+            .is_success()
+    );
+    let hits = search(&kernel, "zephyr config").await;
+    assert!(hits.iter().all(|hit| hit.session_id != old), "{hits:?}");
+}
+
+#[tokio::test]
+async fn sessions_written_before_the_store_are_indexed() {
+    let llm = MockLlm::start().await;
+    llm.always(Request::is_planner, |req| {
+        Reply::text(format!("Answer to {}", req.last_user_text()))
+    });
+
+    let mut kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+    })
+    .await;
+
+    let first = kernel.session().await;
+    kernel.query(&first, "Where is the Zephyr config?").await;
+    kernel.finish(&first).await;
+    let second = kernel.session().await;
+    kernel.query(&second, "How to cook a banana?").await;
+    kernel.finish(&second).await;
+
+    // the user store is missing (as written by the older kernel):
+    kernel
+        .restart(|root| {
+            let store = walk(&root.join("data"))
+                .into_iter()
+                .find(|dir| dir.ends_with("1/store"))
+                .unwrap();
+            std::fs::remove_dir_all(store).unwrap();
+        })
+        .await;
+
+    // the summaries & the search index are rebuilt from the session databases:
+    let sessions = kernel.sessions(&UserSessionsQuery::new(0)).await;
+    assert_eq!(ids(&sessions), [second, first]);
+    assert_eq!(
+        sessions[1].title.as_deref(),
+        Some("Where is the Zephyr config?")
+    );
+    assert_eq!(sessions[1].message_count, 2);
+
+    let hits = search(&kernel, "zephyr").await;
+    assert_eq!(hits.len(), 2, "{hits:?}");
+    assert!(hits.iter().all(|hit| hit.session_id == first));
+}
+
+#[tokio::test]
+async fn past_conversations_are_searched_by_planner() {
+    let question = "When is the zephyr release?";
+
+    let llm = MockLlm::start().await;
+    llm.once(
+        move |req| req.is_planner() && req.last_user_text() == question,
+        Reply::tool("search_history", json!({ "query": "zephyr release" })),
+    )
+    .always(Request::is_planner, |req| {
+        let found = req
+            .messages()
+            .iter()
+            .any(|msg| msg["role"] == "tool" && Request::message_text(msg).contains("Friday"));
+        match found {
+            true => Reply::text("The release is on Friday."),
+            false => Reply::text(format!("Answer to {}", req.last_user_text())),
+        }
+    });
+
+    let kernel = Kernel::start(&llm, |settings| {
+        set(settings, "sessions.generate_titles", false);
+    })
+    .await;
+
+    let old = kernel.session().await;
+    kernel
+        .query(&old, "The zephyr release is planned for Friday")
+        .await;
+    kernel.finish(&old).await;
+
+    // the found messages are fed back to the planner before answering:
+    let new = kernel.session().await;
+    let events = kernel.query(&new, question).await;
+    assert_eq!(answer(&events), "The release is on Friday.");
+
+    let history = kernel.history(&new).await;
+    assert_eq!(roles(&history), ["user", "assistant", "tool", "assistant"]);
+    assert!(
+        Request::message_text(&history[2]).contains("The zephyr release is planned for Friday")
+    );
+}
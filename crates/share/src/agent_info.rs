@@ -0,0 +1,15 @@
+use crate::{AgentStatus, Skill};
+use anylm::api::Tool;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The agent details with the cached tools schemas
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct AgentInfo {
+    #[serde(flatten)]
+    pub status: AgentStatus,
+    pub skills: Vec<Skill>,
+    /// The worker tools schemas cached by the kernel (by skill name, empty if dormant)
+    #[serde(default)]
+    pub tools: HashMap<String, Vec<Tool>>,
+}
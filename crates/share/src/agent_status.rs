@@ -0,0 +1,27 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The agent worker status
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStatus {
+    pub name: String,
+    pub description: String,
+    pub version: String,
+    /// True if the agent worker process is running
+    pub resident: bool,
+    /// Seconds since the last agent use (if it was ever used)
+    pub idle_secs: Option<u64>,
+    /// True if the agent crashed too often and is excluded from planning
+    #[serde(default)]
+    pub quarantined: bool,
+    /// The recent agent crashes (oldest first)
+    #[serde(default)]
+    pub crashes: Vec<AgentCrash>,
+}
+
+/// The agent worker crash record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentCrash {
+    pub time: DateTime<Utc>,
+    pub reason: String,
+}
@@ -9,6 +9,9 @@ pub enum EventKind {
     Answer,
     Error,
     Finish,
+    Cancelled,
+    Cycle,
+    Compacted,
 }
 
 /// The event task info
@@ -81,6 +84,32 @@ impl Event {
         Self::new(EventKind::Finish, "")
     }
 
+    /// Creates a final chunk of the cancelled query
+    pub fn cancelled() -> Self {
+        Self::new(EventKind::Cancelled, "")
+    }
+
+    /// Creates a planning/control cycle event (max_cycles 0 = unlimited)
+    pub fn cycle(cycle: usize, max_cycles: usize) -> Self {
+        Self::new(
+            EventKind::Cycle,
+            serde_json::json!({ "cycle": cycle, "max_cycles": max_cycles }).to_string(),
+        )
+    }
+
+    /// Creates a context compaction event (mode is `trim` or `summary`)
+    pub fn compacted(mode: &str, tokens_before: usize, tokens_after: usize) -> Self {
+        Self::new(
+            EventKind::Compacted,
+            serde_json::json!({
+                "mode": mode,
+                "tokens_before": tokens_before,
+                "tokens_after": tokens_after,
+            })
+            .to_string(),
+        )
+    }
+
     /// Converts the chunk to string
     pub fn to_string(&self) -> String {
         // SAFETY: will be never panic
@@ -0,0 +1,22 @@
+use crate::SessionId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The conversation history message found by the search
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryHit {
+    pub session_id: SessionId,
+    /// The session title
+    pub title: Option<String>,
+    /// The message index in the session database
+    pub index: usize,
+    /// The message role (user, assistant or tool)
+    pub role: String,
+    /// The message text snippet around the matched terms
+    pub text: String,
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Whether the message is compressed out of the active session history
+    pub compressed: bool,
+    /// The relevance score (keyword and semantic matches)
+    pub score: f32,
+}
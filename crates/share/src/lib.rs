@@ -19,6 +19,18 @@ pub use session_id::SessionId;
 pub mod session_info;
 pub use session_info::SessionInfo;
 
+pub mod session_summary;
+pub use session_summary::SessionSummary;
+
+pub mod session_export;
+pub use session_export::SessionExport;
+
+pub mod session_prune;
+pub use session_prune::{PruneReason, PrunedSession};
+
+pub mod history_hit;
+pub use history_hit::HistoryHit;
+
 pub mod skill;
 pub use skill::Skill;
 
@@ -26,11 +38,20 @@ pub mod event;
 pub use event::{Event, EventKind, EventTaskInfo};
 
 pub mod user_query;
-pub use user_query::{CompactQuery, HandleQuery, UserSessionsQuery};
+pub use user_query::{
+    CompactQuery, ExportFormat, ExportQuery, ForkQuery, HandleQuery, HistorySearchQuery,
+    PruneQuery, RegenerateQuery, SessionsSort, UserSessionsQuery,
+};
 
 pub mod agent_metadata;
 pub use agent_metadata::AgentMetadata;
 
+pub mod agent_status;
+pub use agent_status::{AgentCrash, AgentStatus};
+
+pub mod agent_info;
+pub use agent_info::AgentInfo;
+
 pub mod status_data;
 pub use status_data::StatusData;
 
@@ -0,0 +1,20 @@
+use crate::SessionId;
+use anylm::api::Message;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The session export data (the lossless history archive)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionExport {
+    /// The exported session id
+    pub id: SessionId,
+    pub title: Option<String>,
+    pub last_activity: Option<DateTime<Utc>>,
+    /// The number of the compressed original messages (the history summary follows them)
+    pub compressed_until: usize,
+    /// The first message index of every conversation turn
+    #[serde(default)]
+    pub turns: Vec<u64>,
+    /// All the session messages (including the compressed originals)
+    pub messages: Vec<Message>,
+}
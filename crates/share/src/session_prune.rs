@@ -0,0 +1,26 @@
+use crate::SessionId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The reason of the session removal by the retention policy
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneReason {
+    /// The session is inactive longer than allowed
+    Age,
+    /// The user has more sessions than allowed
+    Count,
+    /// The user sessions take more disk space than allowed
+    DiskUsage,
+}
+
+/// The session removed (or to be removed) by the retention policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrunedSession {
+    pub id: SessionId,
+    pub title: Option<String>,
+    pub last_activity: DateTime<Utc>,
+    /// The session database size in bytes
+    pub disk_usage: u64,
+    pub reason: PruneReason,
+}
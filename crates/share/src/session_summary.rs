@@ -0,0 +1,17 @@
+use crate::SessionId;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// The session listing entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub id: SessionId,
+    /// The session title (None until the first turn)
+    pub title: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
+    /// The number of the history messages (after compaction)
+    pub message_count: u64,
+    /// The history tokens count (after compaction)
+    pub token_count: u64,
+}
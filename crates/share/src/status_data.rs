@@ -1,10 +1,17 @@
-use crate::AgentMetadata;
+use crate::AgentStatus;
 use serde::{Deserialize, Serialize};
 
 /// The /status response structure
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum StatusData {
-    Error { error: String },
-    Success { agents: Vec<AgentMetadata> },
+    Error {
+        error: String,
+    },
+    Success {
+        agents: Vec<AgentStatus>,
+        /// The number of the sessions open in memory
+        #[serde(default)]
+        open_sessions: usize,
+    },
 }
@@ -1,16 +1,47 @@
 use anylm::api::Message;
 use serde::{Deserialize, Serialize};
 
+/// The user sessions list sorting
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionsSort {
+    /// The recently active sessions first
+    #[default]
+    LastActivity,
+    /// The recently created sessions first
+    Created,
+}
+
 /// The user sessions list query
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSessionsQuery {
     #[serde(default)]
     pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub sort: SessionsSort,
 }
 
 impl UserSessionsQuery {
     pub fn new(limit: usize) -> Self {
-        Self { limit }
+        Self {
+            limit,
+            offset: 0,
+            sort: SessionsSort::default(),
+        }
+    }
+
+    /// Sets the number of the skipped sessions
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the sessions sorting
+    pub fn sort(mut self, sort: SessionsSort) -> Self {
+        self.sort = sort;
+        self
     }
 }
 
@@ -40,3 +71,61 @@ impl CompactQuery {
         }
     }
 }
+
+/// The session export format
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// The lossless archive (can be imported back)
+    #[default]
+    Json,
+    /// The human-readable transcript
+    Markdown,
+}
+
+/// The session export query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// The session fork query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkQuery {
+    /// The history messages before the index are copied (all, if None), the index must start a turn
+    #[serde(default)]
+    pub at: Option<usize>,
+}
+
+/// The answer regeneration data
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegenerateQuery {
+    /// The edited user message (the last one is repeated, if None)
+    #[serde(default)]
+    pub message: Option<Message>,
+}
+
+impl RegenerateQuery {
+    pub fn new(message: Option<Message>) -> Self {
+        Self { message }
+    }
+}
+
+/// The sessions pruning query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruneQuery {
+    /// Only lists the sessions to be removed
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// The user conversation history search query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistorySearchQuery {
+    /// The search text
+    pub q: String,
+    /// The maximum results count (0 = the configured default)
+    #[serde(default)]
+    pub limit: usize,
+}
@@ -0,0 +1,60 @@
+use crate::{prelude::*, skills::SkillName, tools};
+
+const NAME: &str = "system-agent";
+
+const DESCRIPTION: &str = r#"
+Local system management agent. Provides hardware information, live system metrics,
+desktop appearance management, power control, audio volume adjustment, media playback
+control and local music library search.
+"#;
+
+const PROMPT: &str = r#"
+You are the System Manager agent.
+
+You operate exclusively on the local machine and have no knowledge outside the tools
+provided to you.
+
+Use tools whenever system state or hardware interaction is required. Never invent
+information about the system if it can be obtained through a tool.
+
+Be concise, deterministic and task-oriented. Perform only the requested actions and
+report the actual results returned by the tools.
+"#;
+
+/// Builds the system agent with all its skills
+pub fn build() -> Agent {
+    Agent::new(NAME)
+        .version(APP_VERSION)
+        .description(DESCRIPTION)
+        .prompt(PROMPT)
+        .skill(
+            Skill::new(
+                SkillName::SystemInfo,
+                "Hardware information, live system metrics and connected devices.",
+            )
+            .tools(tools::info::tools),
+        )
+        .skill(
+            Skill::new(
+                SkillName::MediaControl,
+                "Audio volume control, media playback (play/pause, stop, next/prev track) and search or play music.",
+            )
+            .tools(tools::media::tools)
+            .tools(tools::audio::tools)
+            .tools(tools::music::tools),
+        )
+        .skill(
+            Skill::new(
+                SkillName::PowerManagement,
+                "Shutdown, reboot, suspend and power scheduling.",
+            )
+            .tools(tools::power::tools),
+        )
+        .skill(
+            Skill::new(
+                SkillName::ThemeSwitching,
+                "Desktop appearance and theme management.",
+            )
+            .tools(tools::theme::tools),
+        )
+}
@@ -1,6 +0,0 @@
-use crate::prelude::*;
-
-/// API: Handles the server ping
-pub async fn handle_ping() -> Response {
-    Response::ok().text("pong")
-}
@@ -1,2 +0,0 @@
-pub mod health;
-pub mod tools;
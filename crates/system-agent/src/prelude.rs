@@ -1,5 +1,6 @@
 #![allow(unused_imports)]
-pub use crate::{APP_NAME, APP_VERSION, error::Error, settings::Settings};
+pub use crate::{APP_NAME, APP_VERSION, settings::Settings};
+pub use ovsy_agent_sdk::{Agent, Skill, tool_args, variants};
 pub use ovsy_share::Event;
 
 pub use std::result::Result as StdResult;
@@ -1,97 +1,8 @@
-use crate::{
-    prelude::*,
-    skills::{Skill, SkillName},
-    tools,
-};
+use crate::prelude::*;
 
 /// The settings instance
 static SETTINGS: State<Config<Settings>> = State::default();
 
-const NAME: &str = "system-agent";
-
-const DESCRIPTION: &str = r#"
-Local system management agent. Provides hardware information, live system metrics,
-desktop appearance management, power control, audio volume adjustment, media playback
-control and local music library search.
-"#;
-
-const PROMPT: &str = r#"
-You are the System Manager agent.
-
-You operate exclusively on the local machine and have no knowledge outside the tools
-provided to you.
-
-Use tools whenever system state or hardware interaction is required. Never invent
-information about the system if it can be obtained through a tool.
-
-Be concise, deterministic and task-oriented. Perform only the requested actions and
-report the actual results returned by the tools.
-"#;
-
-const SKILLS: &[Skill] = &[
-    Skill::new(
-        SkillName::SystemInfo,
-        "Hardware information, live system metrics and connected devices.",
-        tools::info::tools_list,
-    ),
-    Skill::new(
-        SkillName::MediaControl,
-        "Audio volume control, media playback (play/pause, stop, next/prev track) and search or play music.",
-        || {
-            let mut tools = tools::media::tools_list();
-            tools.extend(tools::audio::tools_list());
-            tools.extend(tools::music::tools_list());
-            tools
-        },
-    ),
-    Skill::new(
-        SkillName::PowerManagement,
-        "Shutdown, reboot, suspend and power scheduling.",
-        tools::power::tools_list,
-    ),
-    Skill::new(
-        SkillName::ThemeSwitching,
-        "Desktop appearance and theme management.",
-        tools::theme::tools_list,
-    ),
-];
-
-/// The agent metadata
-#[derive(Clone, Debug, Serialize)]
-pub struct AgentMetadata {
-    pub name: &'static str,
-    pub description: &'static str,
-    pub version: &'static str,
-    pub prompt: &'static str,
-    pub skills: &'static [Skill],
-}
-
-impl AgentMetadata {
-    pub fn tools(&self, skills: Vec<SkillName>) -> Vec<anylm::api::Tool> {
-        if skills.is_empty() {
-            self.skills.iter().flat_map(Skill::tools_list).collect()
-        } else {
-            self.skills
-                .iter()
-                .filter(|skill| skills.contains(&skill.name))
-                .flat_map(Skill::tools_list)
-                .collect()
-        }
-    }
-}
-
-impl ::std::default::Default for AgentMetadata {
-    fn default() -> Self {
-        Self {
-            name: NAME,
-            description: DESCRIPTION,
-            version: APP_VERSION,
-            prompt: PROMPT,
-            skills: SKILLS,
-        }
-    }
-}
-
 /// The behavior options
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BehaviorOptions {
@@ -123,9 +34,6 @@ impl ::std::default::Default for ServerOptions {
 pub struct Settings {
     pub server: ServerOptions,
     pub behavior: BehaviorOptions,
-
-    #[serde(skip, default)]
-    pub metadata: AgentMetadata,
 }
 
 impl Settings {
@@ -1,66 +1,59 @@
 use crate::prelude::*;
-use anylm::api::{Schema, Tool};
 use system_utils::AudioControl;
 
-pub fn tools_list() -> Vec<Tool> {
-    vec![
+/// Registers the audio control tools
+pub fn tools(skill: Skill) -> Skill {
+    skill
         // ________________________________________
         //              SET VOLUME
-        Tool::new(
+        .tool(
             "set_volume",
             "Sets the system audio volume to the specified percentage.",
+            handle_set_volume,
         )
-        .required_property(
-            "volume",
-            Schema::integer("Target audio volume percentage (0-100)."),
-        ),
-        Tool::new(
+        .tool(
             "increase_volume",
             "Increases the system audio volume by the specified percentage.",
+            handle_increase_volume,
         )
-        .required_property(
-            "amount",
-            Schema::integer("Amount to increase the audio volume by."),
-        ),
-        Tool::new(
+        .tool(
             "decrease_volume",
             "Decreases the system audio volume by the specified percentage.",
+            handle_decrease_volume,
         )
-        .required_property(
-            "amount",
-            Schema::integer("Amount to decrease the audio volume by."),
-        ),
         // ________________________________________
         //              GET VOLUME
-        Tool::new(
+        .tool(
             "get_volume",
             "Returns the current system audio volume percentage (0-100).",
-        ),
+            handle_get_volume,
+        )
         // ________________________________________
         //              MUTE/UNMUTE
-        Tool::new(
+        .tool(
             "is_muted",
             "Checks if the system audio is currently muted. Returns a boolean representation.",
-        ),
-        Tool::new(
+            handle_is_muted,
+        )
+        .tool(
             "set_mute",
             "Mutes or unmutes the system audio based on the provided boolean flag.",
+            handle_set_mute,
         )
-        .required_property(
-            "mute",
-            Schema::boolean("True to mute the audio, false to unmute it."),
-        ),
-    ]
 }
 
-#[derive(Deserialize)]
-pub struct SetVolumeAction {
-    volume: u32,
+tool_args! {
+    pub struct SetVolumeAction {
+        /// Target audio volume percentage (0-100).
+        volume: u32,
+    }
 }
 
-#[derive(Deserialize)]
-pub struct DeltaVolumeAction {
-    amount: u32,
+tool_args! {
+    pub struct DeltaVolumeAction {
+        /// Amount to change the audio volume by.
+        amount: u32,
+    }
 }
 
 #[log(skip_all, fields(action))]
@@ -118,9 +111,11 @@ pub async fn handle_get_volume(tx: Sender<Bytes>) -> Result<()> {
     }
 }
 
-#[derive(Deserialize)]
-pub struct MuteAction {
-    mute: bool,
+tool_args! {
+    pub struct MuteAction {
+        /// True to mute the audio, false to unmute it.
+        mute: bool,
+    }
 }
 
 #[log(skip_all, fields(action))]
@@ -1,30 +1,32 @@
 use crate::prelude::*;
-use anylm::api::Tool;
 use system_utils::SystemMonitor;
 
 static SYSTEM_MONITOR: State<SystemMonitor> = State::default();
 
-pub fn tools_list() -> Vec<Tool> {
-    vec![
+/// Registers the system information tools
+pub fn tools(skill: Skill) -> Skill {
+    skill
         // ________________________________________
         //              BASIC INFO
-        Tool::new(
+        .tool(
             "get_system_info",
             "Returns static system information including operating system, CPU, GPU, RAM, motherboard, storage devices, and other hardware details.",
-        ),
+            handle_system_info,
+        )
         // ________________________________________
         //              SYSTEM METRICS
-        Tool::new(
+        .tool(
             "get_system_metrics",
             "Returns current live system metrics including CPU usage, memory usage, temperatures, disk usage, network activity and other runtime statistics.",
-        ),
+            handle_system_metrics,
+        )
         // ________________________________________
         //              DEVICES LIST
-        Tool::new(
+        .tool(
             "get_devices_list",
             "Returns a formatted list of currently connected hardware devices.",
-        ),
-    ]
+            handle_devices_list,
+        )
 }
 
 #[log(skip_all)]
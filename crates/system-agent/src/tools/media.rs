@@ -1,53 +1,67 @@
 use crate::prelude::*;
-use anylm::api::{Schema, Tool};
 use system_utils::MediaControl;
 
-pub fn tools_list() -> Vec<Tool> {
-    vec![
-        #[cfg(target_os = "linux")]
-        Tool::new("media_play", "Starts media playback."),
-        #[cfg(target_os = "linux")]
-        Tool::new("media_pause", "Pauses media playback."),
-        Tool::new("media_play_pause", "Toggles between play and pause."),
-        Tool::new("media_stop", "Stops media playback."),
-        Tool::new("media_next_track", "Skips to the next track."),
-        Tool::new("media_previous_track", "Returns to the previous track."),
-        #[cfg(target_os = "linux")]
-        Tool::new(
+/// Registers the media playback tools
+pub fn tools(skill: Skill) -> Skill {
+    #[cfg(target_os = "linux")]
+    let skill = skill
+        .tool("media_play", "Starts media playback.", handle_media_play)
+        .tool("media_pause", "Pauses media playback.", handle_media_pause);
+
+    let skill = skill
+        .tool(
+            "media_play_pause",
+            "Toggles between play and pause.",
+            handle_media_play_pause,
+        )
+        .tool("media_stop", "Stops media playback.", handle_media_stop)
+        .tool(
+            "media_next_track",
+            "Skips to the next track.",
+            handle_media_next_track,
+        )
+        .tool(
+            "media_previous_track",
+            "Returns to the previous track.",
+            handle_media_previous_track,
+        );
+
+    #[cfg(target_os = "linux")]
+    let skill = skill
+        .tool(
             "media_seek_forward",
             "Seeks forward by the specified number of seconds.",
+            handle_media_seek_forward,
         )
-        .required_property(
-            "seconds",
-            Schema::integer("Number of seconds to seek forward."),
-        ),
-        #[cfg(target_os = "linux")]
-        Tool::new(
+        .tool(
             "media_seek_backward",
             "Seeks backward by the specified number of seconds.",
+            handle_media_seek_backward,
         )
-        .required_property(
-            "seconds",
-            Schema::integer("Number of seconds to seek backward."),
-        ),
-        #[cfg(target_os = "linux")]
-        Tool::new(
+        .tool(
             "media_metadata",
             "Returns metadata for the currently playing media.",
-        ),
-        #[cfg(target_os = "linux")]
-        Tool::new("media_position", "Returns the current playback position."),
-        #[cfg(target_os = "linux")]
-        Tool::new(
+            handle_media_metadata,
+        )
+        .tool(
+            "media_position",
+            "Returns the current playback position.",
+            handle_media_position,
+        )
+        .tool(
             "media_duration",
             "Returns the duration of the current media.",
-        ),
-    ]
+            handle_media_duration,
+        );
+
+    skill
 }
 
-#[derive(Deserialize)]
-pub struct SeekAction {
-    seconds: u32,
+tool_args! {
+    pub struct SeekAction {
+        /// Number of seconds to seek by.
+        seconds: u32,
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -1,49 +1,41 @@
 use crate::prelude::*;
-use anylm::api::{Schema, Tool};
 use music_index::{MusicIndexer, SearchIntent};
 
 static MUSIC_INDEX: State<Option<MusicIndexer>> = State::default();
 
-pub fn tools_list() -> Vec<Tool> {
-    vec![
+/// Registers the music library tools
+pub fn tools(skill: Skill) -> Skill {
+    skill
         // ________________________________________
         //              SEARCH MUSIC
-        Tool::new(
+        .tool(
             "search_music",
             "Searches the local music library without starting playback.",
+            handle_search_music,
         )
-        .optional_property(
-            "query",
-            Schema::string("General natural-language music search query."),
-        )
-        .optional_property("band", Schema::string("Artist or band name."))
-        .optional_property("album", Schema::string("Album title."))
-        .optional_property("track", Schema::string("Track title."))
-        .optional_property("genre", Schema::string("Music genre.")),
         // ________________________________________
         //              PLAY MUSIC
-        Tool::new(
+        .tool(
             "play_music",
             "Searches the local music library and immediately starts playback.",
+            handle_play_music,
         )
-        .optional_property(
-            "query",
-            Schema::string("General natural-language music search query."),
-        )
-        .optional_property("band", Schema::string("Artist or band name."))
-        .optional_property("album", Schema::string("Album title."))
-        .optional_property("track", Schema::string("Track title."))
-        .optional_property("genre", Schema::string("Music genre.")),
-    ]
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MusicAction {
-    pub query: Option<String>,
-    pub band: Option<String>,
-    pub album: Option<String>,
-    pub track: Option<String>,
-    pub genre: Option<String>,
+tool_args! {
+    #[derive(Debug, Clone, Serialize)]
+    pub struct MusicAction {
+        /// General natural-language music search query.
+        pub query: Option<String>,
+        /// Artist or band name.
+        pub band: Option<String>,
+        /// Album title.
+        pub album: Option<String>,
+        /// Track title.
+        pub track: Option<String>,
+        /// Music genre.
+        pub genre: Option<String>,
+    }
 }
 
 async fn music_index() -> Result<MusicIndexer> {
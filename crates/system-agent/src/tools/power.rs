@@ -1,50 +1,40 @@
 use crate::prelude::*;
-use anylm::api::{Schema, Tool};
 use system_utils::{PowerManager, power::PowerMode};
 
-pub fn tools_list() -> Vec<Tool> {
-    vec![
+/// Registers the power management tools
+pub fn tools(skill: Skill) -> Skill {
+    skill
         // ________________________________________
         //              SCHEDULE POWER
-        Tool::new(
+        .tool(
             "schedule_power",
             "Schedules or immediately executes a system power action.",
+            handle_schedule_power,
         )
-        .required_property(
-            "mode",
-            Schema::string("Power action to perform.").variants(set![
-                str!("shutdown"),
-                str!("reboot"),
-                str!("suspend"),
-                str!("lock"),
-                str!("logout"),
-            ]),
-        )
-        .optional_property(
-            "timestamp",
-            Schema::string(
-                "Optional ISO-8601 UTC datetime. If omitted, the action is executed immediately.",
-            ),
-        ),
         // ________________________________________
         //              CANCEL SCHEDULING
-        Tool::new(
+        .tool(
             "cancel_power",
             "Cancels the currently scheduled power action if one exists.",
-        ),
+            handle_cancel_power,
+        )
         // ________________________________________
         //              GET STATUS
-        Tool::new(
+        .tool(
             "get_power_status",
             "Returns the currently scheduled power action and its execution time, if any.",
-        ),
-    ]
+            handle_power_status,
+        )
 }
 
-#[derive(Deserialize)]
-pub struct PowerAction {
-    timestamp: Option<DateTime<Utc>>,
-    mode: PowerMode,
+tool_args! {
+    pub struct PowerAction {
+        /// Optional ISO-8601 UTC datetime. If omitted, the action is executed immediately.
+        timestamp: Option<DateTime<Utc>>,
+        /// Power action to perform.
+        #[schema(variants(&["shutdown", "reboot", "suspend", "lock", "logout"]))]
+        mode: PowerMode,
+    }
 }
 
 #[log(skip_all, fields(action))]
@@ -1,21 +1,24 @@
 use crate::prelude::*;
-use anylm::api::{Schema, Tool};
 use system_utils::{SystemTheme, ThemeStyle};
 
-pub fn tools_list() -> Vec<Tool> {
-    vec![
+/// Registers the theme switching tools
+pub fn tools(skill: Skill) -> Skill {
+    skill
         // ________________________________________
         //              SET THEME
-        Tool::new("set_theme", "Changes the system appearance theme.").required_property(
-            "style",
-            Schema::string("Target theme style.").variants(set![str!("light"), str!("dark")]),
-        ),
-    ]
+        .tool(
+            "set_theme",
+            "Changes the system appearance theme.",
+            handle_set_theme,
+        )
 }
 
-#[derive(Deserialize)]
-pub struct ThemeAction {
-    style: ThemeStyle,
+tool_args! {
+    pub struct ThemeAction {
+        /// Target theme style.
+        #[schema(variants(&["light", "dark"]))]
+        style: ThemeStyle,
+    }
 }
 
 #[log(skip_all, fields(action))]